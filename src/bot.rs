@@ -1,3 +1,4 @@
+use crate::rcon::RconClient;
 use anyhow::Result;
 use azalea::app::{App, Plugin, Update};
 use azalea::ecs::schedule::IntoScheduleConfigs;
@@ -8,6 +9,9 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 
+#[cfg(test)]
+pub(crate) mod mock_server;
+
 // Constants for connection and timing
 const INIT_WAIT_ATTEMPTS: u32 = 50;
 const INIT_WAIT_DELAY_MS: u64 = 100;
@@ -15,6 +19,15 @@ const GAME_STATE_WAIT_ATTEMPTS: u32 = 100;
 const STATE_SYNC_TIMEOUT_MS: u64 = 2_000;
 const CHUNK_SYNC_TIMEOUT_MS: u64 = 10_000;
 const STATE_SYNC_POLL_MS: u64 = 5;
+const RECONNECT_ATTEMPTS: u32 = 5;
+const RECONNECT_BASE_DELAY_MS: u64 = 1_000;
+const QUERY_TIMEOUT_MS: u64 = 3_000;
+/// Offline account the bot joins as
+pub const BOT_NAME: &str = "flintmc_testbot";
+// Scoreboard objective and holders queries store their results in
+const QUERY_OBJECTIVE: &str = "flintmc.query";
+const QUERY_VALUE: &str = "#value";
+const QUERY_FOUND: &str = "#found";
 // Must be configured during Event::Init, before Azalea allocates PartialWorld.
 // Vanilla servers support at most 32 chunks and may clamp this request lower.
 const CLIENT_VIEW_DISTANCE: u8 = 32;
@@ -59,6 +72,18 @@ struct State {
     world_ready_tx: Option<std::sync::mpsc::SyncSender<()>>,
     view_distance: Arc<AtomicU32>,
     simulation_distance: Arc<AtomicU32>,
+    /// This connection's id, and the id of the bot's latest connection
+    connection_id: u64,
+    current_connection: Arc<AtomicU64>,
+}
+
+impl State {
+    /// Mark the bot as disconnected, unless a newer connection has replaced this one
+    fn mark_disconnected(&self) {
+        if self.current_connection.load(Ordering::SeqCst) == self.connection_id {
+            self.in_game.store(false, Ordering::SeqCst);
+        }
+    }
 }
 
 impl Default for State {
@@ -71,10 +96,27 @@ impl Default for State {
             world_ready_tx: None,
             view_distance: Arc::new(AtomicU32::new(0)),
             simulation_distance: Arc::new(AtomicU32::new(0)),
+            connection_id: 0,
+            current_connection: Arc::new(AtomicU64::new(0)),
         }
     }
 }
 
+/// A query whose command stored no score, typically because the server couldn't
+/// parse it
+#[derive(Debug)]
+pub struct NoScore {
+    pub query: String,
+}
+
+impl std::fmt::Display for NoScore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "query returned no score: {}", self.query)
+    }
+}
+
+impl std::error::Error for NoScore {}
+
 #[derive(Clone)]
 pub struct TestBot {
     client: Option<Arc<RwLock<Option<Client>>>>,
@@ -85,9 +127,23 @@ pub struct TestBot {
     next_inventory_owner: Arc<AtomicU64>,
     next_command_ack: Arc<AtomicU64>,
     command_query_lock: Arc<parking_lot::Mutex<()>>,
+    query_objective_ready: Arc<AtomicBool>,
     active_player: Arc<parking_lot::Mutex<ActivePlayer>>,
     view_distance: Arc<AtomicU32>,
     simulation_distance: Arc<AtomicU32>,
+    /// Command transport that returns output directly, when configured
+    rcon: Option<Arc<parking_lot::Mutex<RconClient>>>,
+    /// No client joined: queries read RCON output and there is no client world
+    rcon_only: bool,
+    /// Cleared when an RCON command fails to reach the server, set again on connecting
+    rcon_alive: Arc<AtomicBool>,
+    /// Server address, kept for reconnecting
+    server: Option<String>,
+    /// Version name the server reported in its status response
+    server_version: Arc<RwLock<Option<String>>>,
+    connection_id: Arc<AtomicU64>,
+    /// Block states read in one pass for the assertions of the current tick
+    block_cache: Arc<parking_lot::Mutex<HashMap<[i32; 3], String>>>,
 }
 
 impl Default for TestBot {
@@ -101,9 +157,17 @@ impl Default for TestBot {
             next_inventory_owner: Arc::new(AtomicU64::new(1)),
             next_command_ack: Arc::new(AtomicU64::new(1)),
             command_query_lock: Arc::new(parking_lot::Mutex::new(())),
+            query_objective_ready: Arc::new(AtomicBool::new(false)),
             active_player: Arc::new(parking_lot::Mutex::new(ActivePlayer::default())),
             view_distance: Arc::new(AtomicU32::new(0)),
             simulation_distance: Arc::new(AtomicU32::new(0)),
+            rcon: None,
+            rcon_only: false,
+            rcon_alive: Arc::new(AtomicBool::new(false)),
+            server: None,
+            server_version: Arc::new(RwLock::new(None)),
+            connection_id: Arc::new(AtomicU64::new(0)),
+            block_cache: Arc::new(parking_lot::Mutex::new(HashMap::new())),
         }
     }
 }
@@ -113,7 +177,7 @@ impl TestBot {
         Self::default()
     }
 
-    pub(crate) fn lock_command_query(&self) -> parking_lot::MutexGuard<'_, ()> {
+    fn lock_command_query(&self) -> parking_lot::MutexGuard<'_, ()> {
         self.command_query_lock.lock()
     }
 
@@ -126,7 +190,55 @@ impl TestBot {
     }
 
     pub fn connect(&mut self, server: &str) -> Result<()> {
-        let account = Account::offline("flintmc_testbot");
+        self.server = Some(server.to_string());
+        self.read_server_version(server);
+        self.establish_connection(server)
+    }
+
+    /// Ask the server's status endpoint for its version, without joining
+    pub fn read_server_version(&self, server: &str) {
+        match ping_version(server) {
+            Ok(version) => *self.server_version.write() = Some(version),
+            Err(error) => tracing::warn!("Failed to read the server version: {:#}", error),
+        }
+    }
+
+    /// Version name of the connected server, when its status response could be read
+    pub fn server_version(&self) -> Option<String> {
+        self.server_version.read().clone()
+    }
+
+    /// Connect again after the server dropped the bot, retrying with exponential
+    /// backoff. Clones of this bot share the new connection.
+    pub fn reconnect(&mut self) -> Result<()> {
+        let server = self
+            .server
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Bot was never connected"))?;
+        let mut delay = std::time::Duration::from_millis(RECONNECT_BASE_DELAY_MS);
+        for attempt in 1..=RECONNECT_ATTEMPTS {
+            std::thread::sleep(delay);
+            tracing::info!("Reconnecting to {} (attempt {})", server, attempt);
+            match self.establish_connection(&server) {
+                Ok(()) => return Ok(()),
+                Err(error) => tracing::warn!("Reconnect attempt {} failed: {:#}", attempt, error),
+            }
+            delay *= 2;
+        }
+        anyhow::bail!("failed to reconnect to {server} after {RECONNECT_ATTEMPTS} attempts")
+    }
+
+    /// Whether the bot is in game. Turns false when the server kicks the bot or the
+    /// connection drops. Without a client, the RCON session has to be alive.
+    pub fn is_connected(&self) -> bool {
+        match &self.in_game {
+            Some(in_game) => in_game.load(Ordering::SeqCst),
+            None => self.rcon_only && self.rcon_alive.load(Ordering::SeqCst),
+        }
+    }
+
+    fn establish_connection(&mut self, server: &str) -> Result<()> {
+        let account = Account::offline(BOT_NAME);
 
         tracing::info!("Connecting to server: {}", server);
 
@@ -136,16 +248,28 @@ impl TestBot {
         let (update_tx, update_rx) = std::sync::mpsc::sync_channel(1);
         let (world_ready_tx, world_ready_rx) = std::sync::mpsc::sync_channel(1);
 
-        let state = State {
+        let mut state = State {
             chat_tx: Some(chat_tx),
             ack_tx: Some(ack_tx),
             world_ready_tx: Some(world_ready_tx),
+            view_distance: self.view_distance.clone(),
+            simulation_distance: self.simulation_distance.clone(),
+            connection_id: self.connection_id.fetch_add(1, Ordering::SeqCst) + 1,
+            current_connection: self.connection_id.clone(),
             ..Default::default()
         };
+        // A reconnect reuses the shared handles so existing clones see the new client
+        if let (Some(client), Some(in_game)) = (&self.client, &self.in_game) {
+            *client.write() = None;
+            in_game.store(false, Ordering::SeqCst);
+            state.client_handle = client.clone();
+            state.in_game = in_game.clone();
+        }
+        *self.active_player.lock() = ActivePlayer::default();
+        self.block_cache.lock().clear();
         let client_handle = state.client_handle.clone();
         let in_game = state.in_game.clone();
-        let view_distance = state.view_distance.clone();
-        let simulation_distance = state.simulation_distance.clone();
+        let exit_state = state.clone();
 
         // Spawn the bot in a background thread with LocalSet (required by new azalea version)
         let server_owned = server.to_string();
@@ -178,16 +302,17 @@ impl TestBot {
                             }
                             tracing::info!("Bot world spawned");
                         }
+                        Event::Disconnect(reason) => {
+                            state.mark_disconnected();
+                            tracing::warn!("Bot disconnected: {:?}", reason);
+                        }
                         Event::Chat(m) => {
                             // Extract the message content
                             let message = m.message().to_string();
-                            // Try to get sender name (best effort)
-                            // Fallback: parse "<Name>"
-                            let sender = if message.starts_with('<') {
-                                message.find('>').map(|end| message[1..end].to_string())
-                            } else {
-                                None
-                            };
+                            // Only signed player chat identifies its sender. System
+                            // messages (say, tellraw, plugins) can fake a "<Name>"
+                            // prefix, so they never carry one.
+                            let sender = m.sender_uuid().and_then(|_| m.sender());
 
                             if message.contains("__flintmc_ack_") {
                                 if let Some(tx) = &state.ack_tx {
@@ -247,6 +372,7 @@ impl TestBot {
                 if let AppExit::Error(e) = result {
                     tracing::error!("Bot connection error: {}", e);
                 }
+                exit_state.mark_disconnected();
             });
         });
 
@@ -277,11 +403,9 @@ impl TestBot {
 
         self.client = Some(client_handle);
         self.in_game = Some(in_game);
-        self.chat_rx = Some(Arc::new(parking_lot::Mutex::new(chat_rx)));
-        self.ack_rx = Some(Arc::new(parking_lot::Mutex::new(ack_rx)));
-        self.update_rx = Some(Arc::new(parking_lot::Mutex::new(update_rx)));
-        self.view_distance = view_distance;
-        self.simulation_distance = simulation_distance;
+        share_receiver(&mut self.chat_rx, chat_rx);
+        share_receiver(&mut self.ack_rx, ack_rx);
+        share_receiver(&mut self.update_rx, update_rx);
         tracing::info!("Connected successfully and in game state");
 
         world_ready_rx
@@ -352,10 +476,9 @@ impl TestBot {
                 is_flying: true,
             },
         );
-        // The acknowledgement packet is sent after the abilities packet on the same
-        // connection, so receiving it proves the server has processed flying=true.
-        self.send_command_synced("execute if entity flintmc_testbot run return 1")?;
-        Ok(())
+        // The marker is sent after the abilities packet on the same connection, so
+        // receiving it proves the server has processed flying=true.
+        self.fence_client()
     }
 
     pub fn effective_chunk_distance(&self) -> Result<u32> {
@@ -389,11 +512,50 @@ impl TestBot {
         }
     }
 
+    /// Send every command over RCON from now on. The client keeps observing the world
+    /// and performing player actions.
+    pub fn connect_rcon(&mut self, addr: &str, password: &str) -> Result<()> {
+        tracing::info!("Connecting to RCON: {}", addr);
+        let client = RconClient::connect(addr, password)?;
+        match &self.rcon {
+            // Reconnecting: clones share the session
+            Some(rcon) => *rcon.lock() = client,
+            None => self.rcon = Some(Arc::new(parking_lot::Mutex::new(client))),
+        }
+        self.rcon_alive.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Run commands and queries over RCON alone, for servers that can't accept the
+    /// bot. Nothing observes the world client-side, so player actions are unavailable.
+    pub fn connect_rcon_only(&mut self, addr: &str, password: &str) -> Result<()> {
+        self.connect_rcon(addr, password)?;
+        self.rcon_only = true;
+        Ok(())
+    }
+
+    pub fn is_rcon_only(&self) -> bool {
+        self.rcon_only
+    }
+
     pub fn send_command(&self, command: &str) -> Result<()> {
+        if self.rcon.is_some() {
+            self.rcon_output(command)?;
+            return Ok(());
+        }
+        self.send_client_command(command)
+    }
+
+    /// Send a command as the bot's chat, even when RCON is connected, so it's ordered
+    /// after the packets the client sent before it
+    fn send_client_command(&self, command: &str) -> Result<()> {
         let client_guard = self.get_client()?;
         let client = client_guard
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("Bot not initialized"))?;
+        if !self.is_connected() {
+            anyhow::bail!("Bot disconnected from the server");
+        }
 
         // Add "/" prefix if not present
         let command_with_slash = if command.starts_with('/') {
@@ -406,23 +568,204 @@ impl TestBot {
         Ok(())
     }
 
+    /// Run a command over RCON and return what the server answered
+    fn rcon_output(&self, command: &str) -> Result<String> {
+        let rcon = self
+            .rcon
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("RCON is not connected"))?;
+        let output = rcon.lock().exec(command).inspect_err(|_| {
+            // The session can't be trusted after a failed exchange
+            self.rcon_alive.store(false, Ordering::SeqCst);
+        })?;
+        tracing::debug!("RCON command: {} -> {}", command, output);
+        Ok(output)
+    }
+
+    /// Read a query score from `scoreboard players get` output; empty when unset
+    fn rcon_score(&self, holder: &str) -> Result<String> {
+        let output = self.rcon_output(&format!(
+            "scoreboard players get {holder} {QUERY_OBJECTIVE}"
+        ))?;
+        Ok(parse_score_output(&output, holder)?
+            .map(|score| score.to_string())
+            .unwrap_or_default())
+    }
+
     /// Send a command and wait until the server has processed it. Commands from one
     /// connection are ordered, so receiving the marker also acknowledges every command
     /// sent before it without relying on an arbitrary delay.
     pub fn send_command_synced(&self, command: &str) -> Result<()> {
         self.send_command(command)?;
+        // RCON only answers once the command has run
+        if self.rcon.is_some() {
+            return Ok(());
+        }
+        self.wait_for_ack(command)
+    }
+
+    /// Run an `execute` subcommand (`run time query gametime`, `if entity @e[...]`) and
+    /// return the result it stores. The value is read back through a scoreboard score in
+    /// a marker message, so nothing depends on the server's language or message format.
+    pub fn query_score(&self, subcommand: &str) -> Result<i64> {
+        let _query_guard = self.lock_command_query();
+        self.prepare_query(&[QUERY_VALUE])?;
+        self.send_command(&format!(
+            "execute store result score {QUERY_VALUE} {QUERY_OBJECTIVE} {subcommand}"
+        ))?;
+        let reply = if self.rcon_only {
+            self.rcon_score(QUERY_VALUE)?
+        } else {
+            self.query_marker(subcommand, vec![score_component(QUERY_VALUE)])?
+        };
+        reply.trim().parse().map_err(|_| {
+            anyhow::Error::new(NoScore {
+                query: subcommand.to_string(),
+            })
+        })
+    }
+
+    /// Whether an `execute if` condition (`block 0 64 0 minecraft:stone`) holds
+    pub fn query_condition(&self, condition: &str) -> Result<bool> {
+        Ok(self.query_score(&format!("if {condition}"))? > 0)
+    }
+
+    /// Read `path` from an entity's data. Returns `None` when the entity exists but has
+    /// no value at `path` (e.g. an empty inventory slot).
+    pub fn query_entity_nbt(&self, selector: &str, path: &str) -> Result<Option<String>> {
+        self.query_nbt(&format!("entity {selector}"), "entity", selector, path)
+            .map_err(|error| error.context(format!("querying entity {selector} {path}")))
+    }
+
+    /// Read `path` from the block entity at `pos`
+    pub fn query_block_nbt(&self, pos: [i32; 3], path: &str) -> Result<Option<String>> {
+        let pos = format!("{} {} {}", pos[0], pos[1], pos[2]);
+        // Matching the root against `{}` only succeeds for block entities
+        self.query_nbt(&format!("data block {pos} {{}}"), "block", &pos, path)
+            .map_err(|error| error.context(format!("querying block entity at {pos} {path}")))
+    }
+
+    /// Store whether `exists` matches and how many elements `path` matches, then read
+    /// both scores and the value itself through an `nbt` text component.
+    fn query_nbt(
+        &self,
+        exists: &str,
+        source: &str,
+        target: &str,
+        path: &str,
+    ) -> Result<Option<String>> {
+        let _query_guard = self.lock_command_query();
+        self.prepare_query(&[QUERY_FOUND, QUERY_VALUE])?;
+        let data = format!("data {source} {target} {path}");
+        self.send_command(&format!(
+            "execute store success score {QUERY_FOUND} {QUERY_OBJECTIVE} if {exists}"
+        ))?;
+        self.send_command(&format!(
+            "execute store result score {QUERY_VALUE} {QUERY_OBJECTIVE} if {data}"
+        ))?;
+        if self.rcon_only {
+            let found = self.rcon_score(QUERY_FOUND)?;
+            let matches = self.rcon_score(QUERY_VALUE)?;
+            let value = match matches.parse::<u32>() {
+                Ok(matches) if matches > 0 => parse_data_output(
+                    &self.rcon_output(&format!("data get {source} {target} {path}"))?,
+                )?,
+                _ => String::new(),
+            };
+            return parse_nbt_reply(&format!("{found},{matches}:{value}"));
+        }
+        let reply = self.query_marker(
+            &data,
+            vec![
+                score_component(QUERY_FOUND),
+                serde_json::json!(","),
+                score_component(QUERY_VALUE),
+                serde_json::json!(":"),
+                serde_json::json!({ "nbt": path, source: target }),
+            ],
+        )?;
+        parse_nbt_reply(&reply)
+    }
+
+    /// Create the scoreboard objective queries store their results in, and clear the
+    /// `holders` the query reads so a command that fails to run can't leave a stale
+    /// result behind. Adding an existing objective only fails, so this is safe to
+    /// repeat after a reconnect.
+    fn prepare_query(&self, holders: &[&str]) -> Result<()> {
+        if !self.query_objective_ready.swap(true, Ordering::SeqCst) {
+            self.send_command(&format!(
+                "scoreboard objectives add {QUERY_OBJECTIVE} dummy"
+            ))?;
+        }
+        for holder in holders {
+            self.send_command(&format!(
+                "scoreboard players reset {holder} {QUERY_OBJECTIVE}"
+            ))?;
+        }
+        Ok(())
+    }
+
+    /// Send a marker followed by `components` and return the text after the marker
+    fn query_marker(&self, query: &str, components: Vec<serde_json::Value>) -> Result<String> {
         let id = self.next_command_ack.fetch_add(1, Ordering::Relaxed);
-        let marker = format!("__flintmc_ack_{id}__");
+        let marker = format!("__flintmc_ack_q{id}__");
+        let mut message = vec![serde_json::json!(marker)];
+        message.extend(components);
         self.send_command(&format!(
-            "tellraw flintmc_testbot {{\"text\":\"{marker}\"}}"
+            "tellraw flintmc_testbot {}",
+            serde_json::Value::Array(message)
         ))?;
 
+        let deadline =
+            std::time::Instant::now() + std::time::Duration::from_millis(QUERY_TIMEOUT_MS);
+        while std::time::Instant::now() < deadline {
+            let Some(ack_rx) = &self.ack_rx else {
+                anyhow::bail!("command acknowledgement channel is unavailable");
+            };
+            if !self.is_connected() {
+                anyhow::bail!("Bot disconnected from the server");
+            }
+            if let Ok(reply) = ack_rx
+                .lock()
+                .recv_timeout(std::time::Duration::from_millis(STATE_SYNC_POLL_MS))
+                && let Some((_, value)) = reply.split_once(&marker)
+            {
+                return Ok(value.to_string());
+            }
+        }
+        anyhow::bail!("timed out waiting for the result of: {query}")
+    }
+
+    /// Wait for a chat marker sent after `command`. Commands and packets to the client
+    /// are ordered, so the marker also fences everything sent before it.
+    fn wait_for_ack(&self, command: &str) -> Result<()> {
+        self.wait_for_marker(command, Self::send_command)
+    }
+
+    /// Wait until the server has handled every packet the client sent so far. The
+    /// marker goes out over the game connection, behind those packets; an RCON reply
+    /// says nothing about them.
+    pub fn fence_client(&self) -> Result<()> {
+        self.wait_for_marker("client packets", Self::send_client_command)
+    }
+
+    fn wait_for_marker(&self, command: &str, send: fn(&Self, &str) -> Result<()>) -> Result<()> {
+        let id = self.next_command_ack.fetch_add(1, Ordering::Relaxed);
+        let marker = format!("__flintmc_ack_{id}__");
+        send(
+            self,
+            &format!("tellraw flintmc_testbot {{\"text\":\"{marker}\"}}"),
+        )?;
+
         let deadline =
             std::time::Instant::now() + std::time::Duration::from_millis(STATE_SYNC_TIMEOUT_MS);
         while std::time::Instant::now() < deadline {
             let Some(ack_rx) = &self.ack_rx else {
                 anyhow::bail!("command acknowledgement channel is unavailable");
             };
+            if !self.is_connected() {
+                anyhow::bail!("Bot disconnected from the server");
+            }
             if ack_rx
                 .lock()
                 .recv_timeout(std::time::Duration::from_millis(STATE_SYNC_POLL_MS))
@@ -438,7 +781,21 @@ impl TestBot {
     /// emitted after earlier commands/ticks. After observing it, wait for a subsequent
     /// Azalea ECS update so packet-driven world mutations are visible to readers.
     pub fn sync_client_world(&self) -> Result<()> {
-        self.send_command_synced("execute if entity flintmc_testbot run return 1")?;
+        // RCON answers once commands have run, and there's no client world to catch up
+        if self.rcon_only {
+            return Ok(());
+        }
+        // Always fence through chat: an RCON reply says nothing about which packets
+        // the client has seen.
+        let command = "execute if entity flintmc_testbot run return 1";
+        self.send_command(command)?;
+        self.wait_for_ack(command)?;
+        self.wait_for_world_update()
+    }
+
+    /// Wait for the next Azalea ECS update, after which packets handled before it are
+    /// visible to readers
+    fn wait_for_world_update(&self) -> Result<()> {
         let Some(update_rx) = &self.update_rx else {
             anyhow::bail!("Azalea update channel is unavailable");
         };
@@ -487,6 +844,11 @@ impl TestBot {
         })?;
         let _ = updates.blocking_recv();
         let _ = updates.blocking_recv();
+        if rot.is_some() {
+            // The new direction is sent as a movement packet the server must have
+            // handled before the bot interacts along it
+            self.fence_client()?;
+        }
         Ok(())
     }
 
@@ -513,10 +875,11 @@ impl TestBot {
         let _ = updates.blocking_recv();
         let _ = ticks.blocking_recv();
         let _ = ticks.blocking_recv();
-        // The acknowledgement is sent after the interaction packet on the same
-        // connection. Waiting for it and a subsequent ECS update makes both world
-        // and inventory changes visible without requiring a visible state change.
-        self.sync_client_world()
+        // The marker is sent after the interaction packet on the same connection.
+        // Waiting for it and a subsequent ECS update makes both world and inventory
+        // changes visible without requiring a visible state change.
+        self.fence_client()?;
+        self.wait_for_world_update()
     }
 
     pub fn select_hotbar(&self, slot: u8) -> Result<()> {
@@ -539,7 +902,9 @@ impl TestBot {
         })?;
         let _ = ticks.blocking_recv();
         let _ = ticks.blocking_recv();
-        Ok(())
+        // The slot change goes out as a packet; the server must see it before the
+        // next interaction is judged against the held item
+        self.fence_client()
     }
 
     pub fn allocate_inventory_owner(&self) -> u64 {
@@ -772,6 +1137,54 @@ impl TestBot {
         }
     }
 
+    /// Fence once, then read every position from the client's world under a single
+    /// lock. Later `cached_block` calls answer from this pass until the cache is
+    /// cleared. Positions whose chunks aren't loaded are left out. Returns how many
+    /// positions were cached.
+    pub fn prefetch_blocks(&self, positions: &[[i32; 3]]) -> Result<usize> {
+        self.sync_client_world()?;
+        let states = self.get_blocks(positions)?;
+        let mut cache = self.block_cache.lock();
+        cache.clear();
+        for (pos, state) in positions.iter().zip(states) {
+            if let Some(state) = state {
+                cache.insert(*pos, state);
+            }
+        }
+        Ok(cache.len())
+    }
+
+    /// Read several blocks under one world lock; `None` where the chunk isn't loaded
+    pub fn get_blocks(&self, positions: &[[i32; 3]]) -> Result<Vec<Option<String>>> {
+        let client_guard = self.get_client()?;
+        let client = client_guard
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Bot not initialized"))?;
+
+        let Ok(world_lock) = client.world() else {
+            return Ok(vec![None; positions.len()]);
+        };
+        let world = world_lock.read();
+        Ok(positions
+            .iter()
+            .map(|pos| {
+                world
+                    .get_block_state(azalea::BlockPos::new(pos[0], pos[1], pos[2]))
+                    .map(|state| format!("{:?}", state))
+            })
+            .collect())
+    }
+
+    /// A block state from the last `prefetch_blocks`
+    pub fn cached_block(&self, pos: [i32; 3]) -> Option<String> {
+        self.block_cache.lock().get(&pos).cloned()
+    }
+
+    /// Forget prefetched block states, e.g. once anything may have changed the world
+    pub fn clear_block_cache(&self) {
+        self.block_cache.lock().clear();
+    }
+
     /// Get the bot's current position
     pub fn get_position(&self) -> Result<[i32; 3]> {
         let client_guard = self.get_client()?;
@@ -805,7 +1218,7 @@ pub fn slot_to_minecraft_name(slot: PlayerSlot) -> &'static str {
     }
 }
 
-fn all_player_slots() -> [PlayerSlot; 14] {
+pub(crate) fn all_player_slots() -> [PlayerSlot; 14] {
     [
         PlayerSlot::Hotbar1,
         PlayerSlot::Hotbar2,
@@ -831,3 +1244,357 @@ fn normalized_item_id(id: &str) -> String {
         format!("minecraft:{id}")
     }
 }
+
+/// Ask the server for its version through a status ping, before logging in
+fn ping_version(server: &str) -> Result<String> {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let status = rt
+        .block_on(azalea::ping::ping_server(server))
+        .map_err(|error| anyhow::anyhow!("status ping to {server} failed: {error}"))?;
+    Ok(status.version.name)
+}
+
+/// Store `receiver` in `slot`, inside the existing shared mutex if there is one
+fn share_receiver<T>(slot: &mut Option<Arc<parking_lot::Mutex<T>>>, receiver: T) {
+    match slot {
+        Some(shared) => *shared.lock() = receiver,
+        None => *slot = Some(Arc::new(parking_lot::Mutex::new(receiver))),
+    }
+}
+
+fn score_component(holder: &str) -> serde_json::Value {
+    serde_json::json!({ "score": { "name": holder, "objective": QUERY_OBJECTIVE } })
+}
+
+/// The score in `scoreboard players get` output (`#value has 5 [flintmc.query]`), or
+/// `None` when the reply names `holder` and the objective without a score (`none is
+/// set`). Any other reply is an error rather than a missing score.
+fn parse_score_output(output: &str, holder: &str) -> Result<Option<i64>> {
+    let score = output
+        .rsplit_once('[')
+        .and_then(|(before, _)| before.split_whitespace().last())
+        .and_then(|score| score.parse().ok());
+    if score.is_some() {
+        return Ok(score);
+    }
+    if output.contains(holder) && output.contains(QUERY_OBJECTIVE) {
+        return Ok(None);
+    }
+    anyhow::bail!("unrecognized scoreboard reply for {holder}: {output:?}")
+}
+
+/// The value in `data get` output (`... has the following entity data: 5`)
+fn parse_data_output(output: &str) -> Result<String> {
+    output
+        .split_once("data: ")
+        .map(|(_, value)| value.trim().to_string())
+        .ok_or_else(|| anyhow::anyhow!("unrecognized data reply: {output:?}"))
+}
+
+/// Parse `<found>,<matches>:<value>` from an NBT query marker
+fn parse_nbt_reply(reply: &str) -> Result<Option<String>> {
+    let malformed = || anyhow::anyhow!("malformed query reply: {reply}");
+    let (found, rest) = reply.split_once(',').ok_or_else(malformed)?;
+    let (matches, value) = rest.split_once(':').ok_or_else(malformed)?;
+    if found.trim() != "1" {
+        anyhow::bail!("not found");
+    }
+    match matches.trim().parse::<u32>().map_err(|_| malformed())? {
+        0 => Ok(None),
+        _ => Ok(Some(value.trim().to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock_server::{MockServer, SIMULATION_DISTANCE};
+    use super::{QUERY_TIMEOUT_MS, parse_data_output, parse_nbt_reply, parse_score_output};
+
+    #[test]
+    fn test_parse_nbt_reply() {
+        assert_eq!(
+            parse_nbt_reply("1,1:[1.5d, 64.0d, -2.5d]").unwrap(),
+            Some("[1.5d, 64.0d, -2.5d]".to_string())
+        );
+        assert_eq!(parse_nbt_reply("1,0:").unwrap(), None);
+        assert!(parse_nbt_reply("0,0:").is_err());
+        assert!(parse_nbt_reply("1:").is_err());
+    }
+
+    #[test]
+    fn test_query_score_reads_marker_reply() {
+        let mut server = MockServer::start().unwrap();
+        let bot = server.bot().unwrap();
+        bot.send_command("tick step 5").unwrap();
+        assert_eq!(bot.query_score("run time query gametime").unwrap(), 5);
+        // The objective is only created once
+        bot.query_score("run time query gametime").unwrap();
+        let created = server
+            .commands()
+            .iter()
+            .filter(|command| command.starts_with("scoreboard objectives add"))
+            .count();
+        assert_eq!(created, 1);
+        // Only the holder the query reads is cleared
+        let resets: Vec<_> = server
+            .commands()
+            .into_iter()
+            .filter(|command| command.starts_with("scoreboard players reset"))
+            .collect();
+        assert!(
+            resets
+                .iter()
+                .all(|command| command == "scoreboard players reset #value flintmc.query"),
+            "{resets:?}"
+        );
+    }
+
+    #[test]
+    fn test_query_nbt_distinguishes_missing_value_and_target() {
+        let mut server = MockServer::start().unwrap();
+        server.set_entity_data("@e[tag=a,limit=1]", "Pos", "[1.5d, 64.0d, -2.5d]");
+        server.set_block_entity_data([1, 2, 3], "Items", "[]");
+        let bot = server.bot().unwrap();
+
+        assert_eq!(
+            bot.query_entity_nbt("@e[tag=a,limit=1]", "Pos").unwrap(),
+            Some("[1.5d, 64.0d, -2.5d]".to_string())
+        );
+        assert_eq!(
+            bot.query_entity_nbt("@e[tag=a,limit=1]", "Rotation")
+                .unwrap(),
+            None
+        );
+        assert!(bot.query_entity_nbt("@e[tag=b,limit=1]", "Pos").is_err());
+
+        assert_eq!(
+            bot.query_block_nbt([1, 2, 3], "Items").unwrap(),
+            Some("[]".to_string())
+        );
+        assert!(bot.query_block_nbt([0, 0, 0], "Items").is_err());
+    }
+
+    #[test]
+    fn test_sync_client_world_waits_for_marker_and_update() {
+        let mut server = MockServer::start().unwrap();
+        let bot = server.bot().unwrap();
+        bot.sync_client_world().unwrap();
+
+        server.drop_acks(true);
+        let error = bot.sync_client_world().unwrap_err();
+        assert!(error.to_string().contains("timed out"), "{error}");
+    }
+
+    #[test]
+    fn test_waits_fail_fast_after_disconnect() {
+        let mut server = MockServer::start().unwrap();
+        let bot = server.bot().unwrap();
+        server.disconnect();
+        assert!(!bot.is_connected());
+
+        let start = std::time::Instant::now();
+        let error = bot.query_score("run time query gametime").unwrap_err();
+        assert!(error.to_string().contains("disconnected"), "{error}");
+        assert!(start.elapsed() < std::time::Duration::from_millis(QUERY_TIMEOUT_MS));
+    }
+
+    #[test]
+    fn test_rcon_only_queries_read_command_output() {
+        let server = MockServer::start().unwrap();
+        server.set_entity_data("@e[tag=a,limit=1]", "Pos", "[1.5d, 64.0d, -2.5d]");
+        let bot = server.rcon_only_bot().unwrap();
+        assert!(bot.is_connected());
+
+        bot.send_command("tick step 3").unwrap();
+        assert_eq!(bot.query_score("run time query gametime").unwrap(), 3);
+        assert_eq!(
+            bot.query_entity_nbt("@e[tag=a,limit=1]", "Pos").unwrap(),
+            Some("[1.5d, 64.0d, -2.5d]".to_string())
+        );
+        assert_eq!(
+            bot.query_entity_nbt("@e[tag=a,limit=1]", "Rotation")
+                .unwrap(),
+            None
+        );
+        assert!(bot.query_entity_nbt("@e[tag=b,limit=1]", "Pos").is_err());
+
+        bot.send_command("setblock 0 64 0 minecraft:stone").unwrap();
+        assert!(bot.query_condition("block 0 64 0 minecraft:stone").unwrap());
+        assert!(!bot.query_condition("block 0 64 0 minecraft:dirt").unwrap());
+        bot.sync_client_world().unwrap();
+    }
+
+    #[test]
+    fn test_rcon_only_bot_notices_a_dropped_session() {
+        let server = MockServer::start().unwrap();
+        let mut bot = server.rcon_only_bot().unwrap();
+        assert!(bot.is_connected());
+
+        server.drop_rcon();
+        assert!(bot.query_score("run time query gametime").is_err());
+        assert!(!bot.is_connected());
+
+        bot.connect_rcon_only(server.rcon_addr(), "").unwrap();
+        assert!(bot.is_connected());
+        assert_eq!(bot.query_score("run time query gametime").unwrap(), 0);
+    }
+
+    /// The block at `pos` in the bot's client world, like `minecraft:stone`
+    fn client_block(bot: &super::TestBot, pos: [i32; 3]) -> String {
+        let state = bot.get_block(pos).unwrap().expect("chunk is loaded");
+        crate::executor::block::extract_block_id(&state)
+    }
+
+    #[test]
+    fn test_connect_joins_and_settles_at_the_test_origin() {
+        let server = MockServer::start().unwrap();
+        let bot = server.connect_bot().unwrap();
+        assert!(bot.is_connected());
+        assert_eq!(
+            bot.server_version().as_deref(),
+            Some(azalea::protocol::packets::VERSION_NAME)
+        );
+        // The login's simulation distance caps its chunk radius
+        assert_eq!(
+            bot.detected_distances(),
+            (SIMULATION_DISTANCE, SIMULATION_DISTANCE)
+        );
+        assert_eq!(bot.get_position().unwrap(), [0, 64, 0]);
+        assert!(
+            server
+                .commands()
+                .iter()
+                .any(|command| command.ends_with("tp flintmc_testbot 0.5 64 0.5 0 0"))
+        );
+        // The origin platform arrives as a block update after the chunk batch
+        bot.wait_for_block_chunk([0, 63, 0]).unwrap();
+        bot.sync_client_world().unwrap();
+        assert_eq!(client_block(&bot, [0, 63, 0]), "minecraft:bedrock");
+    }
+
+    #[test]
+    fn test_commands_and_queries_over_chat() {
+        let server = MockServer::start().unwrap();
+        let bot = server.connect_bot().unwrap();
+
+        bot.send_command_synced("tick step 5").unwrap();
+        assert_eq!(server.gametime(), 5);
+        assert_eq!(bot.query_score("run time query gametime").unwrap(), 5);
+
+        // Only markers are acknowledgements; other system chat has no sender
+        bot.send_command("tellraw flintmc_testbot \"hello\"")
+            .unwrap();
+        assert_eq!(
+            bot.recv_chat_timeout(std::time::Duration::from_secs(2)),
+            Some((None, "hello".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_block_updates_reach_the_client_world() {
+        let server = MockServer::start().unwrap();
+        let bot = server.connect_bot().unwrap();
+        bot.wait_for_block_chunk([2, 64, 2]).unwrap();
+        assert_eq!(client_block(&bot, [2, 64, 2]), "minecraft:air");
+
+        bot.send_command("setblock 2 64 2 minecraft:stone").unwrap();
+        bot.sync_client_world().unwrap();
+        assert_eq!(client_block(&bot, [2, 64, 2]), "minecraft:stone");
+
+        server.set_block([2, 64, 2], "minecraft:dirt");
+        bot.sync_client_world().unwrap();
+        assert_eq!(client_block(&bot, [2, 64, 2]), "minecraft:dirt");
+    }
+
+    #[test]
+    fn test_teleport_and_interact_are_fenced() {
+        let server = MockServer::start().unwrap();
+        let bot = server.connect_bot().unwrap();
+        bot.send_command_synced("setblock 3 64 3 minecraft:stone")
+            .unwrap();
+        bot.sync_client_world().unwrap();
+
+        // Looking straight down at the stone
+        bot.teleport([3.5, 65.0, 3.5], Some([0.0, 90.0])).unwrap();
+        assert_eq!(bot.get_position().unwrap(), [3, 65, 3]);
+        bot.interact().unwrap();
+        // The fence returns only once the server has handled the click
+        assert_eq!(server.interactions(), vec![[3, 64, 3]]);
+    }
+
+    #[test]
+    fn test_lost_messages_time_out() {
+        let server = MockServer::start().unwrap();
+        let bot = server.connect_bot().unwrap();
+
+        server.drop_acks(true);
+        let error = bot.send_command_synced("tick step").unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("timed out waiting for command acknowledgement"),
+            "{error}"
+        );
+        server.drop_acks(false);
+
+        server.ignore_teleports(true);
+        let error = bot.teleport([1.5, 70.0, 1.5], None).unwrap_err();
+        assert!(
+            error.to_string().contains("Timed out waiting for teleport"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_connect_fails_when_the_login_is_refused() {
+        let server = MockServer::start().unwrap();
+        server.refuse_logins(true);
+        assert!(server.connect_bot().is_err());
+        assert!(server.commands().is_empty());
+    }
+
+    #[test]
+    fn test_disconnect_is_noticed_by_a_joined_bot() {
+        let server = MockServer::start().unwrap();
+        let bot = server.connect_bot().unwrap();
+        server.disconnect();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while bot.is_connected() && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(!bot.is_connected());
+        assert!(bot.send_command("tick step").is_err());
+    }
+
+    #[test]
+    fn test_parse_rcon_output() {
+        assert_eq!(
+            parse_score_output("#value has 12 [flintmc.query]", "#value").unwrap(),
+            Some(12)
+        );
+        assert_eq!(
+            parse_score_output("#value has -3 [flintmc.query]", "#value").unwrap(),
+            Some(-3)
+        );
+        assert_eq!(
+            parse_score_output(
+                "Can't get value of #value for flintmc.query; none is set",
+                "#value"
+            )
+            .unwrap(),
+            None
+        );
+        assert!(
+            parse_score_output("Unknown scoreboard objective 'flintmc.query'", "#value").is_err()
+        );
+        assert!(parse_score_output("", "#value").is_err());
+        assert_eq!(
+            parse_data_output("1, 64, 3 has the following block data: [{id: \"minecraft:stone\"}]")
+                .unwrap(),
+            "[{id: \"minecraft:stone\"}]"
+        );
+        assert!(parse_data_output("Found no elements matching Pos").is_err());
+    }
+}
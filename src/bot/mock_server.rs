@@ -0,0 +1,553 @@
+//! A stand-in server for testing `TestBot` without Minecraft.
+//!
+//! The game listener speaks the real protocol through Azalea's connection types: it
+//! accepts an offline login, sends a small overworld as one chunk batch, and answers
+//! the bot's chat commands. `tellraw` comes back as system chat, `setblock` and blocks
+//! changed with [`MockServer::set_block`] as block updates, and `tp` as a position
+//! change, so [`MockServer::connect_bot`] drives a real `TestBot::connect`.
+//!
+//! Commands run against a small model of the server (game time, scoreboard scores,
+//! entity and block entity data), also reachable over a real RCON socket. The RCON
+//! bot from [`MockServer::bot`] has no client: markers go straight to its
+//! acknowledgement channel and a background thread stands in for Azalea's updates,
+//! which is quicker for tests that only need commands and queries.
+
+mod game;
+
+pub(crate) use game::SIMULATION_DISTANCE;
+
+use super::{AckReceiver, BOT_NAME, TestBot, UpdateReceiver};
+use crate::rcon::{self, Packet, RconClient};
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Sender, SyncSender};
+use tokio::sync::mpsc::UnboundedSender;
+
+const ACCEPT_POLL_MS: u64 = 5;
+const UPDATE_INTERVAL_MS: u64 = 10;
+
+#[derive(Default)]
+struct MockWorld {
+    gametime: u64,
+    objectives: HashSet<String>,
+    /// Scores of the (single) query objective, by holder
+    scores: HashMap<String, i64>,
+    /// Data paths per entity selector
+    entities: HashMap<String, HashMap<String, String>>,
+    /// Data paths per block entity
+    block_entities: HashMap<[i32; 3], HashMap<String, String>>,
+    /// Block states set with `setblock`; everything else is air
+    blocks: HashMap<[i32; 3], String>,
+    commands: Vec<String>,
+    /// Blocks the bot used, in order
+    interactions: Vec<[i32; 3]>,
+}
+
+/// What the server sends a joined client after running a command
+#[derive(Clone)]
+enum Outbound {
+    Chat(String),
+    Block([i32; 3], String),
+    Teleport([f64; 3], Option<[f32; 2]>),
+    Disconnect,
+}
+
+pub(crate) struct MockServer {
+    addr: String,
+    game_addr: String,
+    handler: Handler,
+    in_game: Arc<AtomicBool>,
+    stopped: Arc<AtomicBool>,
+    receivers: Option<(AckReceiver, UpdateReceiver)>,
+}
+
+impl MockServer {
+    pub(crate) fn start() -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?.to_string();
+        let game_listener = TcpListener::bind("127.0.0.1:0")?;
+        game_listener.set_nonblocking(true)?;
+        let game_addr = game_listener.local_addr()?.to_string();
+
+        let in_game = Arc::new(AtomicBool::new(true));
+        let stopped = Arc::new(AtomicBool::new(false));
+        let (ack_tx, ack_rx) = std::sync::mpsc::channel();
+        let (update_tx, update_rx) = std::sync::mpsc::sync_channel(1);
+
+        let handler = Handler {
+            world: Arc::new(Mutex::new(MockWorld::default())),
+            ack_tx,
+            clients: Arc::new(Mutex::new(Vec::new())),
+            drop_acks: Arc::new(AtomicBool::new(false)),
+            ignore_teleports: Arc::new(AtomicBool::new(false)),
+            refuse_logins: Arc::new(AtomicBool::new(false)),
+            rcon_sessions: Arc::new(Mutex::new(Vec::new())),
+        };
+        let rcon_handler = handler.clone();
+        let accept_stopped = stopped.clone();
+        std::thread::spawn(move || {
+            while !accept_stopped.load(Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let handler = rcon_handler.clone();
+                        std::thread::spawn(move || handler.serve_rcon(stream));
+                    }
+                    Err(_) => std::thread::sleep(std::time::Duration::from_millis(ACCEPT_POLL_MS)),
+                }
+            }
+        });
+
+        let game_handler = handler.clone();
+        let game_stopped = stopped.clone();
+        std::thread::spawn(move || game::listen(game_listener, game_handler, game_stopped));
+
+        let update_stopped = stopped.clone();
+        let update_in_game = in_game.clone();
+        std::thread::spawn(move || spin_updates(update_tx, update_stopped, update_in_game));
+
+        Ok(Self {
+            addr,
+            game_addr,
+            handler,
+            in_game,
+            stopped,
+            receivers: Some((ack_rx, update_rx)),
+        })
+    }
+
+    /// Address of the game listener, for `TestBot::connect`
+    pub(crate) fn game_addr(&self) -> &str {
+        &self.game_addr
+    }
+
+    /// Address of the RCON listener
+    pub(crate) fn rcon_addr(&self) -> &str {
+        &self.addr
+    }
+
+    /// A bot that joins over the game protocol, through `TestBot::connect`
+    pub(crate) fn connect_bot(&self) -> Result<TestBot> {
+        let mut bot = TestBot::new();
+        bot.connect(&self.game_addr)?;
+        Ok(bot)
+    }
+
+    /// A bot wired to this server without joining, sending commands over RCON. Only
+    /// one can be attached; clone it to share the connection.
+    pub(crate) fn bot(&mut self) -> Result<TestBot> {
+        let (ack_rx, update_rx) = self
+            .receivers
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("a bot is already attached to this server"))?;
+        Ok(TestBot {
+            in_game: Some(self.in_game.clone()),
+            ack_rx: Some(Arc::new(Mutex::new(ack_rx))),
+            update_rx: Some(Arc::new(Mutex::new(update_rx))),
+            rcon: Some(Arc::new(Mutex::new(RconClient::connect(&self.addr, "")?))),
+            ..TestBot::default()
+        })
+    }
+
+    /// A bot that never joins and reads every query result from RCON output
+    pub(crate) fn rcon_only_bot(&self) -> Result<TestBot> {
+        let mut bot = TestBot::new();
+        bot.connect_rcon_only(&self.addr, "")?;
+        Ok(bot)
+    }
+
+    pub(crate) fn set_entity_data(&self, selector: &str, path: &str, value: &str) {
+        self.handler
+            .world
+            .lock()
+            .entities
+            .entry(selector.to_string())
+            .or_default()
+            .insert(path.to_string(), value.to_string());
+    }
+
+    pub(crate) fn set_block_entity_data(&self, pos: [i32; 3], path: &str, value: &str) {
+        self.handler
+            .world
+            .lock()
+            .block_entities
+            .entry(pos)
+            .or_default()
+            .insert(path.to_string(), value.to_string());
+    }
+
+    /// Change a block behind the bot's back, as another player or redstone would
+    pub(crate) fn set_block(&self, pos: [i32; 3], state: &str) {
+        self.handler
+            .world
+            .lock()
+            .blocks
+            .insert(pos, state.to_string());
+        self.handler
+            .broadcast(Outbound::Block(pos, state.to_string()));
+    }
+
+    pub(crate) fn gametime(&self) -> u64 {
+        self.handler.world.lock().gametime
+    }
+
+    /// Every command received so far, in order
+    pub(crate) fn commands(&self) -> Vec<String> {
+        self.handler.world.lock().commands.clone()
+    }
+
+    /// Every block a joined bot used, in order
+    pub(crate) fn interactions(&self) -> Vec<[i32; 3]> {
+        self.handler.world.lock().interactions.clone()
+    }
+
+    /// Stop delivering markers to the bot, as if chat were lost
+    pub(crate) fn drop_acks(&self, drop: bool) {
+        self.handler.drop_acks.store(drop, Ordering::SeqCst);
+    }
+
+    /// Run `tp` without moving a joined bot, as if the position packet were lost
+    pub(crate) fn ignore_teleports(&self, ignore: bool) {
+        self.handler
+            .ignore_teleports
+            .store(ignore, Ordering::SeqCst);
+    }
+
+    /// Turn bots away during login, like a full or whitelisted server
+    pub(crate) fn refuse_logins(&self, refuse: bool) {
+        self.handler.refuse_logins.store(refuse, Ordering::SeqCst);
+    }
+
+    /// Drop every bot from the game; RCON keeps working, like a kicked player
+    pub(crate) fn disconnect(&self) {
+        self.in_game.store(false, Ordering::SeqCst);
+        self.handler.broadcast(Outbound::Disconnect);
+        self.handler.clients.lock().clear();
+    }
+
+    /// Close every RCON connection, like a server restart. New connections are accepted.
+    pub(crate) fn drop_rcon(&self) {
+        for session in self.handler.rcon_sessions.lock().drain(..) {
+            let _ = session.shutdown(std::net::Shutdown::Both);
+        }
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.handler.broadcast(Outbound::Disconnect);
+    }
+}
+
+/// Send an update after every interval, like Azalea's ECS schedule while in game
+fn spin_updates(update_tx: SyncSender<()>, stopped: Arc<AtomicBool>, in_game: Arc<AtomicBool>) {
+    while !stopped.load(Ordering::SeqCst) {
+        if in_game.load(Ordering::SeqCst) {
+            let _ = update_tx.try_send(());
+        }
+        std::thread::sleep(std::time::Duration::from_millis(UPDATE_INTERVAL_MS));
+    }
+}
+
+/// Runs commands from either listener against the model and tells joined clients
+/// what changed
+#[derive(Clone)]
+struct Handler {
+    world: Arc<Mutex<MockWorld>>,
+    ack_tx: Sender<String>,
+    /// Joined clients, in the order they finished logging in
+    clients: Arc<Mutex<Vec<UnboundedSender<Outbound>>>>,
+    drop_acks: Arc<AtomicBool>,
+    ignore_teleports: Arc<AtomicBool>,
+    refuse_logins: Arc<AtomicBool>,
+    /// Open RCON connections, so they can be cut
+    rcon_sessions: Arc<Mutex<Vec<TcpStream>>>,
+}
+
+impl Handler {
+    /// Answer RCON packets until the client goes away. Any password is accepted.
+    fn serve_rcon(&self, mut stream: TcpStream) {
+        use std::io::Write;
+        let _ = stream.set_nonblocking(false);
+        if let Ok(session) = stream.try_clone() {
+            self.rcon_sessions.lock().push(session);
+        }
+        while let Ok(packet) = rcon::decode(&mut stream) {
+            let (kind, body) = match packet.kind {
+                rcon::TYPE_AUTH => (rcon::TYPE_EXEC, String::new()),
+                rcon::TYPE_EXEC => (rcon::TYPE_RESPONSE, self.run(&packet.body)),
+                _ => (
+                    rcon::TYPE_RESPONSE,
+                    format!("Unknown request {}", packet.kind),
+                ),
+            };
+            let reply = rcon::encode(&Packet {
+                id: packet.id,
+                kind,
+                body,
+            });
+            if stream.write_all(&reply).is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Queue `outbound` for every joined client, forgetting clients that left
+    fn broadcast(&self, outbound: Outbound) {
+        self.clients
+            .lock()
+            .retain(|client| client.send(outbound.clone()).is_ok());
+    }
+
+    fn interact(&self, pos: [i32; 3]) {
+        self.world.lock().interactions.push(pos);
+    }
+
+    fn run(&self, command: &str) -> String {
+        let mut world = self.world.lock();
+        world.commands.push(command.to_string());
+        let mut words: Vec<&str> = command.split_whitespace().collect();
+        // The model has a single dimension
+        if let ["execute", "in", _, "run", ..] = words.as_slice() {
+            words.drain(..4);
+        }
+        match words.as_slice() {
+            ["tick", "step"] => world.advance(1),
+            ["tick", "step", ticks] => world.advance(ticks.parse().unwrap_or(1)),
+            // `tick sprint N` runs one tick more than asked, like the real server
+            ["tick", "sprint", ticks] => world.advance(ticks.parse::<u64>().unwrap_or(0) + 1),
+            ["time", "query", "gametime"] => format!("The time is {}", world.gametime),
+            ["scoreboard", "objectives", "add", objective, ..] => {
+                if world.objectives.insert(objective.to_string()) {
+                    format!("Created new objective [{objective}]")
+                } else {
+                    "An objective already exists by that name".to_string()
+                }
+            }
+            ["scoreboard", "players", "reset", "*", _] => {
+                world.scores.clear();
+                String::new()
+            }
+            ["scoreboard", "players", "reset", holder, _] => {
+                world.scores.remove(*holder);
+                String::new()
+            }
+            ["scoreboard", "players", "get", holder, objective] => {
+                match world.scores.get(*holder) {
+                    Some(score) => format!("{holder} has {score} [{objective}]"),
+                    None => format!("Can't get value of {holder} for {objective}; none is set"),
+                }
+            }
+            ["setblock", x, y, z, state] => {
+                let pos = parse_pos([x, y, z]);
+                world.blocks.insert(pos, state.to_string());
+                self.broadcast(Outbound::Block(pos, state.to_string()));
+                format!("Changed the block at {}, {}, {}", pos[0], pos[1], pos[2])
+            }
+            ["fill", x1, y1, z1, x2, y2, z2, state, ..] => {
+                let [min, max] = [parse_pos([x1, y1, z1]), parse_pos([x2, y2, z2])];
+                let air = state.trim_start_matches("minecraft:") == "air";
+                let mut changed = 0;
+                for x in min[0]..=max[0] {
+                    for y in min[1]..=max[1] {
+                        for z in min[2]..=max[2] {
+                            let pos = [x, y, z];
+                            // Joined clients already see air wherever the model has none
+                            let was_air = !world.blocks.contains_key(&pos);
+                            if air {
+                                world.blocks.remove(&pos);
+                            } else {
+                                world.blocks.insert(pos, state.to_string());
+                            }
+                            if !(air && was_air) {
+                                self.broadcast(Outbound::Block(pos, state.to_string()));
+                                changed += 1;
+                            }
+                        }
+                    }
+                }
+                format!("Successfully filled {changed} block(s)")
+            }
+            ["tp", _, x, y, z, rotation @ ..] => {
+                let pos = [x, y, z].map(|axis| axis.parse::<f64>().unwrap_or(0.0));
+                let rotation = match rotation {
+                    [yaw, pitch] => {
+                        Some([yaw, pitch].map(|angle| angle.parse::<f32>().unwrap_or(0.0)))
+                    }
+                    _ => None,
+                };
+                if !self.ignore_teleports.load(Ordering::SeqCst) {
+                    self.broadcast(Outbound::Teleport(pos, rotation));
+                }
+                format!(
+                    "Teleported {} to {}, {}, {}",
+                    BOT_NAME, pos[0], pos[1], pos[2]
+                )
+            }
+            ["data", "get", "entity", selector, path @ ..] => {
+                match world
+                    .entities
+                    .get(*selector)
+                    .and_then(|data| data.get(&path.join(" ")))
+                {
+                    Some(value) => format!("{selector} has the following entity data: {value}"),
+                    None => format!("Found no elements matching {}", path.join(" ")),
+                }
+            }
+            ["data", "get", "block", x, y, z, path @ ..] => {
+                let pos = parse_pos([x, y, z]);
+                match world
+                    .block_entities
+                    .get(&pos)
+                    .and_then(|data| data.get(&path.join(" ")))
+                {
+                    Some(value) => format!(
+                        "{}, {}, {} has the following block data: {value}",
+                        pos[0], pos[1], pos[2]
+                    ),
+                    None => format!("Found no elements matching {}", path.join(" ")),
+                }
+            }
+            [
+                "execute",
+                "store",
+                kind,
+                "score",
+                holder,
+                objective,
+                rest @ ..,
+            ] => {
+                let result = world.evaluate(rest);
+                if !world.objectives.contains(*objective) {
+                    return format!("Unknown scoreboard objective '{objective}'");
+                }
+                let stored = match *kind {
+                    "success" => i64::from(result > 0),
+                    _ => result,
+                };
+                world.scores.insert(holder.to_string(), stored);
+                String::new()
+            }
+            ["tellraw", _, ..] => {
+                let json = command.splitn(3, ' ').nth(2).unwrap_or_default();
+                let Ok(component) = serde_json::from_str::<serde_json::Value>(json) else {
+                    return "Invalid chat component".to_string();
+                };
+                let message = world.render(&component);
+                let marker = message.contains("__flintmc_ack_");
+                if marker && self.drop_acks.load(Ordering::SeqCst) {
+                    return String::new();
+                }
+                // The RCON bot only routes marker messages to the acknowledgement
+                // channel; a joined bot sorts system chat itself
+                if marker {
+                    let _ = self.ack_tx.send(message.clone());
+                }
+                self.broadcast(Outbound::Chat(message));
+                String::new()
+            }
+            _ => String::new(),
+        }
+    }
+}
+
+impl MockWorld {
+    fn advance(&mut self, ticks: u64) -> String {
+        self.gametime += ticks;
+        format!("Stepping {ticks} tick(s)")
+    }
+
+    /// Result of an `execute` chain ending in `run time query gametime`, `if entity`
+    /// or `if data`
+    fn evaluate(&self, words: &[&str]) -> i64 {
+        match words {
+            ["run", "time", "query", "gametime"] => self.gametime as i64,
+            ["run", "return", value] => value.parse().unwrap_or(0),
+            ["if", "block", x, y, z, state] => {
+                let pos = parse_pos([x, y, z]);
+                let actual = self
+                    .blocks
+                    .get(&pos)
+                    .map_or("minecraft:air", String::as_str);
+                // Like the game, only the properties the state names have to match
+                let (id, properties) = state.split_once('[').unwrap_or((state, "]"));
+                let (actual_id, actual_properties) =
+                    actual.split_once('[').unwrap_or((actual, "]"));
+                let actual_properties: Vec<_> =
+                    actual_properties.trim_end_matches(']').split(',').collect();
+                let matches = id == actual_id
+                    && properties
+                        .trim_end_matches(']')
+                        .split(',')
+                        .filter(|property| !property.is_empty())
+                        .all(|property| actual_properties.contains(&property));
+                i64::from(matches)
+            }
+            ["if", "entity", selector] => i64::from(self.entities.contains_key(*selector)),
+            ["if", "data", "entity", selector, path @ ..] => self
+                .entities
+                .get(*selector)
+                .map_or(0, |data| i64::from(data.contains_key(&path.join(" ")))),
+            ["if", "data", "block", x, y, z, path @ ..] => {
+                let pos = parse_pos([x, y, z]);
+                let path = path.join(" ");
+                self.block_entities.get(&pos).map_or(0, |data| {
+                    // Matching the root against `{}` succeeds for any block entity
+                    i64::from(path == "{}" || data.contains_key(&path))
+                })
+            }
+            _ => 0,
+        }
+    }
+
+    /// Plain text of a `tellraw` component; unset scores and missing data render empty
+    fn render(&self, component: &serde_json::Value) -> String {
+        use serde_json::Value;
+        match component {
+            Value::String(text) => text.clone(),
+            Value::Array(parts) => parts.iter().map(|part| self.render(part)).collect(),
+            Value::Object(fields) => {
+                if let Some(text) = fields.get("text").and_then(Value::as_str) {
+                    return text.to_string();
+                }
+                if let Some(holder) = fields
+                    .get("score")
+                    .and_then(|score| score.get("name"))
+                    .and_then(Value::as_str)
+                {
+                    return self
+                        .scores
+                        .get(holder)
+                        .map(ToString::to_string)
+                        .unwrap_or_default();
+                }
+                let path = fields
+                    .get("nbt")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default();
+                let data = if let Some(selector) = fields.get("entity").and_then(Value::as_str) {
+                    self.entities.get(selector)
+                } else if let Some(pos) = fields.get("block").and_then(Value::as_str) {
+                    let mut axes = pos.split_whitespace().map(|axis| axis.parse().unwrap_or(0));
+                    let pos = [(); 3].map(|_| axes.next().unwrap_or(0));
+                    self.block_entities.get(&pos)
+                } else {
+                    None
+                };
+                data.and_then(|data| data.get(path))
+                    .cloned()
+                    .unwrap_or_default()
+            }
+            _ => String::new(),
+        }
+    }
+}
+
+fn parse_pos(axes: [&&str; 3]) -> [i32; 3] {
+    axes.map(|axis| axis.parse().unwrap_or(0))
+}
@@ -0,0 +1,480 @@
+//! The game side of the mock: status pings, an offline login, a one-batch overworld
+//! and the packets commands turn into.
+//!
+//! Packets with nested or registry-backed fields (the login, registry data, chunks,
+//! positions) are written as protocol bytes and read back through Azalea's own
+//! decoder, so the mock needs no NBT or registry types of its own.
+
+use super::{Handler, Outbound};
+use anyhow::Result;
+use azalea::buf::AzaleaRead;
+use azalea::protocol::connect::Connection;
+use azalea::protocol::packets::ClientIntention;
+use azalea::protocol::packets::config::ServerboundConfigPacket;
+use azalea::protocol::packets::config::c_finish_configuration::ClientboundFinishConfiguration;
+use azalea::protocol::packets::config::c_registry_data::ClientboundRegistryData;
+use azalea::protocol::packets::game::ServerboundGamePacket;
+use azalea::protocol::packets::game::c_block_update::ClientboundBlockUpdate;
+use azalea::protocol::packets::game::c_chunk_batch_finished::ClientboundChunkBatchFinished;
+use azalea::protocol::packets::game::c_chunk_batch_start::ClientboundChunkBatchStart;
+use azalea::protocol::packets::game::c_level_chunk_with_light::ClientboundLevelChunkWithLight;
+use azalea::protocol::packets::game::c_login::ClientboundLogin;
+use azalea::protocol::packets::game::c_player_position::ClientboundPlayerPosition;
+use azalea::protocol::packets::game::c_set_chunk_cache_center::ClientboundSetChunkCacheCenter;
+use azalea::protocol::packets::game::c_system_chat::ClientboundSystemChat;
+use azalea::protocol::packets::handshake::ServerboundHandshakePacket;
+use azalea::protocol::packets::login::ServerboundLoginPacket;
+use azalea::protocol::packets::login::c_login_disconnect::ClientboundLoginDisconnect;
+use azalea::protocol::packets::login::c_login_finished::ClientboundLoginFinished;
+use azalea::protocol::packets::status::ServerboundStatusPacket;
+use azalea::protocol::packets::status::c_pong_response::ClientboundPongResponse;
+use azalea::protocol::packets::status::c_status_response::ClientboundStatusResponse;
+use azalea::protocol::packets::{PROTOCOL_VERSION, VERSION_NAME};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::net::TcpStream;
+
+/// Chunk radius and simulation distance the login advertises. They differ so tests
+/// can tell which one the bot settled on.
+const CHUNK_RADIUS: u32 = 4;
+pub(crate) const SIMULATION_DISTANCE: u32 = 3;
+/// Chunks sent around the origin in the spawn batch
+const SPAWN_CHUNK_RADIUS: i32 = 2;
+const MIN_Y: i32 = -64;
+const HEIGHT: i32 = 384;
+const SPAWN: [f64; 3] = [0.5, 64.0, 0.5];
+const ACCEPT_POLL_MS: u64 = 50;
+
+// Relative flags of a position change: keep the current yaw and pitch
+const RELATIVE_Y_ROT: i32 = 0x08;
+const RELATIVE_X_ROT: i32 = 0x10;
+
+/// Accept game connections until the server is dropped
+pub(super) fn listen(listener: std::net::TcpListener, handler: Handler, stopped: Arc<AtomicBool>) {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Failed to create tokio runtime");
+    rt.block_on(async move {
+        let Ok(listener) = tokio::net::TcpListener::from_std(listener) else {
+            return;
+        };
+        while !stopped.load(Ordering::SeqCst) {
+            let accepted = tokio::time::timeout(
+                std::time::Duration::from_millis(ACCEPT_POLL_MS),
+                listener.accept(),
+            )
+            .await;
+            if let Ok(Ok((stream, _))) = accepted {
+                let handler = handler.clone();
+                tokio::spawn(async move {
+                    if let Err(error) = serve(handler, stream).await {
+                        tracing::debug!("Mock game connection ended: {:#}", error);
+                    }
+                });
+            }
+        }
+    });
+}
+
+async fn serve(handler: Handler, stream: TcpStream) -> Result<()> {
+    let mut handshake = Connection::wrap(stream);
+    let ServerboundHandshakePacket::Intention(intention) = handshake.read().await?;
+    match intention.intention {
+        ClientIntention::Status => status(handshake.status()).await,
+        _ => play(handler, handshake.login()).await,
+    }
+}
+
+/// Answer a status ping with the protocol version Azalea was built for
+async fn status(
+    mut conn: Connection<
+        ServerboundStatusPacket,
+        azalea::protocol::packets::status::ClientboundStatusPacket,
+    >,
+) -> Result<()> {
+    loop {
+        match conn.read().await? {
+            ServerboundStatusPacket::StatusRequest(_) => {
+                let status = serde_json::json!({
+                    "version": { "name": VERSION_NAME, "protocol": PROTOCOL_VERSION },
+                    "players": { "max": 1, "online": 0 },
+                    "description": { "text": "flintmc mock server" },
+                });
+                conn.write(decode::<ClientboundStatusResponse>(
+                    Wire::default().string(&status.to_string()),
+                )?)
+                .await?;
+            }
+            ServerboundStatusPacket::PingRequest(ping) => {
+                conn.write(ClientboundPongResponse { time: ping.time })
+                    .await?;
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Log the bot in, spawn it at the origin and relay commands until it leaves
+async fn play(
+    handler: Handler,
+    mut login: Connection<
+        ServerboundLoginPacket,
+        azalea::protocol::packets::login::ClientboundLoginPacket,
+    >,
+) -> Result<()> {
+    let hello = loop {
+        if let ServerboundLoginPacket::Hello(hello) = login.read().await? {
+            break hello;
+        }
+    };
+    if handler.refuse_logins.load(Ordering::SeqCst) {
+        let reason = serde_json::json!({ "text": "Logins are refused" });
+        login
+            .write(decode::<ClientboundLoginDisconnect>(
+                Wire::default().string(&reason.to_string()),
+            )?)
+            .await?;
+        return Ok(());
+    }
+    // Offline mode: no encryption and no compression
+    login
+        .write(decode::<ClientboundLoginFinished>(
+            Wire::default()
+                .bytes(hello.profile_id.as_bytes())
+                .string(&hello.name)
+                .var_int(0),
+        )?)
+        .await?;
+    while !matches!(
+        login.read().await?,
+        ServerboundLoginPacket::LoginAcknowledged(_)
+    ) {}
+
+    let mut config = login.config();
+    config
+        .write(decode::<ClientboundRegistryData>(dimension_types())?)
+        .await?;
+    config
+        .write(decode::<ClientboundFinishConfiguration>(Wire::default())?)
+        .await?;
+    while !matches!(
+        config.read().await?,
+        ServerboundConfigPacket::FinishConfiguration(_)
+    ) {}
+
+    let (mut reader, mut writer) = config.game().into_split();
+    writer
+        .write(decode::<ClientboundLogin>(login_packet())?)
+        .await?;
+    writer
+        .write(decode::<ClientboundSetChunkCacheCenter>(
+            Wire::default().var_int(0).var_int(0),
+        )?)
+        .await?;
+    writer
+        .write(decode::<ClientboundPlayerPosition>(position(
+            0,
+            SPAWN,
+            Some([0.0, 0.0]),
+        ))?)
+        .await?;
+    writer
+        .write(decode::<ClientboundChunkBatchStart>(Wire::default())?)
+        .await?;
+    let mut batch_size = 0;
+    for x in -SPAWN_CHUNK_RADIUS..=SPAWN_CHUNK_RADIUS {
+        for z in -SPAWN_CHUNK_RADIUS..=SPAWN_CHUNK_RADIUS {
+            writer
+                .write(decode::<ClientboundLevelChunkWithLight>(empty_chunk(x, z))?)
+                .await?;
+            batch_size += 1;
+        }
+    }
+    writer
+        .write(decode::<ClientboundChunkBatchFinished>(
+            Wire::default().var_int(batch_size),
+        )?)
+        .await?;
+
+    // Chunks go out empty; blocks set before the bot joined follow as updates.
+    // Registering under the world lock keeps later changes from slipping between.
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let blocks: Vec<_> = {
+        let world = handler.world.lock();
+        handler.clients.lock().push(tx);
+        world
+            .blocks
+            .iter()
+            .map(|(pos, state)| (*pos, state.clone()))
+            .collect()
+    };
+    for (pos, state) in blocks {
+        writer.write(block_update(pos, &state)?).await?;
+    }
+
+    let commands = handler.clone();
+    let reading = tokio::spawn(async move {
+        while let Ok(packet) = reader.read().await {
+            match packet {
+                ServerboundGamePacket::ChatCommand(packet) => {
+                    commands.run(&packet.command);
+                }
+                ServerboundGamePacket::UseItemOn(packet) => {
+                    let pos = packet.block_hit.block_pos;
+                    commands.interact([pos.x, pos.y, pos.z]);
+                }
+                _ => {}
+            }
+        }
+    });
+
+    let mut teleport_id = 0;
+    let result = async {
+        while let Some(outbound) = rx.recv().await {
+            match outbound {
+                Outbound::Chat(text) => {
+                    writer
+                        .write(decode::<ClientboundSystemChat>(system_chat(&text))?)
+                        .await?
+                }
+                Outbound::Block(pos, state) => writer.write(block_update(pos, &state)?).await?,
+                Outbound::Teleport(pos, rotation) => {
+                    teleport_id += 1;
+                    writer
+                        .write(decode::<ClientboundPlayerPosition>(position(
+                            teleport_id,
+                            pos,
+                            rotation,
+                        ))?)
+                        .await?
+                }
+                Outbound::Disconnect => break,
+            }
+        }
+        Ok::<_, anyhow::Error>(())
+    }
+    .await;
+    reading.abort();
+    result
+}
+
+/// A block update with `state`'s default state; properties in `state` are ignored
+fn block_update(pos: [i32; 3], state: &str) -> Result<ClientboundBlockUpdate> {
+    let id = state.split('[').next().unwrap_or(state);
+    let block: azalea::registry::Block = id
+        .parse()
+        .map_err(|_| anyhow::anyhow!("unknown block {id}"))?;
+    Ok(ClientboundBlockUpdate {
+        pos: azalea::BlockPos::new(pos[0], pos[1], pos[2]),
+        block_state: azalea::blocks::BlockState::from(block),
+    })
+}
+
+/// The overworld's dimension type, the only registry entry the client needs to
+/// create its world
+fn dimension_types() -> Wire {
+    let overworld = Wire::default()
+        .u8(NBT_COMPOUND)
+        .nbt_int("min_y", MIN_Y)
+        .nbt_int("height", HEIGHT)
+        .nbt_int("logical_height", HEIGHT)
+        .nbt_byte("has_skylight", 1)
+        .nbt_byte("has_ceiling", 0)
+        .nbt_byte("ultrawarm", 0)
+        .nbt_byte("natural", 1)
+        .nbt_byte("piglin_safe", 0)
+        .nbt_byte("bed_works", 1)
+        .nbt_byte("respawn_anchor_works", 0)
+        .nbt_byte("has_raids", 1)
+        .nbt_double("coordinate_scale", 1.0)
+        .nbt_float("ambient_light", 0.0)
+        .nbt_int("monster_spawn_block_light_limit", 0)
+        .nbt_int("monster_spawn_light_level", 0)
+        .nbt_string("infiniburn", "#minecraft:infiniburn_overworld")
+        .nbt_string("effects", "minecraft:overworld")
+        .u8(NBT_END);
+    Wire::default()
+        .string("minecraft:dimension_type")
+        .var_int(1)
+        .string("minecraft:overworld")
+        .bool(true)
+        .bytes(&overworld.0)
+}
+
+fn login_packet() -> Wire {
+    Wire::default()
+        .i32(1) // entity id
+        .bool(false) // hardcore
+        .var_int(1)
+        .string("minecraft:overworld")
+        .var_int(1) // max players
+        .var_int(CHUNK_RADIUS as i32)
+        .var_int(SIMULATION_DISTANCE as i32)
+        .bool(false) // reduced debug info
+        .bool(true) // show death screen
+        .bool(false) // limited crafting
+        .var_int(0) // dimension type: the only registry entry
+        .string("minecraft:overworld")
+        .i64(0) // hashed seed
+        .u8(1) // creative
+        .u8(0xff) // no previous game mode
+        .bool(false) // debug world
+        .bool(true) // flat world
+        .bool(false) // no death location
+        .var_int(0) // portal cooldown
+        .var_int(63) // sea level
+        .bool(false) // enforces secure chat
+}
+
+/// A position change to `pos`, keeping the current direction without `rotation`
+fn position(id: i32, pos: [f64; 3], rotation: Option<[f32; 2]>) -> Wire {
+    let ([yaw, pitch], relative) = match rotation {
+        Some(rotation) => (rotation, 0),
+        None => ([0.0, 0.0], RELATIVE_Y_ROT | RELATIVE_X_ROT),
+    };
+    Wire::default()
+        .var_int(id)
+        .f64(pos[0])
+        .f64(pos[1])
+        .f64(pos[2])
+        .f64(0.0)
+        .f64(0.0)
+        .f64(0.0)
+        .f32(yaw)
+        .f32(pitch)
+        .i32(relative)
+}
+
+/// A chunk of air, with no heightmaps, block entities or light
+fn empty_chunk(x: i32, z: i32) -> Wire {
+    let mut sections = Wire::default();
+    for _ in 0..HEIGHT / 16 {
+        sections = sections
+            .i16(0) // non-air blocks
+            .u8(0) // single-valued block palette
+            .var_int(0) // air
+            .u8(0) // single-valued biome palette
+            .var_int(0);
+    }
+    Wire::default()
+        .i32(x)
+        .i32(z)
+        .var_int(0) // heightmaps
+        .var_int(sections.0.len() as i32)
+        .bytes(&sections.0)
+        .var_int(0) // block entities
+        .var_int(0) // sky light mask
+        .var_int(0) // block light mask
+        .var_int(0) // empty sky light mask
+        .var_int(0) // empty block light mask
+        .var_int(0) // sky light arrays
+        .var_int(0) // block light arrays
+}
+
+/// A system message whose content is a plain string component
+fn system_chat(text: &str) -> Wire {
+    Wire::default()
+        .u8(NBT_STRING)
+        .u16(text.len() as u16)
+        .bytes(text.as_bytes())
+        .bool(false) // chat, not the action bar
+}
+
+/// Read a packet back from its protocol bytes
+fn decode<P: AzaleaRead>(wire: Wire) -> Result<P> {
+    P::azalea_read(&mut std::io::Cursor::new(wire.0.as_slice()))
+        .map_err(|error| anyhow::anyhow!("malformed mock packet: {error:?}"))
+}
+
+const NBT_END: u8 = 0;
+const NBT_BYTE: u8 = 1;
+const NBT_INT: u8 = 3;
+const NBT_FLOAT: u8 = 5;
+const NBT_DOUBLE: u8 = 6;
+const NBT_STRING: u8 = 8;
+const NBT_COMPOUND: u8 = 10;
+
+/// Protocol bytes, written field by field
+#[derive(Default)]
+struct Wire(Vec<u8>);
+
+impl Wire {
+    fn bytes(mut self, bytes: &[u8]) -> Self {
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    fn u8(self, value: u8) -> Self {
+        self.bytes(&[value])
+    }
+
+    fn bool(self, value: bool) -> Self {
+        self.u8(u8::from(value))
+    }
+
+    fn u16(self, value: u16) -> Self {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    fn i16(self, value: i16) -> Self {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    fn i32(self, value: i32) -> Self {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    fn i64(self, value: i64) -> Self {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    fn f32(self, value: f32) -> Self {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    fn f64(self, value: f64) -> Self {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    fn var_int(mut self, value: i32) -> Self {
+        let mut value = value as u32;
+        loop {
+            if value < 0x80 {
+                return self.u8(value as u8);
+            }
+            self = self.u8((value & 0x7f) as u8 | 0x80);
+            value >>= 7;
+        }
+    }
+
+    fn string(self, value: &str) -> Self {
+        self.var_int(value.len() as i32).bytes(value.as_bytes())
+    }
+
+    fn nbt_name(self, tag: u8, name: &str) -> Self {
+        self.u8(tag).u16(name.len() as u16).bytes(name.as_bytes())
+    }
+
+    fn nbt_byte(self, name: &str, value: u8) -> Self {
+        self.nbt_name(NBT_BYTE, name).u8(value)
+    }
+
+    fn nbt_int(self, name: &str, value: i32) -> Self {
+        self.nbt_name(NBT_INT, name).i32(value)
+    }
+
+    fn nbt_float(self, name: &str, value: f32) -> Self {
+        self.nbt_name(NBT_FLOAT, name).f32(value)
+    }
+
+    fn nbt_double(self, name: &str, value: f64) -> Self {
+        self.nbt_name(NBT_DOUBLE, name).f64(value)
+    }
+
+    fn nbt_string(self, name: &str, value: &str) -> Self {
+        self.nbt_name(NBT_STRING, name)
+            .u16(value.len() as u16)
+            .bytes(value.as_bytes())
+    }
+}
@@ -1,15 +1,14 @@
-//! Minecraft-facing wrapper around flint-core's shared action dispatcher.
+//! Executor-facing wrapper around flint-core's shared action dispatcher.
 
-use crate::executor::adapter::MinecraftWorld;
 use anyhow::Result;
 use colored::Colorize;
 use flint_core::results::ActionOutcome;
 use flint_core::runner::execute_action as execute_core_action;
 use flint_core::test_spec::TimelineEntry;
-use flint_core::traits::FlintPlayer;
+use flint_core::traits::{FlintPlayer, FlintWorld};
 
 pub fn execute_action(
-    world: &mut MinecraftWorld,
+    world: &mut dyn FlintWorld,
     player: &mut Option<Box<dyn FlintPlayer>>,
     tick: u32,
     entry: &TimelineEntry,
@@ -1,22 +1,45 @@
 use crate::bot::{TestBot, slot_to_minecraft_name};
+use crate::executor::backend::{BatchAdapter, BatchWorld};
 use crate::executor::block;
 use crate::executor::tick;
 use anyhow::Result;
 use flint_core::BlockPos;
-use flint_core::test_spec::{Block, EntityNbt, GameMode, Item, PlayerSlot};
+use flint_core::test_spec::{Block, EntityNbt, GameMode, Item, PlayerSlot, TestSpec};
 use flint_core::traits::{EntityState, FlintAdapter, FlintPlayer, FlintWorld, ServerInfo};
 use std::collections::HashMap;
 
-#[allow(dead_code)]
+/// How long bulk-confirmed placements may take to show up after the fence
+const PLACEMENT_CONFIRM_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+const PLACEMENT_CONFIRM_POLL_MS: u64 = 5;
+
+/// Test worlds on the server the bot is connected to. Every world starts at the origin;
+/// runners lay tests out in it at their own offsets.
 pub struct MinecraftAdapter {
     bot: TestBot,
 }
 
-#[allow(dead_code)]
 impl MinecraftAdapter {
     pub fn new(bot: TestBot) -> Self {
         Self { bot }
     }
+
+    /// A world whose test origin is `offset`; entity type lookups stay inside
+    /// `entity_bounds` when given
+    pub(crate) fn world_at(
+        &self,
+        offset: [i32; 3],
+        entity_bounds: Option<[[i32; 3]; 2]>,
+    ) -> MinecraftWorld {
+        MinecraftWorld {
+            bot: self.bot.clone(),
+            offset,
+            current_tick: 0,
+            entities: HashMap::new(),
+            entity_bounds,
+            pending_placements: None,
+            verify_server_side: false,
+        }
+    }
 }
 
 impl FlintAdapter for MinecraftAdapter {
@@ -24,30 +47,111 @@ impl FlintAdapter for MinecraftAdapter {
         // Freeze time globally first when creating test world
         self.bot.send_command_synced("tick freeze")?;
 
-        Ok(Box::new(MinecraftWorld {
-            bot: self.bot.clone(),
-            offset: [0, 0, 0],
-            current_tick: 0,
-            entities: HashMap::new(),
-            entity_bounds: None,
-        }))
+        Ok(Box::new(self.world_at([0, 0, 0], None)))
     }
 
     fn server_info(&self) -> ServerInfo {
         ServerInfo {
-            minecraft_version: "1.21.8".to_string(),
+            minecraft_version: self
+                .bot
+                .server_version()
+                .unwrap_or_else(|| "unknown".to_string()),
         }
     }
 }
 
+impl BatchAdapter for MinecraftAdapter {
+    fn on_server(&self) -> bool {
+        true
+    }
+}
+
+/// The bot's own batches: every test gets a `MinecraftWorld` at its offset, whose
+/// placements are confirmed in bulk and whose reads can be checked on the server
+pub struct AzaleaAdapter {
+    adapter: MinecraftAdapter,
+    verify_server_side: bool,
+}
+
+impl AzaleaAdapter {
+    pub fn new(bot: TestBot, verify_server_side: bool) -> Self {
+        Self {
+            adapter: MinecraftAdapter::new(bot),
+            verify_server_side,
+        }
+    }
+}
+
+impl FlintAdapter for AzaleaAdapter {
+    fn create_test_world(&self) -> Result<Box<dyn FlintWorld>> {
+        self.adapter.create_test_world()
+    }
+
+    fn server_info(&self) -> ServerInfo {
+        self.adapter.server_info()
+    }
+}
+
+impl BatchAdapter for AzaleaAdapter {
+    fn batch_worlds(
+        &self,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+    ) -> Result<Vec<Box<dyn BatchWorld>>> {
+        Ok(tests_with_offsets
+            .iter()
+            .map(|(test, offset)| {
+                let [min, max] = test.cleanup_region();
+                let mut world = self.adapter.world_at(
+                    *offset,
+                    Some([
+                        [min[0] + offset[0], min[1] + offset[1], min[2] + offset[2]],
+                        [max[0] + offset[0], max[1] + offset[1], max[2] + offset[2]],
+                    ]),
+                );
+                world.verify_server_side = self.verify_server_side;
+                Box::new(world) as Box<dyn BatchWorld>
+            })
+            .collect())
+    }
+
+    fn on_server(&self) -> bool {
+        true
+    }
+}
+
 pub struct MinecraftWorld {
     pub bot: TestBot,
     pub offset: [i32; 3],
     pub current_tick: u64,
     pub(crate) entities: HashMap<String, MinecraftEntity>,
     pub(crate) entity_bounds: Option<[[i32; 3]; 2]>,
+    /// Placements sent but not yet confirmed, while deferring (world position, block)
+    pub(crate) pending_placements: Option<Vec<([i32; 3], Block)>>,
+    /// Confirm every block read from the client with `execute if block`
+    pub(crate) verify_server_side: bool,
 }
 
+/// The client's copy of a block disagrees with the server, so the assertion reading it
+/// can't be trusted either way
+#[derive(Debug)]
+pub struct Desync {
+    pub pos: [i32; 3],
+    pub client: String,
+}
+
+impl std::fmt::Display for Desync {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [x, y, z] = self.pos;
+        write!(
+            f,
+            "desync: the client sees {} at [{x}, {y}, {z}] but the server does not",
+            self.client
+        )
+    }
+}
+
+impl std::error::Error for Desync {}
+
 #[derive(Debug, Clone)]
 pub(crate) struct MinecraftEntity {
     entity_type: String,
@@ -79,6 +183,21 @@ impl MinecraftWorld {
         format!("@e[tag={},type={},limit=1]", entity.tag, entity.entity_type)
     }
 
+    /// Read a block from the client's world, waiting for its chunk to arrive
+    fn poll_block_id(&self, world_pos: [i32; 3]) -> Result<String> {
+        for _ in 0..10 {
+            if let Ok(Some(actual_block_str)) = self.bot.get_block(world_pos) {
+                let normalized_id = block::extract_block_id(&actual_block_str);
+                if !normalized_id.is_empty() {
+                    return Ok(normalized_id);
+                }
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+
+        anyhow::bail!("timed out reading block at {world_pos:?}")
+    }
+
     pub(crate) fn set_block_checked(&mut self, pos: BlockPos, block: &Block) -> Result<()> {
         let world_pos = self.world_pos(pos);
         let block_spec = block.to_command();
@@ -89,6 +208,10 @@ impl MinecraftWorld {
         let expected = block.clone();
         self.bot.wait_for_block_chunk(world_pos)?;
         self.bot.send_command(&cmd)?;
+        if let Some(pending) = self.pending_placements.as_mut() {
+            pending.push((world_pos, expected));
+            return Ok(());
+        }
         self.bot.sync_client_world()?;
         self.bot.wait_until("block synchronization", || {
             let Ok(Some(actual_block_str)) = self.bot.get_block(world_pos) else {
@@ -112,6 +235,24 @@ impl Drop for MinecraftWorld {
     }
 }
 
+impl BatchWorld for MinecraftWorld {
+    fn defer_placements(&mut self) {
+        self.pending_placements.get_or_insert_with(Vec::new);
+    }
+
+    fn take_placements(&mut self) -> Vec<([i32; 3], Block)> {
+        self.pending_placements.take().unwrap_or_default()
+    }
+
+    fn set_current_tick(&mut self, tick: u64) {
+        self.current_tick = tick;
+    }
+
+    fn as_minecraft(&self) -> Option<&MinecraftWorld> {
+        Some(self)
+    }
+}
+
 impl FlintWorld for MinecraftWorld {
     fn do_tick(&mut self) -> Result<()> {
         let mut bot = self.bot.clone();
@@ -130,26 +271,40 @@ impl FlintWorld for MinecraftWorld {
 
     fn get_block(&self, pos: BlockPos, requested_nbt: &[String]) -> Result<Block> {
         let world_pos = self.world_pos(pos);
-        for _ in 0..10 {
-            if let Ok(Some(actual_block_str)) = self.bot.get_block(world_pos) {
-                let normalized_id = block::extract_block_id(&actual_block_str);
-                let mut block = block::make_block(&normalized_id);
-                if !normalized_id.is_empty() {
-                    let mut values = Vec::with_capacity(requested_nbt.len());
-                    for path in requested_nbt {
-                        values.push((path.clone(), query_block_data(&self.bot, world_pos, path)?));
-                    }
-                    let nbt = flint_core::test_spec::EntityNbt::from_string_values(values);
-                    if !requested_nbt.is_empty() {
-                        block.nbt = Some(nbt);
-                    }
-                    return Ok(block);
-                }
+        // Prefetched with the other assertions of this tick, unless its chunk wasn't
+        // loaded yet
+        let normalized_id = match self
+            .bot
+            .cached_block(world_pos)
+            .map(|state| block::extract_block_id(&state))
+            .filter(|id| !id.is_empty())
+        {
+            Some(id) => id,
+            None => self.poll_block_id(world_pos)?,
+        };
+
+        if self.verify_server_side
+            && !self.bot.query_condition(&format!(
+                "block {} {} {} {normalized_id}",
+                world_pos[0], world_pos[1], world_pos[2]
+            ))?
+        {
+            return Err(Desync {
+                pos: world_pos,
+                client: normalized_id,
             }
-            std::thread::sleep(std::time::Duration::from_millis(50));
+            .into());
         }
 
-        anyhow::bail!("timed out reading block at {world_pos:?}")
+        let mut block = block::make_block(&normalized_id);
+        if !requested_nbt.is_empty() {
+            let mut values = Vec::with_capacity(requested_nbt.len());
+            for path in requested_nbt {
+                values.push((path.clone(), query_block_data(&self.bot, world_pos, path)?));
+            }
+            block.nbt = Some(EntityNbt::from_string_values(values));
+        }
+        Ok(block)
     }
 
     fn set_block(&mut self, pos: BlockPos, block: &Block) -> Result<()> {
@@ -336,163 +491,122 @@ impl FlintWorld for MinecraftWorld {
     }
 }
 
+/// A placement that didn't take: what was placed and what the client sees instead,
+/// `None` when the chunk isn't loaded
+#[derive(Debug, Clone)]
+pub(crate) struct PlacementMismatch {
+    pub pos: [i32; 3],
+    pub expected: Block,
+    pub actual: Option<Block>,
+}
+
+impl PlacementMismatch {
+    pub fn expected(&self) -> String {
+        self.expected.to_command()
+    }
+
+    pub fn actual(&self) -> String {
+        self.actual
+            .as_ref()
+            .map_or_else(|| "chunk not loaded".to_string(), Block::to_command)
+    }
+
+    pub fn message(&self) -> String {
+        match self.actual {
+            Some(_) => format!("expected {}, got {}", self.expected(), self.actual()),
+            None => format!("expected {}, {}", self.expected(), self.actual()),
+        }
+    }
+}
+
+/// Confirm placements sent without waiting: one fence, then every position is read
+/// in bulk until all match or the timeout passes. Only the last placement at each
+/// position counts. Returns the positions that still differ, with what was found.
+pub(crate) fn confirm_placements(
+    bot: &TestBot,
+    placements: &[([i32; 3], Block)],
+) -> Result<Vec<PlacementMismatch>> {
+    let mut seen = std::collections::HashSet::new();
+    let mut latest: Vec<&([i32; 3], Block)> = placements
+        .iter()
+        .rev()
+        .filter(|(pos, _)| seen.insert(*pos))
+        .collect();
+    latest.reverse();
+    if latest.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    bot.sync_client_world()?;
+    let positions: Vec<[i32; 3]> = latest.iter().map(|(pos, _)| *pos).collect();
+    let deadline = std::time::Instant::now() + PLACEMENT_CONFIRM_TIMEOUT;
+    loop {
+        let states = bot.get_blocks(&positions)?;
+        let mismatches: Vec<PlacementMismatch> = latest
+            .iter()
+            .zip(states)
+            .filter_map(|((pos, expected), state)| {
+                let actual = state.map(|state| block::make_block(&block::extract_block_id(&state)));
+                if let Some(actual) = &actual
+                    && actual.id == expected.id
+                    && block::properties_match(actual, expected)
+                {
+                    return None;
+                }
+                Some(PlacementMismatch {
+                    pos: *pos,
+                    expected: expected.clone(),
+                    actual,
+                })
+            })
+            .collect();
+        if mismatches.is_empty() || std::time::Instant::now() >= deadline {
+            return Ok(mismatches);
+        }
+        std::thread::sleep(std::time::Duration::from_millis(PLACEMENT_CONFIRM_POLL_MS));
+    }
+}
+
 fn query_entity_numbers(bot: &TestBot, selector: &str, path: &str) -> Result<Vec<f64>> {
-    let message = query_entity_data_message(bot, selector, path)?;
-    let values = parse_numbers_after_colon(&message);
+    let value = query_entity_data(bot, selector, path)?;
+    let values = parse_nbt_numbers(&value);
     if values.is_empty() {
-        anyhow::bail!("entity query returned no numbers: {message}");
+        anyhow::bail!("entity query returned no numbers: {value}");
     }
     Ok(values)
 }
 
-fn query_entity_count(bot: &TestBot, selector: &str) -> Result<usize> {
-    let _query_guard = bot.lock_command_query();
-    drain_chat(bot);
-    bot.send_command(&format!("execute if entity {selector}"))?;
-    let timeout = std::time::Duration::from_secs(3);
-    let started = std::time::Instant::now();
-    while started.elapsed() < timeout {
-        if let Some((sender, message)) =
-            bot.recv_chat_timeout(std::time::Duration::from_millis(tick::CHAT_POLL_TIMEOUT_MS))
-        {
-            if sender.is_some() {
-                continue;
-            }
-            if message.contains("Test failed") || message.contains("No entity was found") {
-                return Ok(0);
-            }
-            if let Some(count) = message
-                .split(|character: char| !character.is_ascii_digit())
-                .rfind(|part| !part.is_empty())
-                .and_then(|part| part.parse::<usize>().ok())
-            {
-                return Ok(count);
-            }
-        }
-    }
-    anyhow::bail!("timed out counting entities matching {selector}")
+pub(crate) fn query_entity_count(bot: &TestBot, selector: &str) -> Result<usize> {
+    let count = bot.query_score(&format!("if entity {selector}"))?;
+    Ok(count.max(0) as usize)
 }
 
 pub(crate) fn query_daytime(bot: &TestBot) -> Result<u64> {
-    query_time_command(bot, "time query minecraft:day", "daytime").map(|time| time % 24_000)
+    query_time(bot, "minecraft:day").map(|time| time % 24_000)
 }
 
 pub(crate) fn query_gametime(bot: &TestBot) -> Result<u64> {
-    query_time_command(bot, "time query gametime", "game time")
+    query_time(bot, "gametime")
 }
 
-fn query_time_command(bot: &TestBot, command: &str, label: &str) -> Result<u64> {
-    let _query_guard = bot.lock_command_query();
-    drain_chat(bot);
-
-    bot.send_command(command)?;
-    let timeout = std::time::Duration::from_secs(3);
-    let started = std::time::Instant::now();
-    while started.elapsed() < timeout {
-        if let Some((sender, message)) =
-            bot.recv_chat_timeout(std::time::Duration::from_millis(tick::CHAT_POLL_TIMEOUT_MS))
-        {
-            if sender.is_some() || !message.to_ascii_lowercase().contains("time") {
-                continue;
-            }
-            if let Some(time) = message
-                .split(|character: char| !character.is_ascii_digit())
-                .rfind(|part| !part.is_empty())
-                .and_then(|part| part.parse::<u64>().ok())
-            {
-                return Ok(time);
-            }
-            anyhow::bail!("time query returned no numeric {label}: {message}");
-        }
-    }
-    anyhow::bail!("timed out querying world {label}")
-}
-
-fn query_entity_data(bot: &TestBot, selector: &str, path: &str) -> Result<String> {
-    let message = query_entity_data_message(bot, selector, path)?;
-    Ok(message
-        .split_once(':')
-        .map(|(_, value)| value.trim().to_string())
-        .unwrap_or_else(|| message.trim().to_string()))
-}
-
-fn query_block_data(bot: &TestBot, pos: BlockPos, path: &str) -> Result<String> {
-    let _query_guard = bot.lock_command_query();
-    drain_chat(bot);
-    bot.send_command(&format!(
-        "data get block {} {} {} {path}",
-        pos[0], pos[1], pos[2]
-    ))?;
-    let timeout = std::time::Duration::from_secs(3);
-    let started = std::time::Instant::now();
-    while started.elapsed() < timeout {
-        if let Some((sender, message)) =
-            bot.recv_chat_timeout(std::time::Duration::from_millis(tick::CHAT_POLL_TIMEOUT_MS))
-        {
-            if sender.is_some() {
-                continue;
-            }
-            if message.contains("is not a block entity")
-                || message.contains("Found no elements")
-                || message.contains("No elements matching")
-            {
-                anyhow::bail!("block entity query failed: {message}");
-            }
-            if message.contains(path) || message.contains("block data") {
-                return Ok(message
-                    .split_once(':')
-                    .map(|(_, value)| value.trim().to_string())
-                    .unwrap_or_else(|| message.trim().to_string()));
-            }
-        }
-    }
-    anyhow::bail!(
-        "timed out querying block entity at [{}, {}, {}] {path}",
-        pos[0],
-        pos[1],
-        pos[2]
-    )
+fn query_time(bot: &TestBot, kind: &str) -> Result<u64> {
+    let time = bot.query_score(&format!("run time query {kind}"))?;
+    u64::try_from(time).map_err(|_| anyhow::anyhow!("time query returned {time} for {kind}"))
 }
 
-fn query_entity_data_message(bot: &TestBot, selector: &str, path: &str) -> Result<String> {
-    let _query_guard = bot.lock_command_query();
-    drain_chat(bot);
-
-    bot.send_command(&format!("data get entity {selector} {path}"))?;
-    let timeout = std::time::Duration::from_secs(3);
-    let started = std::time::Instant::now();
-
-    while started.elapsed() < timeout {
-        if let Some((_, message)) =
-            bot.recv_chat_timeout(std::time::Duration::from_millis(tick::CHAT_POLL_TIMEOUT_MS))
-        {
-            if message.contains("No entity was found") || message.contains("Found no elements") {
-                anyhow::bail!("entity query failed: {message}");
-            }
-            if message.contains(path) || message.contains("entity data") {
-                return Ok(message);
-            }
-        }
-    }
-
-    anyhow::bail!("timed out querying entity {selector} {path}")
+pub(crate) fn query_entity_data(bot: &TestBot, selector: &str, path: &str) -> Result<String> {
+    bot.query_entity_nbt(selector, path)?
+        .ok_or_else(|| anyhow::anyhow!("entity {selector} has no {path}"))
 }
 
-fn drain_chat(bot: &TestBot) {
-    while bot
-        .recv_chat_timeout(std::time::Duration::from_millis(
-            tick::CHAT_DRAIN_TIMEOUT_MS,
-        ))
-        .is_some()
-    {}
+pub(crate) fn query_block_data(bot: &TestBot, pos: BlockPos, path: &str) -> Result<String> {
+    bot.query_block_nbt(pos, path)?
+        .ok_or_else(|| anyhow::anyhow!("block entity at {pos:?} has no {path}"))
 }
 
-fn parse_numbers_after_colon(message: &str) -> Vec<f64> {
-    let value_part = message
-        .split_once(':')
-        .map(|(_, value)| value)
-        .unwrap_or(message);
-    value_part
+/// Numbers of an SNBT value such as `[1.5d, 64.0d, 2.5d]` or `3b`
+pub(crate) fn parse_nbt_numbers(value: &str) -> Vec<f64> {
+    value
         .split(|c: char| {
             !(c.is_ascii_digit() || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E')
         })
@@ -702,7 +816,7 @@ fn reconcile_inventory_slot(
 
 #[cfg(test)]
 mod tests {
-    use super::reconcile_inventory_slot;
+    use super::{parse_nbt_numbers, reconcile_inventory_slot};
     use flint_core::test_spec::{Item, PlayerSlot};
     use std::collections::HashMap;
 
@@ -739,4 +853,13 @@ mod tests {
             Some(&Item::with_count("minecraft:oak_sign", 1))
         );
     }
+
+    #[test]
+    fn nbt_numbers_ignore_type_suffixes() {
+        assert_eq!(
+            parse_nbt_numbers("[1.5d, -64.0d, 2.5E-4d]"),
+            vec![1.5, -64.0, 2.5e-4]
+        );
+        assert_eq!(parse_nbt_numbers("3b"), vec![3.0]);
+    }
 }
@@ -0,0 +1,436 @@
+//! What the batch loop needs from a backend beyond flint-core's traits. A backend is
+//! a `FlintAdapter` whose worlds host every test of a batch; what it offers on top is
+//! optional, so any server implementation runs through the same loop as the bot.
+
+use super::adapter::MinecraftWorld;
+use anyhow::Result;
+use flint_core::BlockPos;
+use flint_core::test_spec::{Block, EntityNbt, GameMode, Item, PlayerSlot, TestSpec};
+use flint_core::traits::{EntityState, FlintAdapter, FlintPlayer, FlintWorld};
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+/// A backend that can advance its worlds several ticks at once, faster than calling
+/// `do_tick` for each
+pub trait Sprint {
+    /// Run `ticks` ticks. The adapter's worlds count them as if `do_tick` had run.
+    fn sprint(&self, ticks: u32) -> Result<()>;
+}
+
+/// A backend a batch can run on
+pub trait BatchAdapter: FlintAdapter {
+    /// A world for each test of the batch, in the test's coordinates. The worlds share
+    /// their time: ticking one ticks them all. By default the tests share one world
+    /// from `create_test_world`, each through a view of it shifted to the test's offset.
+    fn batch_worlds(
+        &self,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+    ) -> Result<Vec<Box<dyn BatchWorld>>> {
+        let world = Arc::new(Mutex::new(self.create_test_world()?));
+        Ok(tests_with_offsets
+            .iter()
+            .enumerate()
+            .map(|(test_idx, (test, offset))| {
+                Box::new(TestView {
+                    world: world.clone(),
+                    region: test.cleanup_region(),
+                    test_idx,
+                    offset: *offset,
+                    tick: None,
+                }) as Box<dyn BatchWorld>
+            })
+            .collect())
+    }
+
+    /// The backend's sprint, if it has one. Only used off the bot's server, where
+    /// time otherwise advances one `do_tick` at a time.
+    fn as_sprint(&self) -> Option<&dyn Sprint> {
+        None
+    }
+
+    /// Whether the worlds are on the server the executor's bot is connected to. The
+    /// batch then also uses the bot for what the traits have no call for: game rules
+    /// and time, force-loaded chunks, clearing entities, stepping time in lockstep with
+    /// a reference server and the chat summary.
+    fn on_server(&self) -> bool {
+        false
+    }
+}
+
+/// A test's world in a batch. The hooks default to a world whose placements take
+/// effect at once.
+pub trait BatchWorld: FlintWorld {
+    /// Send placements without waiting for each to show up, until `take_placements`
+    fn defer_placements(&mut self) {}
+
+    /// Stop deferring and return the placements still to be confirmed (world position,
+    /// block)
+    fn take_placements(&mut self) -> Vec<([i32; 3], Block)> {
+        Vec::new()
+    }
+
+    /// The batch's tick, after the bot advanced time without the world
+    fn set_current_tick(&mut self, _tick: u64) {}
+
+    /// The bot's own world for the test, which breakpoints can inspect
+    fn as_minecraft(&self) -> Option<&MinecraftWorld> {
+        None
+    }
+}
+
+/// One test's part of a shared batch world: positions are shifted by the test's
+/// offset, entity aliases are scoped to the test and type lookups only see its region
+struct TestView {
+    world: Arc<Mutex<Box<dyn FlintWorld>>>,
+    region: [[i32; 3]; 2],
+    test_idx: usize,
+    offset: [i32; 3],
+    /// Set once the bot advances time instead of the world
+    tick: Option<u64>,
+}
+
+impl TestView {
+    fn world_pos(&self, pos: BlockPos) -> BlockPos {
+        [
+            pos[0] + self.offset[0],
+            pos[1] + self.offset[1],
+            pos[2] + self.offset[2],
+        ]
+    }
+
+    fn alias(&self, alias: &str) -> String {
+        // The player alias names the one bot, not a summoned entity
+        if alias == "player" {
+            alias.to_string()
+        } else {
+            format!("t{}.{alias}", self.test_idx)
+        }
+    }
+
+    fn localize(&self, mut entity: EntityState) -> EntityState {
+        entity.pos = entity.pos.map(|pos| shift(pos, self.offset, -1.0));
+        entity
+    }
+
+    fn contains(&self, pos: [f64; 3]) -> bool {
+        let [min, max] = self.region;
+        (0..3).all(|axis| pos[axis] >= f64::from(min[axis]) && pos[axis] < f64::from(max[axis] + 1))
+    }
+}
+
+fn shift(pos: [f64; 3], offset: [i32; 3], sign: f64) -> [f64; 3] {
+    [
+        pos[0] + sign * f64::from(offset[0]),
+        pos[1] + sign * f64::from(offset[1]),
+        pos[2] + sign * f64::from(offset[2]),
+    ]
+}
+
+impl BatchWorld for TestView {
+    fn set_current_tick(&mut self, tick: u64) {
+        self.tick = Some(tick);
+    }
+}
+
+impl FlintWorld for TestView {
+    fn do_tick(&mut self) -> Result<()> {
+        self.world.lock().do_tick()
+    }
+
+    fn current_tick(&self) -> u64 {
+        self.tick
+            .unwrap_or_else(|| self.world.lock().current_tick())
+    }
+
+    fn get_time(&self) -> Result<u64> {
+        self.world.lock().get_time()
+    }
+
+    fn get_block(&self, pos: BlockPos, requested_nbt: &[String]) -> Result<Block> {
+        self.world
+            .lock()
+            .get_block(self.world_pos(pos), requested_nbt)
+    }
+
+    fn set_block(&mut self, pos: BlockPos, block: &Block) -> Result<()> {
+        let pos = self.world_pos(pos);
+        self.world.lock().set_block(pos, block)
+    }
+
+    fn summon_entity(
+        &mut self,
+        alias: &str,
+        entity_type: &str,
+        pos: [f64; 3],
+        nbt: Option<&EntityNbt>,
+    ) -> Result<()> {
+        let alias = self.alias(alias);
+        let pos = shift(pos, self.offset, 1.0);
+        self.world
+            .lock()
+            .summon_entity(&alias, entity_type, pos, nbt)
+    }
+
+    fn teleport_entity(&mut self, alias: &str, pos: [f64; 3], rot: Option<[f32; 2]>) -> Result<()> {
+        let alias = self.alias(alias);
+        let pos = shift(pos, self.offset, 1.0);
+        self.world.lock().teleport_entity(&alias, pos, rot)
+    }
+
+    fn get_entity(&self, alias: &str, requested_nbt: &[String]) -> Result<Vec<EntityState>> {
+        let entities = self
+            .world
+            .lock()
+            .get_entity(&self.alias(alias), requested_nbt)?;
+        Ok(entities
+            .into_iter()
+            .map(|entity| self.localize(entity))
+            .collect())
+    }
+
+    fn find_entity(&self, entity_type: &str, requested_nbt: &[String]) -> Result<Vec<EntityState>> {
+        let entities = self.world.lock().find_entity(entity_type, requested_nbt)?;
+        Ok(entities
+            .into_iter()
+            .map(|entity| self.localize(entity))
+            .filter(|entity| entity.pos.is_some_and(|pos| self.contains(pos)))
+            .collect())
+    }
+
+    fn create_player(&mut self) -> Box<dyn FlintPlayer> {
+        Box::new(OffsetPlayer {
+            player: self.world.lock().create_player(),
+            offset: self.offset,
+        })
+    }
+
+    fn fill(&mut self, region: [[i32; 3]; 2], block: &Block) -> Result<()> {
+        let region = [self.world_pos(region[0]), self.world_pos(region[1])];
+        self.world.lock().fill(region, block)
+    }
+}
+
+/// A player of the batch world that moves in its test's coordinates
+struct OffsetPlayer {
+    player: Box<dyn FlintPlayer>,
+    offset: [i32; 3],
+}
+
+impl FlintPlayer for OffsetPlayer {
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self.player.as_any_mut()
+    }
+
+    fn set_slot(&mut self, slot: PlayerSlot, item: Option<&Item>) -> Result<()> {
+        self.player.set_slot(slot, item)
+    }
+
+    fn get_slot(&mut self, slot: PlayerSlot, requested_data: Vec<String>) -> Result<Option<Item>> {
+        self.player.get_slot(slot, requested_data)
+    }
+
+    fn select_hotbar(&mut self, slot: u8) -> Result<()> {
+        self.player.select_hotbar(slot)
+    }
+
+    fn selected_hotbar(&self) -> u8 {
+        self.player.selected_hotbar()
+    }
+
+    fn teleport(&mut self, pos: [f64; 3], rot: Option<[f32; 2]>) -> Result<()> {
+        self.player.teleport(shift(pos, self.offset, 1.0), rot)
+    }
+
+    fn interact(&mut self) -> Result<()> {
+        self.player.interact()
+    }
+
+    fn set_game_mode(&mut self, mode: GameMode) -> Result<()> {
+        self.player.set_game_mode(mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::block;
+    use crate::executor::fake::{FakeAdapter, assert_block, place, spec};
+    use crate::executor::{TestExecutor, TestRunOutput};
+    use flint_core::traits::ServerInfo;
+
+    /// Run `tests` side by side, ten blocks apart, through the batch loop
+    fn run(adapter: &dyn BatchAdapter, tests: &[TestSpec], fail_fast: bool) -> TestRunOutput {
+        let tests_with_offsets: Vec<_> = tests
+            .iter()
+            .cloned()
+            .zip((0..).map(|idx| [idx * 10, 0, 0]))
+            .collect();
+        let mut executor = TestExecutor::new();
+        executor.set_quiet(true);
+        executor.set_fail_fast(fail_fast);
+        executor
+            .run_batch(adapter, &tests_with_offsets, false)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_placement_then_assertion() {
+        let adapter = FakeAdapter::new();
+        let tests = [
+            spec(
+                "passes",
+                vec![
+                    place(0, [1, 1, 1], "minecraft:stone"),
+                    assert_block(1, [1, 1, 1], "minecraft:stone"),
+                ],
+            ),
+            spec(
+                "fails",
+                vec![
+                    place(0, [1, 1, 1], "minecraft:stone"),
+                    assert_block(1, [1, 1, 1], "minecraft:dirt"),
+                ],
+            ),
+        ];
+        let output = run(&adapter, &tests, false);
+        assert!(output.results[0].success);
+        assert!(!output.results[1].success);
+        assert_eq!(output.failures.len(), 1);
+        assert_eq!(output.failures[0].0, "fails");
+        assert_eq!(output.mismatches.len(), 1);
+        assert_eq!(output.mismatches[0].tick, 1);
+    }
+
+    #[test]
+    fn test_scripted_tick_behaviour_is_seen_by_delayed_asserts() {
+        // Stone turns into dirt five ticks after the run starts
+        let adapter = FakeAdapter::new().on_tick(|blocks, tick| {
+            if tick == 5 && blocks.contains_key(&[1, 1, 1]) {
+                blocks.insert([1, 1, 1], block::make_block("minecraft:dirt"));
+            }
+        });
+        let tests = [spec(
+            "delayed",
+            vec![
+                place(0, [1, 1, 1], "minecraft:stone"),
+                assert_block(4, [1, 1, 1], "minecraft:stone"),
+                assert_block(5, [1, 1, 1], "minecraft:dirt"),
+            ],
+        )];
+        let output = run(&adapter, &tests, false);
+        assert!(output.results[0].success);
+        // Sprinting to the last entry ticks exactly to it
+        assert_eq!(adapter.log().lock().ticks, 5);
+    }
+
+    #[test]
+    fn test_tests_are_isolated_by_offset() {
+        let tests = [
+            spec(
+                "stone",
+                vec![
+                    place(0, [1, 1, 1], "minecraft:stone"),
+                    assert_block(1, [1, 1, 1], "minecraft:stone"),
+                ],
+            ),
+            spec(
+                "dirt",
+                vec![
+                    place(0, [1, 1, 1], "minecraft:dirt"),
+                    assert_block(1, [1, 1, 1], "minecraft:dirt"),
+                ],
+            ),
+        ];
+        let adapter = FakeAdapter::new();
+        let output = run(&adapter, &tests, false);
+        assert!(output.results.iter().all(|result| result.success));
+        assert_eq!(adapter.log().lock().fills[1], [[10, 0, 0], [13, 3, 3]]);
+    }
+
+    #[test]
+    fn test_merged_timeline_ticks_the_world_once() {
+        let adapter = FakeAdapter::new();
+        let tests = [
+            spec("short", vec![assert_block(2, [0, 0, 0], "minecraft:air")]),
+            spec("long", vec![assert_block(10, [0, 0, 0], "minecraft:air")]),
+        ];
+        let output = run(&adapter, &tests, false);
+        assert!(output.results.iter().all(|result| result.success));
+        let log = adapter.log();
+        let log = log.lock();
+        assert_eq!(log.worlds_created, 1);
+        assert_eq!(log.ticks, 10);
+    }
+
+    #[test]
+    fn test_finished_tests_are_cleaned_up_once() {
+        let adapter = FakeAdapter::new();
+        let tests = [
+            spec("short", vec![place(0, [1, 1, 1], "minecraft:stone")]),
+            spec("long", vec![assert_block(6, [1, 1, 1], "minecraft:air")]),
+        ];
+        let output = run(&adapter, &tests, false);
+        assert!(output.results.iter().all(|result| result.success));
+        // One fill per test before the run, one each after it finishes
+        assert_eq!(adapter.log().lock().fills.len(), 4);
+    }
+
+    #[test]
+    fn test_sprint_replaces_ticking_one_at_a_time() {
+        /// The fake backend, with a sprint that only records how far it was asked to go
+        #[derive(Default)]
+        struct Sprinting {
+            adapter: FakeAdapter,
+            sprints: Mutex<Vec<u32>>,
+        }
+        impl FlintAdapter for Sprinting {
+            fn create_test_world(&self) -> Result<Box<dyn FlintWorld>> {
+                self.adapter.create_test_world()
+            }
+
+            fn server_info(&self) -> ServerInfo {
+                self.adapter.server_info()
+            }
+        }
+        impl BatchAdapter for Sprinting {
+            fn as_sprint(&self) -> Option<&dyn Sprint> {
+                Some(self)
+            }
+        }
+        impl Sprint for Sprinting {
+            fn sprint(&self, ticks: u32) -> Result<()> {
+                self.sprints.lock().push(ticks);
+                Ok(())
+            }
+        }
+
+        let adapter = Sprinting::default();
+        let tests = [spec(
+            "sprints",
+            vec![
+                assert_block(1, [0, 0, 0], "minecraft:air"),
+                assert_block(3, [0, 0, 0], "minecraft:air"),
+                assert_block(10, [0, 0, 0], "minecraft:air"),
+            ],
+        )];
+        let output = run(&adapter, &tests, false);
+        assert!(output.results[0].success);
+        // Single ticks still go through the world
+        assert_eq!(*adapter.sprints.lock(), [2, 7]);
+        assert_eq!(adapter.adapter.log().lock().ticks, 1);
+    }
+
+    #[test]
+    fn test_fail_fast_stops_the_batch() {
+        let adapter = FakeAdapter::new();
+        let tests = [
+            spec("fails", vec![assert_block(1, [1, 1, 1], "minecraft:stone")]),
+            spec("later", vec![assert_block(8, [1, 1, 1], "minecraft:stone")]),
+        ];
+        let output = run(&adapter, &tests, true);
+        assert_eq!(output.mismatches.len(), 1);
+        assert_eq!(output.mismatches[0].test_name, "fails");
+        assert!(output.results[1].success);
+        assert_eq!(adapter.log().lock().ticks, 1);
+    }
+}
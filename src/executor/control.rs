@@ -0,0 +1,174 @@
+//! Out-of-game control for interactive mode.
+//!
+//! Commands can be typed into a terminal REPL on stdin or sent as lines to a local TCP
+//! port, in addition to in-game chat. Both accept the chat command set with or without
+//! the `!` prefix (`run foo`, `!record bar`), and `step`/`continue` at breakpoints.
+//! Replies go back to the source of the command instead of the in-game chat.
+
+use anyhow::{Context, Result};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, mpsc};
+
+use super::TestExecutor;
+
+/// A command line received from the terminal or a socket client
+pub struct ControlLine {
+    pub line: String,
+    pub reply: Sender<String>,
+}
+
+#[derive(Clone)]
+pub struct ControlChannel {
+    tx: Sender<ControlLine>,
+    rx: Arc<parking_lot::Mutex<Receiver<ControlLine>>>,
+}
+
+impl Default for ControlChannel {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            tx,
+            rx: Arc::new(parking_lot::Mutex::new(rx)),
+        }
+    }
+}
+
+impl ControlChannel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read commands from stdin; replies are printed to stdout.
+    pub fn spawn_stdin(&self) {
+        let tx = self.tx.clone();
+        let (reply_tx, reply_rx) = mpsc::channel::<String>();
+        std::thread::spawn(move || {
+            for reply in reply_rx {
+                println!("  {reply}");
+            }
+        });
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let command = ControlLine {
+                    line,
+                    reply: reply_tx.clone(),
+                };
+                if tx.send(command).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Accept newline-delimited commands on a TCP address. A bare port binds to
+    /// localhost.
+    pub fn listen(&self, addr: &str) -> Result<()> {
+        let addr = if addr.parse::<u16>().is_ok() {
+            format!("127.0.0.1:{addr}")
+        } else {
+            addr.to_string()
+        };
+        let listener = TcpListener::bind(&addr)
+            .with_context(|| format!("binding control socket on {addr}"))?;
+        tracing::info!("Control channel listening on {addr}");
+
+        let tx = self.tx.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let tx = tx.clone();
+                std::thread::spawn(move || {
+                    if let Err(error) = serve_client(stream, tx) {
+                        tracing::warn!("Control client disconnected: {error}");
+                    }
+                });
+            }
+        });
+        Ok(())
+    }
+
+    /// Queue a command as if it had been typed, for front ends that own their transport.
+    pub fn push(&self, command: ControlLine) {
+        let _ = self.tx.send(command);
+    }
+
+    pub fn try_recv(&self) -> Option<ControlLine> {
+        self.rx.lock().try_recv().ok()
+    }
+}
+
+fn serve_client(stream: TcpStream, tx: Sender<ControlLine>) -> Result<()> {
+    let mut writer = stream.try_clone()?;
+    let (reply_tx, reply_rx) = mpsc::channel::<String>();
+    std::thread::spawn(move || {
+        for reply in reply_rx {
+            if writeln!(writer, "{reply}").is_err() {
+                break;
+            }
+        }
+    });
+
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        tx.send(ControlLine {
+            line,
+            reply: reply_tx.clone(),
+        })?;
+    }
+    Ok(())
+}
+
+/// Turn a control line into a chat-style command (`run foo` → `!run foo`)
+pub fn as_chat_command(line: &str) -> String {
+    let line = line.trim();
+    if line.starts_with('!') {
+        line.to_string()
+    } else {
+        format!("!{line}")
+    }
+}
+
+impl TestExecutor {
+    /// Enable the control channel: a stdin REPL and, optionally, a TCP listener.
+    pub fn enable_control(&mut self, stdin: bool, listen: Option<&str>) -> Result<()> {
+        if !stdin && listen.is_none() {
+            return Ok(());
+        }
+        let control = self.control.get_or_insert_with(ControlChannel::new);
+        if stdin {
+            control.spawn_stdin();
+        }
+        if let Some(addr) = listen {
+            control.listen(addr)?;
+        }
+        Ok(())
+    }
+
+    /// The control channel, created on first use. Breakpoints wait on it for
+    /// `step`/`continue`.
+    pub fn control_channel(&mut self) -> ControlChannel {
+        self.control.get_or_insert_with(ControlChannel::new).clone()
+    }
+
+    /// Answer the command being handled: to the control client that sent it, or in
+    /// chat for commands that came from the game.
+    pub(super) fn reply(&self, message: &str) -> Result<()> {
+        match &self.reply_to {
+            Some(reply) => {
+                let _ = reply.send(message.to_string());
+                Ok(())
+            }
+            None => self.bot.send_command_synced(&format!("say {message}")),
+        }
+    }
+}
@@ -0,0 +1,296 @@
+//! In-process `FlintWorld`/`FlintPlayer` backend for testing the executor without a
+//! server: every world is a plain block map, and ticks run scripted rules over it.
+//! Also the small test specs executor scenarios are written with, for any backend.
+
+use anyhow::Result;
+use flint_core::BlockPos;
+use flint_core::test_spec::{
+    ActionType, AssertType, Block, BlockCheck, BlockPlacement, BlockSpec, CleanupSpec, EntityNbt,
+    GameMode, Item, PlayerSlot, SetupSpec, TestSpec, TickSpec, TimelineEntry,
+};
+use flint_core::traits::{EntityState, FlintAdapter, FlintPlayer, FlintWorld, ServerInfo};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::backend::BatchAdapter;
+use super::block;
+
+/// Non-air blocks of a fake world
+pub type BlockMap = HashMap<BlockPos, Block>;
+
+type TickRule = Arc<dyn Fn(&mut BlockMap, u64) + Send + Sync>;
+
+/// What the fake worlds were asked to do, shared by every world of an adapter
+#[derive(Debug, Default)]
+pub struct FakeLog {
+    pub worlds_created: usize,
+    /// `do_tick` calls, summed over all worlds
+    pub ticks: u64,
+    /// Regions passed to `fill`, in order
+    pub fills: Vec<[[i32; 3]; 2]>,
+}
+
+#[derive(Default)]
+pub struct FakeAdapter {
+    rules: Vec<TickRule>,
+    log: Arc<Mutex<FakeLog>>,
+}
+
+impl FakeAdapter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `rule` over each world's blocks after every tick, with the new tick number
+    pub fn on_tick(mut self, rule: impl Fn(&mut BlockMap, u64) + Send + Sync + 'static) -> Self {
+        self.rules.push(Arc::new(rule));
+        self
+    }
+
+    pub fn log(&self) -> Arc<Mutex<FakeLog>> {
+        self.log.clone()
+    }
+}
+
+impl FlintAdapter for FakeAdapter {
+    fn create_test_world(&self) -> Result<Box<dyn FlintWorld>> {
+        self.log.lock().worlds_created += 1;
+        Ok(Box::new(FakeWorld {
+            blocks: BlockMap::new(),
+            tick: 0,
+            entities: HashMap::new(),
+            rules: self.rules.clone(),
+            log: self.log.clone(),
+        }))
+    }
+
+    fn server_info(&self) -> ServerInfo {
+        ServerInfo {
+            minecraft_version: "fake".to_string(),
+        }
+    }
+}
+
+impl BatchAdapter for FakeAdapter {}
+
+pub struct FakeWorld {
+    blocks: BlockMap,
+    tick: u64,
+    entities: HashMap<String, EntityState>,
+    rules: Vec<TickRule>,
+    log: Arc<Mutex<FakeLog>>,
+}
+
+impl FlintWorld for FakeWorld {
+    fn do_tick(&mut self) -> Result<()> {
+        self.tick += 1;
+        for rule in &self.rules {
+            rule(&mut self.blocks, self.tick);
+        }
+        self.log.lock().ticks += 1;
+        Ok(())
+    }
+
+    fn current_tick(&self) -> u64 {
+        self.tick
+    }
+
+    fn get_time(&self) -> Result<u64> {
+        Ok(self.tick % 24_000)
+    }
+
+    fn get_block(&self, pos: BlockPos, _requested_nbt: &[String]) -> Result<Block> {
+        Ok(self
+            .blocks
+            .get(&pos)
+            .cloned()
+            .unwrap_or_else(|| block::make_block("minecraft:air")))
+    }
+
+    fn set_block(&mut self, pos: BlockPos, block: &Block) -> Result<()> {
+        if block.id == "minecraft:air" {
+            self.blocks.remove(&pos);
+        } else {
+            self.blocks.insert(pos, block.clone());
+        }
+        Ok(())
+    }
+
+    fn summon_entity(
+        &mut self,
+        alias: &str,
+        entity_type: &str,
+        pos: [f64; 3],
+        _nbt: Option<&EntityNbt>,
+    ) -> Result<()> {
+        self.entities.insert(
+            alias.to_string(),
+            EntityState {
+                entity_type: Some(entity_type.to_string()),
+                pos: Some(pos),
+                rot: None,
+                nbt: HashMap::new(),
+            },
+        );
+        Ok(())
+    }
+
+    fn teleport_entity(&mut self, alias: &str, pos: [f64; 3], rot: Option<[f32; 2]>) -> Result<()> {
+        let Some(entity) = self.entities.get_mut(alias) else {
+            anyhow::bail!("cannot teleport unknown entity alias: {alias}");
+        };
+        entity.pos = Some(pos);
+        if rot.is_some() {
+            entity.rot = rot;
+        }
+        Ok(())
+    }
+
+    fn get_entity(&self, alias: &str, _requested_nbt: &[String]) -> Result<Vec<EntityState>> {
+        Ok(self.entities.get(alias).cloned().into_iter().collect())
+    }
+
+    fn find_entity(
+        &self,
+        entity_type: &str,
+        _requested_nbt: &[String],
+    ) -> Result<Vec<EntityState>> {
+        let wanted = entity_type.trim_start_matches("minecraft:");
+        Ok(self
+            .entities
+            .values()
+            .filter(|entity| {
+                entity
+                    .entity_type
+                    .as_deref()
+                    .is_some_and(|found| found.trim_start_matches("minecraft:") == wanted)
+            })
+            .cloned()
+            .collect())
+    }
+
+    fn create_player(&mut self) -> Box<dyn FlintPlayer> {
+        Box::new(FakePlayer::default())
+    }
+
+    fn fill(&mut self, region: [[i32; 3]; 2], block: &Block) -> Result<()> {
+        let [min, max] = region;
+        for x in min[0]..=max[0] {
+            for y in min[1]..=max[1] {
+                for z in min[2]..=max[2] {
+                    self.set_block([x, y, z], block)?;
+                }
+            }
+        }
+        self.log.lock().fills.push(region);
+        Ok(())
+    }
+}
+
+pub struct FakePlayer {
+    inventory: HashMap<PlayerSlot, Item>,
+    selected_hotbar: u8,
+    position: Option<[f64; 3]>,
+}
+
+impl Default for FakePlayer {
+    fn default() -> Self {
+        Self {
+            inventory: HashMap::new(),
+            selected_hotbar: 1,
+            position: None,
+        }
+    }
+}
+
+impl FlintPlayer for FakePlayer {
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    fn set_slot(&mut self, slot: PlayerSlot, item: Option<&Item>) -> Result<()> {
+        match item {
+            Some(item) => self.inventory.insert(slot, item.clone()),
+            None => self.inventory.remove(&slot),
+        };
+        Ok(())
+    }
+
+    fn get_slot(&mut self, slot: PlayerSlot, _requested_data: Vec<String>) -> Result<Option<Item>> {
+        Ok(self.inventory.get(&slot).cloned())
+    }
+
+    fn select_hotbar(&mut self, slot: u8) -> Result<()> {
+        if !(1..=9).contains(&slot) {
+            anyhow::bail!("invalid hotbar slot {slot}");
+        }
+        self.selected_hotbar = slot;
+        Ok(())
+    }
+
+    fn selected_hotbar(&self) -> u8 {
+        self.selected_hotbar
+    }
+
+    fn teleport(&mut self, pos: [f64; 3], _rot: Option<[f32; 2]>) -> Result<()> {
+        self.position = Some(pos);
+        Ok(())
+    }
+
+    fn interact(&mut self) -> Result<()> {
+        if self.position.is_none() {
+            anyhow::bail!("player must be teleported before interacting");
+        }
+        Ok(())
+    }
+
+    fn set_game_mode(&mut self, _mode: GameMode) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A test named `name` with a 4×4×4 cleanup region at its offset
+pub fn spec(name: &str, timeline: Vec<TimelineEntry>) -> TestSpec {
+    TestSpec {
+        flint_version: None,
+        name: name.to_string(),
+        description: None,
+        tags: vec![],
+        minecraft_ids: vec![],
+        dependencies: vec![],
+        setup: Some(SetupSpec {
+            cleanup: Some(CleanupSpec {
+                region: [[0, 0, 0], [3, 3, 3]],
+            }),
+            player: None,
+            world: Default::default(),
+        }),
+        timeline,
+        breakpoints: vec![],
+    }
+}
+
+pub fn place(tick: u32, pos: [i32; 3], block: &str) -> TimelineEntry {
+    TimelineEntry {
+        at: TickSpec::Single(tick),
+        action_type: ActionType::PlaceEach {
+            blocks: vec![BlockPlacement {
+                pos,
+                block: block::make_block(block),
+            }],
+        },
+    }
+}
+
+pub fn assert_block(tick: u32, pos: [i32; 3], block: &str) -> TimelineEntry {
+    TimelineEntry {
+        at: TickSpec::Single(tick),
+        action_type: ActionType::Assert {
+            checks: vec![AssertType::Block(BlockCheck {
+                pos,
+                is: BlockSpec::Single(block::make_block(block)),
+            })],
+        },
+    }
+}
@@ -3,17 +3,30 @@
 use anyhow::Result;
 use flint_core::loader::TestLoader;
 use flint_core::spatial::pair_tests_with_offsets;
-use flint_core::test_spec::TestSpec;
+use flint_core::test_spec::{GameMode, Item, PlayerSlot, TestSpec};
 use std::path::PathBuf;
 
-use super::{DEFAULT_TESTS_DIR, TestExecutor, block, recorder, tick};
-use crate::spatial_batch::group_tests_by_world_config;
+use super::{DEFAULT_TESTS_DIR, TestExecutor, TestRunOutput, adapter, block, recorder, tick};
+use crate::bot::all_player_slots;
+use crate::spatial_batch::{BatchTest, group_tests_by_world_config};
+
+/// Tag for entities that already existed when the recorder last looked at the region
+const RECORDER_SEEN_TAG: &str = "flintmc.recorder.seen";
+
+/// Gap between the recording area and the area used to verify it on save
+const VERIFY_PADDING: i32 = 16;
+
+/// Failed assertions listed in chat when verification fails
+const MAX_REPORTED_MISMATCHES: usize = 8;
 
 /// Parse command parts from a chat message
 /// Returns (command, args) if a valid command was found
 pub fn parse_command(message: &str) -> Option<(String, Vec<String>)> {
     // Skip bot's own messages
-    if message.contains("flintmc_testbot") || message.contains("[Server]") {
+    if message.contains("flintmc_testbot")
+        || message.contains("[Server]")
+        || message.contains("[Rcon]")
+    {
         return None;
     }
 
@@ -69,39 +82,36 @@ impl TestExecutor {
     // Command handlers
 
     pub(super) fn handle_help(&mut self) -> Result<()> {
-        self.bot.send_command("say Commands:")?;
-        self.bot
-            .send_command("say !search <pattern> - Search tests by name")?;
-        self.bot
-            .send_command("say !run <test_name> [step] - Run a specific test")?;
-        self.bot.send_command("say !run-all - Run all tests")?;
-        self.bot
-            .send_command("say !run-tags <tag1,tag2> - Run tests with tags")?;
-        self.bot.send_command("say !list - List all tests")?;
-        self.bot.send_command("say !reload - Reload test files")?;
-        self.bot
-            .send_command("say Recorder: !record <name>, !tick/!next, !save, !cancel")?;
-        self.bot
-            .send_command("say Recorder actions: !assert <x> <y> <z>, !assert_changes")?;
-        self.bot.send_command(
-            "say Recorder actions: !use [item] - record tp + interact at player pose",
+        self.reply("Commands:")?;
+        self.reply("!search <pattern> - Search tests by name")?;
+        self.reply("!run <test_name> [step] - Run a specific test")?;
+        self.reply("!run-all - Run all tests")?;
+        self.reply("!run-tags <tag1,tag2> - Run tests with tags")?;
+        self.reply("!list - List all tests")?;
+        self.reply("!reload - Reload test files")?;
+        self.reply("!goto <tick> - Replay the last run and pause at that tick")?;
+        self.reply("Recorder: !record <name>, !tick/!next, !save [force], !cancel")?;
+        self.reply(
+            "Recorder actions: !assert <x> <y> <z>, !assert_changes, !assert_entity <alias>",
         )?;
-        self.bot.send_command(
-            "say Recorder actions: !pos1 <x> <y> <z>, !pos - Allow to use assert for a 3d area",
+        self.reply("Recorder actions: !use [item] - record tp + interact at player pose")?;
+        self.reply(
+            "Recorder actions: !pos1 <x> <y> <z>, !pos - Allow to use assert for a 3d area",
         )?;
-        self.bot.send_command(
-            "say Recorder actions: !sprint <tick> - ticks this ticks and asserts after each tick",
+        self.reply(
+            "Recorder actions: !sprint <tick> - ticks this ticks and asserts after each tick",
         )?;
-        self.bot.send_command("say !stop - Exit interactive mode")?;
+        self.reply(
+            "Recorder actions: !observe <ticks> - step and assert every change in the region",
+        )?;
+        self.reply("!stop - Exit interactive mode")?;
         Ok(())
     }
 
     pub(super) fn handle_list(&mut self, all_test_files: &[std::path::PathBuf]) -> Result<()> {
-        self.bot
-            .send_command(&format!("say Found {} tests:", all_test_files.len()))?;
+        self.reply(&format!("Found {} tests:", all_test_files.len()))?;
         for test in load_test_specs(all_test_files) {
-            self.bot
-                .send_command_synced(&format!("say - {}", test_label(&test)))?;
+            self.reply(&format!("- {}", test_label(&test)))?;
         }
         Ok(())
     }
@@ -115,17 +125,14 @@ impl TestExecutor {
         let mut found = 0;
         for test in load_test_specs(all_test_files) {
             if test.name.to_lowercase().contains(&pattern_lower) {
-                self.bot
-                    .send_command_synced(&format!("say - {}", test_label(&test)))?;
+                self.reply(&format!("- {}", test_label(&test)))?;
                 found += 1;
             }
         }
         if found == 0 {
-            self.bot
-                .send_command(&format!("say No tests matching '{}'", pattern))?;
+            self.reply(&format!("No tests matching '{}'", pattern))?;
         } else {
-            self.bot
-                .send_command(&format!("say Found {} matching tests", found))?;
+            self.reply(&format!("Found {} matching tests", found))?;
         }
         Ok(())
     }
@@ -138,41 +145,33 @@ impl TestExecutor {
     ) -> Result<()> {
         if let Some(test) = find_test(all_test_files, test_name) {
             if step_mode {
-                self.bot.send_command(&format!(
-                    "say Running test: {} (step mode - type 's' or 'c')",
+                self.reply(&format!(
+                    "Running test: {} (step mode - type 's' or 'c')",
                     test.name
                 ))?;
             } else {
-                self.bot
-                    .send_command(&format!("say Running test: {}", test.name))?;
+                self.reply(&format!("Running test: {}", test.name))?;
             }
 
+            self.last_run = vec![test.clone()];
             let tests_with_offsets = pair_tests_with_offsets(vec![test]);
             let output = self.run_tests_parallel(&tests_with_offsets, step_mode)?;
 
             for result in &output.results {
                 let status = if result.success { "PASS" } else { "FAIL" };
-                self.bot
-                    .send_command(&format!("say [{}] {}", status, result.test_name))?;
+                self.reply(&format!("[{}] {}", status, result.test_name))?;
             }
         } else {
-            self.bot
-                .send_command(&format!("say Test '{}' not found", test_name))?;
+            self.reply(&format!("Test '{}' not found", test_name))?;
         }
         Ok(())
     }
 
     pub(super) fn handle_run_all(&mut self, all_test_files: &[std::path::PathBuf]) -> Result<()> {
-        self.bot.send_command(&format!(
-            "say Running all {} tests...",
-            all_test_files.len()
-        ))?;
+        self.reply(&format!("Running all {} tests...", all_test_files.len()))?;
 
         let (passed, failed) = self.run_test_groups(load_test_specs(all_test_files).collect())?;
-        self.bot.send_command(&format!(
-            "say Results: {} passed, {} failed",
-            passed, failed
-        ))?;
+        self.reply(&format!("Results: {} passed, {} failed", passed, failed))?;
         Ok(())
     }
 
@@ -184,33 +183,78 @@ impl TestExecutor {
         let test_files = test_loader.collect_by_tags(tags);
 
         if test_files.is_empty() {
-            self.bot
-                .send_command(&format!("say No tests found with tags: {:?}", tags))?;
+            self.reply(&format!("No tests found with tags: {:?}", tags))?;
             return Ok(());
         }
 
-        self.bot.send_command(&format!(
-            "say Running {} tests with tags {:?}...",
+        self.reply(&format!(
+            "Running {} tests with tags {:?}...",
             test_files.len(),
             tags
         ))?;
 
         let (passed, failed) = self.run_test_groups(load_test_specs(&test_files).collect())?;
-        self.bot.send_command(&format!(
-            "say Results: {} passed, {} failed",
-            passed, failed
+        self.reply(&format!("Results: {} passed, {} failed", passed, failed))?;
+        Ok(())
+    }
+
+    /// Run the last tests again from the start and pause at the end of `tick`. The
+    /// tests' own breakpoints are skipped.
+    pub(super) fn handle_goto(&mut self, tick: u32) -> Result<()> {
+        if self.last_run.is_empty() {
+            self.reply("Nothing to replay: run a test first")?;
+            return Ok(());
+        }
+        let last_run = std::mem::take(&mut self.last_run);
+        let specs = last_run
+            .iter()
+            .cloned()
+            .map(|mut test| {
+                test.breakpoints = vec![tick];
+                test
+            })
+            .collect();
+        self.reply(&format!(
+            "Replaying {} test(s) to tick {} (type 's' or 'c' there)",
+            last_run.len(),
+            tick
         ))?;
+
+        let result = self.run_test_groups(specs);
+        self.last_run = last_run;
+        let (passed, failed) = result?;
+        self.reply(&format!("Results: {} passed, {} failed", passed, failed))?;
         Ok(())
     }
 
     fn run_test_groups(&mut self, specs: Vec<TestSpec>) -> Result<(usize, usize)> {
+        self.run_specs(specs, |_| Ok(()))
+    }
+
+    /// Run tests grouped by world config, handing each group's output to `on_output`
+    /// as soon as it completes. Tests given with their file are checked against its
+    /// snapshot sidecar. Returns (passed, failed).
+    pub fn run_specs<T: BatchTest>(
+        &mut self,
+        specs: Vec<T>,
+        mut on_output: impl FnMut(&TestRunOutput) -> Result<()>,
+    ) -> Result<(usize, usize)> {
+        self.last_run = specs.iter().map(|test| test.spec().clone()).collect();
         let mut passed = 0;
         let mut failed = 0;
         for group in group_tests_by_world_config(specs) {
-            let tests_with_offsets = pair_tests_with_offsets(group);
+            self.set_batch_files(
+                group
+                    .iter()
+                    .map(|test| test.file().map(std::path::Path::to_path_buf))
+                    .collect(),
+            );
+            let tests_with_offsets =
+                pair_tests_with_offsets(group.iter().map(|test| test.spec().clone()).collect());
             let output = self.run_tests_parallel(&tests_with_offsets, false)?;
             passed += output.results.iter().filter(|r| r.success).count();
             failed += output.results.iter().filter(|r| !r.success).count();
+            on_output(&output)?;
         }
         Ok((passed, failed))
     }
@@ -224,8 +268,18 @@ impl TestExecutor {
         player_name: Option<String>,
     ) -> Result<()> {
         if self.recorder.is_some() {
-            self.bot
-                .send_command("say Recording already in progress. Use !save or !cancel first.")?;
+            self.reply("Recording already in progress. Use !save or !cancel first.")?;
+            return Ok(());
+        }
+
+        // With an allowlist, only allowed players may be tracked; an unspecified
+        // player (e.g. from --record) defaults to the first allowed one.
+        let player_name = player_name.or_else(|| self.allowed_players.first().cloned());
+        if !self.allowed_players.is_empty() && !self.is_allowed(player_name.as_deref()) {
+            self.reply(&format!(
+                "Cannot record player {}: not in the allowlist",
+                player_name.as_deref().unwrap_or("@p")
+            ))?;
             return Ok(());
         }
 
@@ -242,9 +296,7 @@ impl TestExecutor {
                 pos[2].floor() as i32,
             ],
             Err(_) => {
-                self.bot.send_command(
-                    "say Warning: Could not get player position, using bot position",
-                )?;
+                self.reply("Warning: Could not get player position, using bot position")?;
                 self.bot.get_position().unwrap_or([0, 64, 0])
             }
         };
@@ -256,17 +308,28 @@ impl TestExecutor {
         let initial_blocks = self.scan_blocks_around(scan_center, recorder_state.scan_radius)?;
         recorder_state.snapshot = initial_blocks;
 
+        // Entities already in the region belong to the scene, not to the recording.
+        self.bot.send_command_synced(&format!(
+            "tag {} add {RECORDER_SEEN_TAG}",
+            scan_entity_selector(scan_center, recorder_state.scan_radius, "")
+        ))?;
+
+        match self.query_record_player_setup(&recorder_state) {
+            Ok(player) => recorder_state.player_setup = Some(player),
+            Err(error) => {
+                tracing::warn!("Could not capture recording player inventory: {error}");
+                self.reply("Warning: Could not capture player inventory")?;
+            }
+        }
+
         self.recorder = Some(recorder_state);
 
         // Freeze time for controlled recording
         self.bot.send_command_synced("tick freeze")?;
 
-        self.bot
-            .send_command(&format!("say Recording started: {}", test_name))?;
-        self.bot
-            .send_command("say Time frozen. Block changes will be detected automatically!")?;
-        self.bot
-            .send_command("say Commands: !assert, !use, !tick, !save, !cancel")?;
+        self.reply(&format!("Recording started: {}", test_name))?;
+        self.reply("Time frozen. Block changes will be detected automatically!")?;
+        self.reply("Commands: !assert, !assert_entity, !use, !tick, !save, !cancel")?;
 
         Ok(())
     }
@@ -274,8 +337,7 @@ impl TestExecutor {
     pub(super) fn handle_record_tick(&mut self) -> Result<()> {
         // Check if recorder exists first
         if self.recorder.is_none() {
-            self.bot
-                .send_command("say No recording in progress. Use !record <name> to start.")?;
+            self.reply("No recording in progress. Use !record <name> to start.")?;
             return Ok(());
         }
 
@@ -292,8 +354,8 @@ impl TestExecutor {
         recorder.next_tick();
         let new_tick = recorder.current_tick;
 
-        self.bot.send_command(&format!(
-            "say Stepped game tick, now recording tick {} (was {})",
+        self.reply(&format!(
+            "Stepped game tick, now recording tick {} (was {})",
             new_tick, current_tick
         ))?;
 
@@ -312,11 +374,16 @@ impl TestExecutor {
     }
 
     pub(super) fn handle_record_assert(&mut self, args: &[String]) -> Result<()> {
+        self.record_block_asserts(args, false)
+    }
+
+    /// Record the blocks at a position (or the `!pos1` area) either as plain assertions
+    /// or as `!sprint` state samples.
+    fn record_block_asserts(&mut self, args: &[String], sample: bool) -> Result<()> {
         let _recorder = match self.recorder.as_mut() {
             Some(r) => r,
             None => {
-                self.bot
-                    .send_command("say No recording in progress. Use !record <name> to start.")?;
+                self.reply("No recording in progress. Use !record <name> to start.")?;
                 return Ok(());
             }
         };
@@ -350,15 +417,19 @@ impl TestExecutor {
             if let Some(block_str) = self.bot.get_block(pos)? {
                 let block_id = block::extract_block_id(&block_str);
                 let recorder = self.recorder.as_mut().unwrap();
-                recorder.add_assertion(pos, &block_id);
+                if sample {
+                    recorder.add_state_sample(pos, &block_id);
+                } else {
+                    recorder.add_assertion(pos, &block_id);
+                }
 
-                self.bot.send_command(&format!(
-                    "say Added assert at [{}, {}, {}] = {}",
+                self.reply(&format!(
+                    "Added assert at [{}, {}, {}] = {}",
                     pos[0], pos[1], pos[2], block_id
                 ))?;
             } else {
-                self.bot.send_command(&format!(
-                    "say No block found at [{}, {}, {}]",
+                self.reply(&format!(
+                    "No block found at [{}, {}, {}]",
                     pos[0], pos[1], pos[2]
                 ))?;
             }
@@ -368,13 +439,13 @@ impl TestExecutor {
 
     pub(super) fn handle_record_assert_changes(&mut self) -> Result<()> {
         let Some(recorder) = self.require_recorder() else {
-            self.bot.send_command("say No recording in progress.")?;
+            self.reply("No recording in progress.")?;
             return Ok(());
         };
 
         let count = recorder.convert_actions_to_asserts();
-        self.bot.send_command(&format!(
-            "say Converted {} actions to assertions for this tick.",
+        self.reply(&format!(
+            "Converted {} actions to assertions for this tick.",
             count
         ))?;
         Ok(())
@@ -382,14 +453,13 @@ impl TestExecutor {
 
     pub(super) fn handle_record_use(&mut self, args: &[String]) -> Result<()> {
         if self.recorder.is_none() {
-            self.bot
-                .send_command("say No recording in progress. Use !record <name> to start.")?;
+            self.reply("No recording in progress. Use !record <name> to start.")?;
             return Ok(());
         }
 
         let item = args.first().cloned();
         if args.len() > 1 {
-            self.bot.send_command("say Usage: !use [item]")?;
+            self.reply("Usage: !use [item]")?;
             return Ok(());
         }
 
@@ -403,8 +473,8 @@ impl TestExecutor {
         let recorder = self.recorder.as_mut().unwrap();
         recorder.record_use(pos, Some(rot), item.clone());
 
-        self.bot.send_command(&format!(
-            "say Recorded use at [{:.2}, {:.2}, {:.2}] rot [{:.1}, {:.1}]{}",
+        self.reply(&format!(
+            "Recorded use at [{:.2}, {:.2}, {:.2}] rot [{:.1}, {:.1}]{}",
             pos[0],
             pos[1],
             pos[2],
@@ -417,37 +487,67 @@ impl TestExecutor {
         Ok(())
     }
 
-    pub(super) fn handle_record_save(&mut self) -> Result<bool> {
-        let Some(recorder) = self.recorder.take() else {
-            self.bot.send_command("say No recording in progress.")?;
+    pub(super) fn handle_record_save(&mut self, force: bool) -> Result<bool> {
+        let Some(mut recorder) = self.recorder.take() else {
+            self.reply("No recording in progress.")?;
             return Ok(false);
         };
 
         // Check if there's anything to save
         if recorder.timeline.is_empty() {
-            self.bot
-                .send_command("say Warning: No actions recorded! Test will be empty.")?;
+            self.reply("Warning: No actions recorded! Test will be empty.")?;
+        } else if !force {
+            let mismatches = match self.verify_recording(&recorder) {
+                Ok(mismatches) => mismatches,
+                Err(e) => vec![format!("verification run failed: {e}")],
+            };
+
+            if !mismatches.is_empty() {
+                self.reply(&format!(
+                    "Verification failed: {} assertion(s) did not reproduce",
+                    mismatches.len()
+                ))?;
+                for mismatch in mismatches.iter().take(MAX_REPORTED_MISMATCHES) {
+                    self.reply(&format!("- {}", mismatch))?;
+                }
+                if mismatches.len() > MAX_REPORTED_MISMATCHES {
+                    self.reply(&format!(
+                        "... and {} more",
+                        mismatches.len() - MAX_REPORTED_MISMATCHES
+                    ))?;
+                }
+                self.reply("Keep editing and !save again, or use !save force to save anyway")?;
+
+                // The verification run unfroze time; resume recording from the current world.
+                self.bot.send_command_synced("tick freeze")?;
+                recorder.snapshot = self.scan_blocks_around(
+                    recorder.scan_center.unwrap_or([0, 64, 0]),
+                    recorder.scan_radius,
+                )?;
+                self.recorder = Some(recorder);
+                return Ok(false);
+            }
+
+            self.reply("Verification passed, recording is reproducible")?;
         }
 
         match recorder.save() {
             Ok(path) => {
-                self.bot.send_command(&format!(
-                    "say Test saved to: {}",
+                self.reply(&format!(
+                    "Test saved to: {}",
                     path.file_name().unwrap_or_default().to_string_lossy()
                 ))?;
                 println!("Test saved to: {}", path.display());
 
                 // Print execution commands
-                self.bot
-                    .send_command(&format!("say To execute: !run {}", recorder.test_name))?;
+                self.reply(&format!("To execute: !run {}", recorder.test_name))?;
                 println!(
                     "To execute this test locally:\ncargo run -- --server localhost:25565 {}",
                     recorder.test_name
                 );
             }
             Err(e) => {
-                self.bot
-                    .send_command(&format!("say Failed to save test: {}", e))?;
+                self.reply(&format!("Failed to save test: {}", e))?;
                 eprintln!("Failed to save: {}", e);
                 return Err(e);
             }
@@ -459,11 +559,42 @@ impl TestExecutor {
         Ok(true)
     }
 
+    /// Replay the generated test in a fresh area next to the recording and return a
+    /// description of every assertion that did not reproduce
+    fn verify_recording(&mut self, recorder: &recorder::RecorderState) -> Result<Vec<String>> {
+        let Some(origin) = recorder.origin else {
+            return Ok(Vec::new());
+        };
+
+        let spec = recorder.generate_test_spec();
+        let region = spec.cleanup_region();
+        let shift = recorder.scan_radius * 2 + (region[1][0] - region[0][0] + 1) + VERIFY_PADDING;
+        let offset = [origin[0] + shift, origin[1], origin[2]];
+
+        self.reply(&format!(
+            "Verifying recording at [{}, {}, {}]...",
+            offset[0], offset[1], offset[2]
+        ))?;
+
+        // A verification run must report every mismatch and must not touch the events file.
+        let events_path = self.events_path.take();
+        let fail_fast = std::mem::replace(&mut self.fail_fast, false);
+        let output = self.run_tests_parallel(&[(spec, offset)], false);
+        self.events_path = events_path;
+        self.fail_fast = fail_fast;
+
+        Ok(output?
+            .mismatches
+            .into_iter()
+            .map(|mismatch| format!("tick {}: {}", mismatch.tick, mismatch.message))
+            .collect())
+    }
+
     pub(super) fn handle_record_snapshot(&mut self) -> Result<()> {
         let recorder = match self.recorder.as_ref() {
             Some(r) => r,
             None => {
-                self.bot.send_command("say No recording in progress.")?;
+                self.reply("No recording in progress.")?;
                 return Ok(());
             }
         };
@@ -471,7 +602,7 @@ impl TestExecutor {
         let scan_radius = recorder.scan_radius;
         let scan_center = recorder.scan_center.unwrap_or([0, 64, 0]);
 
-        self.bot.send_command("say Scanning for block changes...")?;
+        self.reply("Scanning for block changes...")?;
 
         // Scan current blocks
         let current_blocks = self.scan_blocks_around(scan_center, scan_radius)?;
@@ -518,8 +649,75 @@ impl TestExecutor {
             }
         }
 
-        self.bot
-            .send_command(&format!("say Found {} block changes", changes))?;
+        self.reply(&format!("Found {} block changes", changes))?;
+
+        self.record_new_entities()?;
+        Ok(())
+    }
+
+    /// Record a `summon` for every entity that entered the scan region since the last
+    /// snapshot. Each entity is tagged with its alias so `!assert_entity` can find it.
+    fn record_new_entities(&mut self) -> Result<usize> {
+        let Some(recorder) = self.recorder.as_ref() else {
+            return Ok(0);
+        };
+        let unseen = scan_entity_selector(
+            recorder.scan_center.unwrap_or([0, 64, 0]),
+            recorder.scan_radius,
+            &format!(",tag=!{RECORDER_SEEN_TAG}"),
+        );
+
+        let count = adapter::query_entity_count(&self.bot, &unseen)?;
+        for _ in 0..count {
+            let selector = unseen.replacen("@e[", "@e[sort=nearest,limit=1,", 1);
+            let entity_type = adapter::query_entity_data(&self.bot, &selector, "id")?
+                .trim_matches('"')
+                .to_string();
+            let pos = self.query_entity_vec3(&selector, "Pos")?;
+
+            let recorder = self.recorder.as_mut().unwrap();
+            let alias = recorder.next_entity_alias(&entity_type);
+            recorder.record_summon(&alias, &entity_type, pos);
+
+            self.bot
+                .send_command_synced(&format!("tag {selector} add flintmc.entity.{alias}"))?;
+            self.bot.send_command_synced(&format!(
+                "tag @e[tag=flintmc.entity.{alias}] add {RECORDER_SEEN_TAG}"
+            ))?;
+            self.reply(&format!(
+                "Recorded summon of {} as '{}'",
+                entity_type, alias
+            ))?;
+        }
+        Ok(count)
+    }
+
+    pub(super) fn handle_record_assert_entity(&mut self, alias: &str) -> Result<()> {
+        let Some(recorder) = self.recorder.as_ref() else {
+            self.reply("No recording in progress. Use !record <name> to start.")?;
+            return Ok(());
+        };
+        if !recorder.entity_aliases.contains_key(alias) {
+            self.reply(&format!("Unknown entity alias '{}'", alias))?;
+            return Ok(());
+        }
+
+        let pos = match self
+            .query_entity_vec3(&format!("@e[tag=flintmc.entity.{alias},limit=1]"), "Pos")
+        {
+            Ok(pos) => pos,
+            Err(_) => {
+                self.reply(&format!("Entity '{}' is no longer present", alias))?;
+                return Ok(());
+            }
+        };
+
+        let recorder = self.recorder.as_mut().unwrap();
+        recorder.add_entity_assertion(alias, pos)?;
+        self.reply(&format!(
+            "Added entity assert {} at [{:.2}, {:.2}, {:.2}]",
+            alias, pos[0], pos[1], pos[2]
+        ))?;
         Ok(())
     }
 
@@ -527,9 +725,9 @@ impl TestExecutor {
         if self.recorder.take().is_some() {
             // Unfreeze time after cancelling
             self.bot.send_command("tick unfreeze")?;
-            self.bot.send_command("say Recording cancelled.")?;
+            self.reply("Recording cancelled.")?;
         } else {
-            self.bot.send_command("say No recording in progress.")?;
+            self.reply("No recording in progress.")?;
         }
         Ok(())
     }
@@ -537,11 +735,49 @@ impl TestExecutor {
     pub(super) fn handle_record_sprint(&mut self, ticks: u32) -> Result<()> {
         for _ in 0..ticks {
             self.handle_record_tick()?;
-            self.handle_record_assert(&self.last_assert_pos.clone())?;
+            self.record_block_asserts(&self.last_assert_pos.clone(), true)?;
         }
         Ok(())
     }
 
+    pub(super) fn handle_record_observe(&mut self, ticks: u32) -> Result<()> {
+        if self.recorder.is_none() {
+            self.reply("No recording in progress. Use !record <name> to start.")?;
+            return Ok(());
+        }
+
+        // Capture what the player changed before the observed ticks start.
+        self.handle_record_snapshot()?;
+
+        let mut total = 0;
+        for _ in 0..ticks {
+            tick::step_tick(&mut self.bot, false)?;
+
+            let recorder = self.recorder.as_mut().unwrap();
+            recorder.next_tick();
+            let scan_radius = recorder.scan_radius;
+            let scan_center = recorder.scan_center.unwrap_or([0, 64, 0]);
+
+            let current_blocks = self.scan_blocks_around(scan_center, scan_radius)?;
+            let recorder = self.recorder.as_mut().unwrap();
+            let changes = recorder.observe_changes(current_blocks);
+            let current_tick = recorder.current_tick;
+            total += changes;
+            if changes > 0 {
+                self.reply(&format!(
+                    "Tick {}: {} block changes asserted",
+                    current_tick, changes
+                ))?;
+            }
+        }
+
+        self.reply(&format!(
+            "Observed {} ticks, {} assertions recorded",
+            ticks, total
+        ))?;
+        Ok(())
+    }
+
     fn query_record_player_pose(
         &self,
         recorder: &recorder::RecorderState,
@@ -552,6 +788,37 @@ impl TestExecutor {
         Ok((pos, rot))
     }
 
+    /// Capture the tracked player's hotbar, offhand, armor, selected slot and game mode
+    fn query_record_player_setup(
+        &self,
+        recorder: &recorder::RecorderState,
+    ) -> Result<recorder::RecordedPlayer> {
+        let target = recorder.player_name.as_deref().unwrap_or("@p");
+
+        let mut inventory = Vec::new();
+        for slot in all_player_slots() {
+            if let Some(message) = self.query_entity_message(target, &player_slot_nbt_path(slot))?
+                && let Some(item) = parse_item_data(&message)
+            {
+                inventory.push((slot, item));
+            }
+        }
+
+        let selected_slot = self.query_entity_numbers(target, "SelectedItemSlot")?;
+        let game_mode = match self.query_entity_numbers(target, "playerGameType")?.first() {
+            Some(mode) if *mode == 0.0 => GameMode::Survival,
+            Some(mode) if *mode == 2.0 => GameMode::Adventure,
+            Some(mode) if *mode == 3.0 => GameMode::Spectator,
+            _ => GameMode::Creative,
+        };
+
+        Ok(recorder::RecordedPlayer {
+            inventory,
+            selected_hotbar: selected_slot.first().map_or(1, |slot| *slot as u8 + 1),
+            game_mode,
+        })
+    }
+
     fn query_entity_vec3(&self, target: &str, path: &str) -> Result<[f64; 3]> {
         let values = self.query_entity_numbers(target, path)?;
         if values.len() < 3 {
@@ -569,38 +836,21 @@ impl TestExecutor {
     }
 
     fn query_entity_numbers(&self, target: &str, path: &str) -> Result<Vec<f64>> {
-        validate_entity_target(target)?;
-        while self
-            .bot
-            .recv_chat_timeout(std::time::Duration::from_millis(
-                tick::CHAT_DRAIN_TIMEOUT_MS,
-            ))
-            .is_some()
-        {}
-        self.bot
-            .send_command(&format!("data get entity {target} {path}"))?;
-
-        let timeout = std::time::Duration::from_secs(3);
-        let started = std::time::Instant::now();
-        while started.elapsed() < timeout {
-            if let Some((_, message)) = self
-                .bot
-                .recv_chat_timeout(std::time::Duration::from_millis(tick::CHAT_POLL_TIMEOUT_MS))
-            {
-                if message.contains(path) || message.contains("entity data") {
-                    let values = parse_numbers_after_colon(&message);
-                    if !values.is_empty() {
-                        return Ok(values);
-                    }
-                }
-                if message.contains("No entity was found") || message.contains("Found no elements")
-                {
-                    anyhow::bail!("failed to query entity {target} {path}: {message}");
-                }
-            }
+        let Some(value) = self.query_entity_message(target, path)? else {
+            anyhow::bail!("failed to query entity {target} {path}: no elements found");
+        };
+        let values = adapter::parse_nbt_numbers(&value);
+        if values.is_empty() {
+            anyhow::bail!("entity {target} {path} query returned no numbers: {value}");
         }
+        Ok(values)
+    }
 
-        anyhow::bail!("timed out querying entity {target} {path}")
+    /// Query an entity data path. Returns `None` when the entity exists but has no
+    /// value at `path` (e.g. an empty inventory slot).
+    fn query_entity_message(&self, target: &str, path: &str) -> Result<Option<String>> {
+        validate_entity_target(target)?;
+        self.bot.query_entity_nbt(target, path)
     }
 }
 
@@ -615,21 +865,64 @@ fn validate_entity_target(target: &str) -> Result<()> {
     Ok(())
 }
 
-fn parse_numbers_after_colon(message: &str) -> Vec<f64> {
-    let value_part = message
-        .split_once(':')
-        .map(|(_, value)| value)
-        .unwrap_or(message);
-    value_part
-        .split(|c: char| {
-            !(c.is_ascii_digit() || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E')
-        })
-        .filter_map(|part| {
-            if part.is_empty() || part == "-" || part == "+" || part == "." {
-                None
-            } else {
-                part.parse::<f64>().ok()
-            }
+/// Selector for non-player entities inside the recorder's scan cube
+fn scan_entity_selector(center: [i32; 3], radius: i32, extra: &str) -> String {
+    format!(
+        "@e[type=!minecraft:player,x={},y={},z={},dx={},dy={},dz={}{extra}]",
+        center[0] - radius,
+        center[1] - radius,
+        center[2] - radius,
+        radius * 2,
+        radius * 2,
+        radius * 2
+    )
+}
+
+/// Player NBT path holding an inventory slot (1.21.5+ keeps offhand and armor in
+/// `equipment`)
+fn player_slot_nbt_path(slot: PlayerSlot) -> String {
+    let equipment = match slot {
+        PlayerSlot::Hotbar1 => return "Inventory[{Slot:0b}]".to_string(),
+        PlayerSlot::Hotbar2 => return "Inventory[{Slot:1b}]".to_string(),
+        PlayerSlot::Hotbar3 => return "Inventory[{Slot:2b}]".to_string(),
+        PlayerSlot::Hotbar4 => return "Inventory[{Slot:3b}]".to_string(),
+        PlayerSlot::Hotbar5 => return "Inventory[{Slot:4b}]".to_string(),
+        PlayerSlot::Hotbar6 => return "Inventory[{Slot:5b}]".to_string(),
+        PlayerSlot::Hotbar7 => return "Inventory[{Slot:6b}]".to_string(),
+        PlayerSlot::Hotbar8 => return "Inventory[{Slot:7b}]".to_string(),
+        PlayerSlot::Hotbar9 => return "Inventory[{Slot:8b}]".to_string(),
+        PlayerSlot::OffHand => "offhand",
+        PlayerSlot::Helmet => "head",
+        PlayerSlot::Chestplate => "chest",
+        PlayerSlot::Leggings => "legs",
+        PlayerSlot::Boots => "feet",
+    };
+    format!("equipment.{equipment}")
+}
+
+/// Parse `{count: 3, id: "minecraft:stone", ...}` from an item's SNBT, with or
+/// without spaces after the keys
+fn parse_item_data(snbt: &str) -> Option<Item> {
+    let id = snbt_field(snbt, "id")?;
+    let id = id.trim_start_matches('"');
+    let id = &id[..id.find('"')?];
+
+    let count = snbt_field(snbt, "count")
+        .and_then(|rest| {
+            rest.split(|c: char| !c.is_ascii_digit())
+                .next()
+                .and_then(|digits| digits.parse::<u8>().ok())
         })
-        .collect()
+        .unwrap_or(1);
+    Some(Item::with_count(id, count))
+}
+
+/// The text following the first `key:` of an SNBT compound
+fn snbt_field<'a>(snbt: &'a str, key: &str) -> Option<&'a str> {
+    let pattern = format!("{key}:");
+    snbt.match_indices(&pattern).find_map(|(start, _)| {
+        let preceding = snbt[..start].chars().next_back();
+        matches!(preceding, Some('{' | ',' | ' '))
+            .then(|| snbt[start + pattern.len()..].trim_start())
+    })
 }
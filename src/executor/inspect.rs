@@ -0,0 +1,319 @@
+//! World inspection while paused at a breakpoint.
+//!
+//! Commands take test-local coordinates of the focused test (the first test of the
+//! batch unless changed with `focus`) and read the world through the same
+//! `FlintWorld`/`FlintPlayer` calls that assertions use.
+
+use flint_core::test_spec::TestSpec;
+use flint_core::traits::{FlintPlayer, FlintWorld};
+
+use super::backend::BatchWorld;
+use super::block;
+use super::handlers::parse_command;
+use crate::bot::{all_player_slots, slot_to_minecraft_name};
+
+/// Widest Y-slice `region` prints; larger areas are cut off at this many columns/rows
+const MAX_SLICE_SIZE: usize = 48;
+
+/// Legend characters for the blocks of a slice, in order of first appearance
+const SLICE_SYMBOLS: &str = "#abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+pub const INSPECT_HELP: &str =
+    "Inspect: inspect <x> <y> <z> [nbt...], region [y], entities, inventory, tests, focus <test>";
+
+pub struct Inspector<'a> {
+    tests: &'a [(TestSpec, [i32; 3])],
+    worlds: &'a [Box<dyn BatchWorld>],
+    players: &'a mut [Option<Box<dyn FlintPlayer>>],
+    focus: usize,
+}
+
+impl<'a> Inspector<'a> {
+    pub fn new(
+        tests: &'a [(TestSpec, [i32; 3])],
+        worlds: &'a [Box<dyn BatchWorld>],
+        players: &'a mut [Option<Box<dyn FlintPlayer>>],
+    ) -> Self {
+        Self {
+            tests,
+            worlds,
+            players,
+            focus: 0,
+        }
+    }
+
+    /// Inspect the test at `index` of the batch
+    pub fn focus_on(&mut self, index: usize) {
+        self.focus = index.min(self.tests.len().saturating_sub(1));
+    }
+
+    /// Handle an inspection command in chat form (`!inspect 0 1 0`). Returns the reply
+    /// lines, or `None` if the message isn't an inspection command.
+    pub fn handle(&mut self, message: &str) -> Option<Vec<String>> {
+        let (command, args) = parse_command(message)?;
+        let reply = match command.as_str() {
+            "!inspect" => self.inspect(&args),
+            "!region" => self.region(&args),
+            "!entities" => self.entities(),
+            "!inventory" => self.inventory(),
+            "!tests" => self.list_tests(),
+            "!focus" => self.set_focus(&args),
+            _ => return None,
+        };
+        Some(reply.unwrap_or_else(|error| vec![format!("Error: {error:#}")]))
+    }
+
+    fn focused(&self) -> anyhow::Result<(&'a TestSpec, &'a dyn BatchWorld)> {
+        let (test, _) = self
+            .tests
+            .get(self.focus)
+            .ok_or_else(|| anyhow::anyhow!("no test in this batch"))?;
+        Ok((test, self.worlds[self.focus].as_ref()))
+    }
+
+    fn inspect(&self, args: &[String]) -> anyhow::Result<Vec<String>> {
+        let Some(pos) = parse_pos(args) else {
+            anyhow::bail!("Usage: inspect <x> <y> <z> [nbt...]");
+        };
+        let requested_nbt = &args[3..];
+        let (test, world) = self.focused()?;
+        let mut block = world.get_block(pos, requested_nbt)?;
+        let nbt = block.nbt.take();
+
+        let mut lines = vec![format!(
+            "[{}] [{}, {}, {}]: {}",
+            test.name,
+            pos[0],
+            pos[1],
+            pos[2],
+            block.to_command()
+        )];
+        if let Some(nbt) = nbt {
+            lines.push(format!("  nbt: {}", nbt.to_snbt()));
+        }
+        Ok(lines)
+    }
+
+    fn region(&self, args: &[String]) -> anyhow::Result<Vec<String>> {
+        let (test, world) = self.focused()?;
+        let [min, max] = test.cleanup_region();
+        let y = match args.first() {
+            Some(y) => y
+                .parse::<i32>()
+                .map_err(|_| anyhow::anyhow!("Usage: region [y]"))?,
+            None => min[1],
+        };
+        if !(min[1]..=max[1]).contains(&y) {
+            anyhow::bail!("y={y} is outside the test area ({}..={})", min[1], max[1]);
+        }
+
+        let rows: Vec<Vec<String>> = (min[2]..=max[2])
+            .take(MAX_SLICE_SIZE)
+            .map(|z| {
+                (min[0]..=max[0])
+                    .take(MAX_SLICE_SIZE)
+                    .map(|x| slice_block(world, [x, y, z]))
+                    .collect()
+            })
+            .collect();
+
+        let mut lines = vec![format!(
+            "[{}] y={} from [{}, {}] (x → right, z ↓)",
+            test.name, y, min[0], min[2]
+        )];
+        lines.extend(render_slice(&rows));
+        Ok(lines)
+    }
+
+    fn entities(&self) -> anyhow::Result<Vec<String>> {
+        let (test, world) = self.focused()?;
+        let Some(world) = world.as_minecraft() else {
+            anyhow::bail!("only the azalea backend tracks entities");
+        };
+        if world.entities.is_empty() {
+            return Ok(vec![format!("[{}] No tracked entities", test.name)]);
+        }
+        let mut aliases: Vec<&String> = world.entities.keys().collect();
+        aliases.sort();
+
+        let mut lines = vec![format!("[{}] Tracked entities:", test.name)];
+        for alias in aliases {
+            let states = world.get_entity(alias, &[])?;
+            let Some(state) = states.first() else {
+                lines.push(format!("  {alias}: gone"));
+                continue;
+            };
+            let entity_type = state.entity_type.as_deref().unwrap_or("?");
+            match state.pos {
+                Some([x, y, z]) => lines.push(format!(
+                    "  {alias} ({entity_type}) at [{x:.2}, {y:.2}, {z:.2}]"
+                )),
+                None => lines.push(format!("  {alias} ({entity_type})")),
+            }
+        }
+        Ok(lines)
+    }
+
+    fn inventory(&mut self) -> anyhow::Result<Vec<String>> {
+        let (test, _) = self.focused()?;
+        let Some(player) = self.players[self.focus].as_mut() else {
+            return Ok(vec![format!("[{}] No virtual player", test.name)]);
+        };
+
+        let mut lines = vec![format!(
+            "[{}] Player inventory (selected hotbar: {}):",
+            test.name,
+            player.selected_hotbar()
+        )];
+        for slot in all_player_slots() {
+            if let Some(item) = player.get_slot(slot, Vec::new())? {
+                lines.push(format!(
+                    "  {}: {} x{}",
+                    slot_to_minecraft_name(slot),
+                    item.id,
+                    item.count
+                ));
+            }
+        }
+        if lines.len() == 1 {
+            lines.push("  (empty)".to_string());
+        }
+        Ok(lines)
+    }
+
+    fn list_tests(&self) -> anyhow::Result<Vec<String>> {
+        Ok(self
+            .tests
+            .iter()
+            .enumerate()
+            .map(|(index, (test, offset))| {
+                let marker = if index == self.focus { "*" } else { " " };
+                format!(
+                    "{marker} {} at [{}, {}, {}]",
+                    test.name, offset[0], offset[1], offset[2]
+                )
+            })
+            .collect())
+    }
+
+    fn set_focus(&mut self, args: &[String]) -> anyhow::Result<Vec<String>> {
+        let name = args.join(" ").to_lowercase();
+        if name.is_empty() {
+            anyhow::bail!("Usage: focus <test>");
+        }
+        let index = self
+            .tests
+            .iter()
+            .position(|(test, _)| test.name.to_lowercase() == name)
+            .or_else(|| {
+                self.tests
+                    .iter()
+                    .position(|(test, _)| test.name.to_lowercase().contains(&name))
+            })
+            .ok_or_else(|| anyhow::anyhow!("no test matching '{name}' in this batch"))?;
+        self.focus = index;
+        Ok(vec![format!("Inspecting {}", self.tests[index].0.name)])
+    }
+}
+
+/// Block id at a test-local position, read straight from the bot's client when the
+/// world is its own
+fn slice_block(world: &dyn BatchWorld, pos: [i32; 3]) -> String {
+    let Some(world) = world.as_minecraft() else {
+        return world
+            .get_block(pos, &[])
+            .map_or_else(|_| "?".to_string(), |block| block.id);
+    };
+    let world_pos = [
+        pos[0] + world.offset[0],
+        pos[1] + world.offset[1],
+        pos[2] + world.offset[2],
+    ];
+    match world.bot.get_block(world_pos) {
+        Ok(Some(state)) => block::extract_block_id(&state),
+        _ => "?".to_string(),
+    }
+}
+
+fn parse_pos(args: &[String]) -> Option<[i32; 3]> {
+    let [x, y, z] = args.get(..3)? else {
+        return None;
+    };
+    Some([x.parse().ok()?, y.parse().ok()?, z.parse().ok()?])
+}
+
+/// Render rows of block ids as a character grid followed by its legend. Air is `.`.
+fn render_slice(rows: &[Vec<String>]) -> Vec<String> {
+    let mut assigned: Vec<(&str, char)> = Vec::new();
+    let mut available = SLICE_SYMBOLS.chars();
+
+    let mut lines: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|id| {
+                    if id == "minecraft:air" {
+                        return '.';
+                    }
+                    if let Some((_, symbol)) =
+                        assigned.iter().find(|(known, _)| *known == id.as_str())
+                    {
+                        return *symbol;
+                    }
+                    // Out of symbols: every further block shares '*'
+                    let symbol = available.next().unwrap_or('*');
+                    assigned.push((id.as_str(), symbol));
+                    symbol
+                })
+                .collect()
+        })
+        .collect();
+
+    for (id, symbol) in &assigned {
+        if *symbol != '*' {
+            lines.push(format!("  {symbol} = {id}"));
+        }
+    }
+    if assigned.iter().any(|(_, symbol)| *symbol == '*') {
+        lines.push("  * = other".to_string());
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_slice_assigns_symbols_in_order() {
+        let rows = vec![
+            vec![
+                "minecraft:stone".to_string(),
+                "minecraft:air".to_string(),
+                "minecraft:redstone_wire".to_string(),
+            ],
+            vec![
+                "minecraft:air".to_string(),
+                "minecraft:stone".to_string(),
+                "minecraft:air".to_string(),
+            ],
+        ];
+        assert_eq!(
+            render_slice(&rows),
+            vec![
+                "#.a",
+                ".#.",
+                "  # = minecraft:stone",
+                "  a = minecraft:redstone_wire",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_pos() {
+        let args: Vec<String> = ["1", "-2", "3", "Items"].map(String::from).to_vec();
+        assert_eq!(parse_pos(&args), Some([1, -2, 3]));
+        assert_eq!(parse_pos(&args[..2]), None);
+        assert_eq!(parse_pos(&["a", "0", "0"].map(String::from)), None);
+    }
+}
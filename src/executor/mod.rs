@@ -2,21 +2,30 @@
 
 mod actions;
 pub mod adapter;
-mod block;
+mod backend;
+pub(crate) mod block;
+pub(crate) mod control;
 mod events;
+#[cfg(test)]
+mod fake;
 mod handlers;
+mod inspect;
+mod rcon_backend;
 mod recorder;
+mod reference;
+mod schedule;
+mod snapshot;
 mod tick;
 
 use crate::bot::TestBot;
-use adapter::MinecraftWorld;
 use anyhow::Result;
+use backend::{BatchAdapter, BatchWorld};
 use colored::Colorize;
 use flint_core::loader::TestLoader;
 use flint_core::results::{ActionOutcome, AssertFailure, AssertPosition, TestResult};
 use flint_core::test_spec::{ActionType, AssertType, TestSpec, TimelineEntry};
 use flint_core::timeline::TimelineAggregate;
-use flint_core::traits::{FlintPlayer, FlintWorld};
+use flint_core::traits::{FlintAdapter, FlintPlayer, FlintWorld, ServerInfo};
 use std::collections::BTreeSet;
 use std::io::Write;
 
@@ -44,6 +53,51 @@ fn asserted_entity_types(test: &TestSpec) -> BTreeSet<String> {
         .collect()
 }
 
+fn is_assertion(entry: &TimelineEntry) -> bool {
+    matches!(
+        entry.action_type,
+        ActionType::Assert { .. } | ActionType::AssertState { .. }
+    )
+}
+
+fn is_placement(entry: &TimelineEntry) -> bool {
+    matches!(entry.action_type, ActionType::PlaceEach { .. })
+}
+
+/// Block positions an assertion reads
+fn asserted_positions(entry: &TimelineEntry) -> Vec<[i32; 3]> {
+    match &entry.action_type {
+        ActionType::Assert { checks } => checks
+            .iter()
+            .filter_map(|check| match check {
+                AssertType::Block(block_check) => Some(block_check.pos),
+                _ => None,
+            })
+            .collect(),
+        ActionType::AssertState { pos, .. } => vec![*pos],
+        _ => Vec::new(),
+    }
+}
+
+/// Whether `sender` is in `allowed_players`. An empty allowlist allows everyone, and
+/// messages without a verified sender are only accepted then.
+pub(crate) fn is_allowed_sender(allowed_players: &[String], sender: Option<&str>) -> bool {
+    if allowed_players.is_empty() {
+        return true;
+    }
+    sender.is_some_and(|sender| {
+        allowed_players
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(sender))
+    })
+}
+
+/// Tag of the glowing marker shown at a failed assertion with --break-on-failure
+const FAILURE_MARKER_TAG: &str = "flintmc.failure_marker";
+
+/// Reconnects allowed while running one batch before giving up
+const MAX_BATCH_RECONNECTS: usize = 3;
+
 // Progress bar constants
 const PROGRESS_BAR_WIDTH: usize = 40;
 
@@ -52,6 +106,55 @@ pub struct TestRunOutput {
     pub results: Vec<TestResult>,
     /// First failure detail per failed test: (test_name, failure_detail)
     pub failures: Vec<(String, AssertFailure)>,
+    /// Every failed assertion, in the order it happened
+    pub mismatches: Vec<AssertMismatch>,
+    /// Positions that differed from the reference server at assertion ticks
+    pub divergences: usize,
+    /// Why the batch was interrupted, once per reconnect before it completed
+    pub reconnects: Vec<String>,
+    /// Assertions whose blocks the client and server disagreed on, with
+    /// `--verify-server-side`. These neither pass nor fail their test.
+    pub desyncs: Vec<AssertMismatch>,
+}
+
+/// How batches are run against the server
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    /// The Azalea bot with every runner feature (default)
+    #[default]
+    Azalea,
+    /// The same bot driven only through the `FlintAdapter` trait, the way other
+    /// server implementations are run
+    Adapter,
+    /// Commands over RCON alone, for servers the bot can't join; blocks are read by
+    /// probing `execute if block` and tests that use the player are reported unsupported
+    Rcon,
+}
+
+/// A failed assertion, rendered for reporting
+pub struct AssertMismatch {
+    pub test_name: String,
+    pub tick: u32,
+    pub message: String,
+}
+
+impl AssertMismatch {
+    fn from_failure(test_name: &str, tick: u32, detail: &AssertFailure) -> Self {
+        let position = match detail.position() {
+            AssertPosition::Coordinate { x, y, z } => format!(" at [{x}, {y}, {z}]"),
+            _ => String::new(),
+        };
+        Self {
+            test_name: test_name.to_string(),
+            tick,
+            message: format!(
+                "expected {}, got {}{}",
+                String::from(detail.expected()),
+                String::from(detail.actual()),
+                position
+            ),
+        }
+    }
 }
 
 pub struct TestExecutor {
@@ -60,11 +163,30 @@ pub struct TestExecutor {
     verbose: bool,
     quiet: bool,
     fail_fast: bool,
+    break_on_failure: bool,
     pos1: Option<[i32; 3]>,
     last_assert_pos: Vec<String>,
     events_path: Option<std::path::PathBuf>,
     events: Option<events::JsonlWriter>,
     enable_breakpoints: bool,
+    /// Snapshot sidecar of each test in the next batch, in batch order
+    snapshot_paths: Vec<Option<std::path::PathBuf>>,
+    update_snapshots: bool,
+    /// Bot on the reference server for differential runs
+    reference: Option<TestBot>,
+    /// Players allowed to send interactive commands (empty allows everyone)
+    allowed_players: Vec<String>,
+    /// Terminal and socket commands, when enabled
+    control: Option<control::ControlChannel>,
+    /// Where replies to the command being handled go; `None` answers in chat
+    reply_to: Option<std::sync::mpsc::Sender<String>>,
+    /// Tests of the last interactive run, replayed by `!goto`
+    last_run: Vec<TestSpec>,
+    /// RCON address and password used for commands once connected
+    rcon: Option<(String, String)>,
+    backend: Backend,
+    /// Confirm assertion blocks on the server, not just in the client's world
+    verify_server_side: bool,
 }
 
 impl Default for TestExecutor {
@@ -75,11 +197,22 @@ impl Default for TestExecutor {
             verbose: false,
             quiet: false,
             fail_fast: false,
+            break_on_failure: false,
             pos1: None,
             last_assert_pos: vec![],
             events_path: None,
             events: None,
             enable_breakpoints: true,
+            snapshot_paths: Vec::new(),
+            update_snapshots: false,
+            reference: None,
+            allowed_players: Vec::new(),
+            control: None,
+            reply_to: None,
+            last_run: Vec::new(),
+            rcon: None,
+            backend: Backend::default(),
+            verify_server_side: false,
         }
     }
 }
@@ -101,6 +234,11 @@ impl TestExecutor {
         self.fail_fast = fail_fast;
     }
 
+    /// Pause at every failed assertion, before the test area is cleaned up.
+    pub fn set_break_on_failure(&mut self, enable: bool) {
+        self.break_on_failure = enable;
+    }
+
     pub fn set_enable_breakpoints(&mut self, enable: bool) {
         self.enable_breakpoints = enable;
     }
@@ -110,8 +248,65 @@ impl TestExecutor {
         self.events_path = Some(path);
     }
 
+    /// Rewrite snapshot sidecars from the server instead of comparing against them.
+    pub fn set_update_snapshots(&mut self, update: bool) {
+        self.update_snapshots = update;
+    }
+
+    /// The file each test of the next batch was loaded from, in batch order, so their
+    /// snapshot sidecars can be found. Tests without a file have no snapshots.
+    pub fn set_batch_files(&mut self, files: Vec<Option<std::path::PathBuf>>) {
+        self.snapshot_paths = files
+            .iter()
+            .map(|file| file.as_deref().map(snapshot::sidecar_path))
+            .collect();
+    }
+
+    /// Only accept interactive commands from these players.
+    pub fn set_allowed_players(&mut self, players: Vec<String>) {
+        self.allowed_players = players;
+    }
+
+    /// Whether a chat sender may control the session. Messages without a verified
+    /// sender are only accepted while no allowlist is configured.
+    fn is_allowed(&self, sender: Option<&str>) -> bool {
+        is_allowed_sender(&self.allowed_players, sender)
+    }
+
+    /// Send commands over RCON instead of chat once connected.
+    pub fn set_rcon(&mut self, addr: String, password: String) {
+        self.rcon = Some((addr, password));
+    }
+
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    /// Check every block an assertion reads against the server with `execute if block`
+    /// and report disagreements as desyncs.
+    pub fn set_verify_server_side(&mut self, verify: bool) {
+        self.verify_server_side = verify;
+    }
+
+    /// What the connected server reports about itself
+    pub fn server_info(&self) -> ServerInfo {
+        adapter::MinecraftAdapter::new(self.bot.clone()).server_info()
+    }
+
     pub fn connect(&mut self, server: &str) -> Result<()> {
-        self.bot.connect(server)
+        if self.backend == Backend::Rcon {
+            let (addr, password) = self
+                .rcon
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("--backend rcon requires --rcon"))?;
+            self.bot.read_server_version(server);
+            return self.bot.connect_rcon_only(addr, password);
+        }
+        self.bot.connect(server)?;
+        if let Some((addr, password)) = &self.rcon {
+            self.bot.connect_rcon(addr, password)?;
+        }
+        Ok(())
     }
 
     /// Start a recording session before or during interactive mode.
@@ -173,163 +368,201 @@ impl TestExecutor {
         let mut all_test_files = test_loader.collect_all_test_files()?;
 
         loop {
-            // Poll for chat messages
-            if let Some((sender, message)) = self
+            // Poll for terminal/socket commands, then for chat messages
+            let (sender, message) = if let Some(command) = self
+                .control
+                .as_ref()
+                .and_then(control::ControlChannel::try_recv)
+            {
+                self.reply_to = Some(command.reply);
+                (None, control::as_chat_command(&command.line))
+            } else if let Some((sender, message)) = self
                 .bot
                 .recv_chat_timeout(std::time::Duration::from_millis(tick::CHAT_POLL_TIMEOUT_MS))
             {
-                let Some((command, args)) = handlers::parse_command(&message) else {
-                    continue;
-                };
+                self.reply_to = None;
+                (sender, message)
+            } else {
+                continue;
+            };
+
+            let Some((command, args)) = handlers::parse_command(&message) else {
+                continue;
+            };
+
+            // Terminal and socket commands are local and always trusted.
+            if self.reply_to.is_none() && !self.is_allowed(sender.as_deref()) {
+                self.bot.send_command(&format!(
+                    "say {} is not allowed to control this session",
+                    sender.as_deref().unwrap_or("Unverified sender")
+                ))?;
+                continue;
+            }
 
-                match command.as_str() {
-                    "!help" => {
-                        self.handle_help()?;
-                    }
+            match command.as_str() {
+                "!help" => {
+                    self.handle_help()?;
+                }
 
-                    "!list" => {
-                        self.handle_list(&all_test_files)?;
+                "!list" => {
+                    self.handle_list(&all_test_files)?;
+                }
+
+                "!search" => {
+                    if args.is_empty() {
+                        self.reply("Usage: !search <pattern>")?;
+                        continue;
                     }
+                    let pattern = args.join(" ");
+                    self.handle_search(&all_test_files, &pattern)?;
+                }
 
-                    "!search" => {
-                        if args.is_empty() {
-                            self.bot.send_command("say Usage: !search <pattern>")?;
-                            continue;
-                        }
-                        let pattern = args.join(" ");
-                        self.handle_search(&all_test_files, &pattern)?;
+                "!run" => {
+                    if args.is_empty() {
+                        self.reply("Usage: !run <test_name> [step]")?;
+                        continue;
                     }
 
-                    "!run" => {
-                        if args.is_empty() {
-                            self.bot
-                                .send_command("say Usage: !run <test_name> [step]")?;
-                            continue;
-                        }
+                    // Check for step flag
+                    let (test_name, step_mode) =
+                        if args.last().map(|s| s.as_str()) == Some("step") && args.len() > 1 {
+                            (args[..args.len() - 1].join(" "), true)
+                        } else {
+                            (args.join(" "), false)
+                        };
 
-                        // Check for step flag
-                        let (test_name, step_mode) =
-                            if args.last().map(|s| s.as_str()) == Some("step") && args.len() > 1 {
-                                (args[..args.len() - 1].join(" "), true)
-                            } else {
-                                (args.join(" "), false)
-                            };
+                    self.handle_run(&all_test_files, &test_name, step_mode)?;
+                }
 
-                        self.handle_run(&all_test_files, &test_name, step_mode)?;
-                    }
+                "!run-all" => {
+                    self.handle_run_all(&all_test_files)?;
+                }
 
-                    "!run-all" => {
-                        self.handle_run_all(&all_test_files)?;
+                "!run-tags" => {
+                    if args.is_empty() {
+                        self.reply("Usage: !run-tags <tag1,tag2,...>")?;
+                        continue;
                     }
+                    let tags: Vec<String> =
+                        args[0].split(',').map(|s| s.trim().to_string()).collect();
+                    self.handle_run_tags(test_loader, &tags)?;
+                }
 
-                    "!run-tags" => {
-                        if args.is_empty() {
-                            self.bot
-                                .send_command("say Usage: !run-tags <tag1,tag2,...>")?;
-                            continue;
-                        }
-                        let tags: Vec<String> =
-                            args[0].split(',').map(|s| s.trim().to_string()).collect();
-                        self.handle_run_tags(test_loader, &tags)?;
-                    }
+                "!stop" => {
+                    self.reply("Exiting interactive mode. Goodbye!")?;
+                    return Ok(());
+                }
 
-                    "!stop" => {
-                        self.bot
-                            .send_command("say Exiting interactive mode. Goodbye!")?;
-                        return Ok(());
-                    }
+                "!goto" => match args.first().and_then(|tick| tick.parse::<u32>().ok()) {
+                    Some(tick) => self.handle_goto(tick)?,
+                    None => self.reply("Usage: !goto <tick>")?,
+                },
 
-                    "!reload" => {
-                        test_loader.verify_and_rebuild_index()?;
-                        all_test_files = test_loader.collect_all_test_files()?;
-                        self.bot.send_command(&format!(
-                            "say Reloaded {} tests",
-                            all_test_files.len()
-                        ))?;
-                    }
+                "!reload" => {
+                    test_loader.verify_and_rebuild_index()?;
+                    all_test_files = test_loader.collect_all_test_files()?;
+                    self.reply(&format!("Reloaded {} tests", all_test_files.len()))?;
+                }
 
-                    // Recorder commands
-                    "!record" => {
-                        if args.is_empty() {
-                            self.bot
-                                .send_command("say Usage: !record <test_name> [player_name]")?;
-                            self.bot.send_command(
-                                "say Example: !record my_test or !record fence/fence_connect",
-                            )?;
-                            continue;
-                        }
-                        let test_name = args[0].clone();
-                        let player_name = args.get(1).cloned().or_else(|| sender.clone());
-                        self.handle_record_start(&test_name, test_loader, player_name)?;
-                    }
-                    "!assert_changes" => {
-                        self.handle_record_assert_changes()?;
+                // Recorder commands
+                "!record" => {
+                    if args.is_empty() {
+                        self.reply("Usage: !record <test_name> [player_name]")?;
+                        self.reply("Example: !record my_test or !record fence/fence_connect")?;
+                        continue;
                     }
+                    let test_name = args[0].clone();
+                    let player_name = args.get(1).cloned().or_else(|| sender.clone());
+                    self.handle_record_start(&test_name, test_loader, player_name)?;
+                }
 
-                    "!use" => {
-                        self.handle_record_use(&args)?;
-                    }
+                "!assert_entity" => {
+                    let Some(alias) = args.first() else {
+                        self.reply("Usage: !assert_entity <alias>")?;
+                        continue;
+                    };
+                    self.handle_record_assert_entity(alias)?;
+                }
 
-                    "!tick" | "!next" => {
-                        self.handle_record_tick()?;
-                    }
+                "!assert_changes" => {
+                    self.handle_record_assert_changes()?;
+                }
 
-                    "!pos1" | "!pos" => {
-                        if (!args.is_empty() && args.len() < 3) || args.len() > 3 {
-                            self.bot.send_command("say Usage: !assert <x> <y> <z>")?;
-                            continue;
-                        }
-                        self.handle_pos1(&args);
+                "!use" => {
+                    self.handle_record_use(&args)?;
+                }
+
+                "!tick" | "!next" => {
+                    self.handle_record_tick()?;
+                }
+
+                "!pos1" | "!pos" => {
+                    if (!args.is_empty() && args.len() < 3) || args.len() > 3 {
+                        self.reply("Usage: !assert <x> <y> <z>")?;
+                        continue;
                     }
+                    self.handle_pos1(&args);
+                }
 
-                    "!assert" => {
-                        if args.len() < 3 {
-                            self.bot.send_command("say Usage: !assert <x> <y> <z>")?;
-                            continue;
-                        }
-                        self.last_assert_pos = args.clone();
-                        self.handle_record_assert(&args)?;
+                "!assert" => {
+                    if args.len() < 3 {
+                        self.reply("Usage: !assert <x> <y> <z>")?;
+                        continue;
                     }
-                    "!sprint" => {
-                        if args.len() != 1 {
-                            self.bot.send_command("say Usage: !sprint <ticks>")?;
-                            self.bot.send_command(
-                                "say: please be assert before a start state of a block/region",
-                            )?;
-                            continue;
-                        }
-                        let ticks = args[0].parse::<u32>().unwrap_or(1);
-                        if ticks == 0 {
-                            self.bot
-                                .send_command("say Sprint ticks must be greater than 0")?;
-                            continue;
-                        }
-                        if self.last_assert_pos.is_empty() {
-                            self.bot.send_command("say Please assert a position first, which should be used for each string (can be also a 3d area)")?;
-                            continue;
-                        }
-                        self.handle_record_sprint(ticks)?;
+                    self.last_assert_pos = args.clone();
+                    self.handle_record_assert(&args)?;
+                }
+                "!sprint" => {
+                    if args.len() != 1 {
+                        self.reply("Usage: !sprint <ticks>")?;
+                        self.bot.send_command(
+                            "say: please be assert before a start state of a block/region",
+                        )?;
+                        continue;
                     }
-
-                    "!save" => {
-                        if self.handle_record_save()? {
-                            // Reload tests after successful save
-                            test_loader.verify_and_rebuild_index()?;
-                            all_test_files = test_loader.collect_all_test_files()?;
-                        }
+                    let ticks = args[0].parse::<u32>().unwrap_or(1);
+                    if ticks == 0 {
+                        self.reply("Sprint ticks must be greater than 0")?;
+                        continue;
                     }
+                    if self.last_assert_pos.is_empty() {
+                        self.reply("Please assert a position first, which should be used for each string (can be also a 3d area)")?;
+                        continue;
+                    }
+                    self.handle_record_sprint(ticks)?;
+                }
+
+                "!observe" => {
+                    let Some(ticks) = args
+                        .first()
+                        .and_then(|ticks| ticks.parse::<u32>().ok())
+                        .filter(|ticks| *ticks > 0)
+                    else {
+                        self.reply("Usage: !observe <ticks>")?;
+                        continue;
+                    };
+                    self.handle_record_observe(ticks)?;
+                }
 
-                    "!cancel" => {
-                        self.handle_record_cancel()?;
+                "!save" => {
+                    let force = args.first().map(|s| s.as_str()) == Some("force");
+                    if self.handle_record_save(force)? {
+                        // Reload tests after successful save
+                        test_loader.verify_and_rebuild_index()?;
+                        all_test_files = test_loader.collect_all_test_files()?;
                     }
+                }
 
-                    _ => {
-                        if command.starts_with('!') {
-                            self.bot.send_command(&format!(
-                                "say Unknown command: {}. Type !help for commands.",
-                                command
-                            ))?;
-                        }
+                "!cancel" => {
+                    self.handle_record_cancel()?;
+                }
+
+                _ => {
+                    if command.starts_with('!') {
+                        self.reply(&format!(
+                            "Unknown command: {}. Type !help for commands.",
+                            command
+                        ))?;
                     }
                 }
             }
@@ -446,30 +679,344 @@ impl TestExecutor {
         Ok(())
     }
 
-    fn create_batch_worlds(
+    /// Clear a test's area: through the bot on a server, which also clears the entities
+    /// the test asserts on, otherwise by filling its world with air
+    fn clean_test_area(
         &self,
+        adapter: &dyn BatchAdapter,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+        test_idx: usize,
+        world: &mut dyn BatchWorld,
+    ) -> Result<()> {
+        let (test, offset) = &tests_with_offsets[test_idx];
+        if adapter.on_server() {
+            return self.cleanup_test_area(test, *offset);
+        }
+        world.fill(test.cleanup_region(), &block::make_block("minecraft:air"))
+    }
+
+    /// Whether the batch runs where the bot is in game, so it can be parked and read
+    /// blocks from its client
+    fn has_client(&self, adapter: &dyn BatchAdapter) -> bool {
+        adapter.on_server() && !self.bot.is_rcon_only()
+    }
+
+    /// Put the batch back to its state before tick 0 for a `goto` replay: clean every
+    /// test area and recreate the worlds and players, on the reference server too.
+    #[allow(clippy::too_many_arguments)]
+    fn restart_batch(
+        &mut self,
+        adapter: &dyn BatchAdapter,
         tests_with_offsets: &[(TestSpec, [i32; 3])],
-    ) -> Vec<MinecraftWorld> {
+        layout_center: [f64; 3],
+        worlds: &mut Vec<Box<dyn BatchWorld>>,
+        players: &mut Vec<Option<Box<dyn FlintPlayer>>>,
+        reference_worlds: &mut Vec<Box<dyn BatchWorld>>,
+        reference_players: &mut Vec<Option<Box<dyn FlintPlayer>>>,
+    ) -> Result<()> {
+        // Dropping the worlds kills the entities they summoned
+        players.clear();
+        worlds.clear();
+        reference_players.clear();
+        reference_worlds.clear();
+
+        *worlds = adapter.batch_worlds(tests_with_offsets)?;
+        for (test_idx, world) in worlds.iter_mut().enumerate() {
+            self.clean_test_area(adapter, tests_with_offsets, test_idx, world.as_mut())?;
+        }
+        if self.has_client(adapter) {
+            self.bot.park_at(layout_center)?;
+        }
+        *players = Self::create_batch_players(worlds, tests_with_offsets)?;
+
+        if let Some((worlds, players)) = self.on_reference(|this| {
+            for (test, offset) in tests_with_offsets {
+                this.cleanup_test_area(test, *offset)?;
+            }
+            this.bot.park_at(layout_center)?;
+            this.create_reference_batch(tests_with_offsets)
+        })? {
+            *reference_worlds = worlds;
+            *reference_players = players;
+        }
+        Ok(())
+    }
+
+    /// Whether a `goto` can replay the batch. Event traces are written as the run
+    /// goes and can't be rewound.
+    fn can_replay(&self) -> bool {
+        if self.events.is_some() {
+            println!(
+                "  {} goto is not available while emitting events; stepping instead",
+                "!".yellow().bold()
+            );
+        }
+        self.events.is_none()
+    }
+
+    /// Pause at a failed assertion with a glowing marker around the failing block.
+    fn break_at_failure(
+        &mut self,
+        reason: &str,
+        marker: Option<[i32; 3]>,
+        test_idx: usize,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+        worlds: &[Box<dyn BatchWorld>],
+        players: &mut [Option<Box<dyn FlintPlayer>>],
+    ) -> Result<tick::BreakpointAction> {
+        // The world may be changed by hand while paused
+        self.bot.clear_block_cache();
+        if let Some([x, y, z]) = marker {
+            self.bot.send_command_synced(&format!(
+                "summon minecraft:block_display {x} {y} {z} {{Tags:[\"{FAILURE_MARKER_TAG}\"],\
+                 Glowing:1b,glow_color_override:16711680,\
+                 block_state:{{Name:\"minecraft:red_stained_glass\"}},\
+                 transformation:{{left_rotation:[0f,0f,0f,1f],right_rotation:[0f,0f,0f,1f],\
+                 translation:[-0.01f,-0.01f,-0.01f],scale:[1.02f,1.02f,1.02f]}}}}"
+            ))?;
+        }
+
+        let mut inspector = inspect::Inspector::new(tests_with_offsets, worlds, players);
+        inspector.focus_on(test_idx);
+        let action = tick::wait_for_step(
+            &mut self.bot,
+            self.control.as_ref(),
+            &self.allowed_players,
+            Some(&mut inspector),
+            reason,
+        );
+
+        if marker.is_some() {
+            self.bot
+                .send_command_synced(&format!("kill @e[tag={FAILURE_MARKER_TAG}]"))?;
+        }
+        action
+    }
+
+    /// Pause at a failed check of `test_idx` under `--break-on-failure`, marking its
+    /// position in the world
+    fn pause_at_failure(
+        &mut self,
+        reason: &str,
+        detail: &AssertFailure,
+        test_idx: usize,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+        worlds: &[Box<dyn BatchWorld>],
+        players: &mut [Option<Box<dyn FlintPlayer>>],
+    ) -> Result<tick::BreakpointAction> {
+        let marker = match detail.position() {
+            AssertPosition::Coordinate { x, y, z } => {
+                Some(self.apply_offset([x, y, z], tests_with_offsets[test_idx].1))
+            }
+            _ => None,
+        };
+        self.break_at_failure(
+            reason,
+            marker,
+            test_idx,
+            tests_with_offsets,
+            worlds,
+            players,
+        )
+    }
+
+    /// Apply what was chosen at a failure breakpoint to the batch loop's state
+    fn resume_after_failure(
+        &self,
+        action: tick::BreakpointAction,
+        stepping_mode: &mut bool,
+        aborted: &mut bool,
+        restart_to: &mut Option<u32>,
+    ) {
+        match action {
+            tick::BreakpointAction::Step => *stepping_mode = true,
+            tick::BreakpointAction::Continue => *stepping_mode = false,
+            tick::BreakpointAction::Abort => *aborted = true,
+            tick::BreakpointAction::Goto(target) => {
+                if self.can_replay() {
+                    *restart_to = Some(target);
+                } else {
+                    *stepping_mode = true;
+                }
+            }
+        }
+    }
+
+    /// Pass/fail per test from its (passed, failed) assertion counts
+    fn collect_results(
+        &self,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+        test_results: &[(usize, usize)],
+    ) -> Vec<TestResult> {
         tests_with_offsets
             .iter()
-            .map(|(test, offset)| {
-                let region = test.cleanup_region();
-                MinecraftWorld {
-                    bot: self.bot.clone(),
-                    offset: *offset,
-                    current_tick: 0,
-                    entities: std::collections::HashMap::new(),
-                    entity_bounds: Some([
-                        self.apply_offset(region[0], *offset),
-                        self.apply_offset(region[1], *offset),
-                    ]),
+            .enumerate()
+            .map(|(idx, (test, _))| {
+                let (passed, failed) = test_results[idx];
+                let success = failed == 0;
+
+                if self.verbose {
+                    println!();
+                    if success {
+                        println!(
+                            "  {} [{}] Test passed: {} assertions",
+                            "✓".green().bold(),
+                            test.name,
+                            passed
+                        );
+                    } else {
+                        println!(
+                            "  {} [{}] Test failed: {} passed, {} failed",
+                            "✗".red().bold(),
+                            test.name,
+                            passed,
+                            failed
+                        );
+                    }
+                }
+
+                if success {
+                    TestResult::new(test.name.clone())
+                } else {
+                    TestResult::new(test.name.clone())
+                        .with_failure_reason(format!("{} assertions failed", failed))
                 }
             })
             .collect()
     }
 
+    /// Confirm the placements deferred on each world in bulk and record every position
+    /// that didn't take as a failed check of its test, like a failed assertion.
+    /// Returns the failures, test-local, for `--fail-fast` and `--break-on-failure`.
+    /// Reference placements are confirmed too, but only reported.
+    #[allow(clippy::too_many_arguments)]
+    fn confirm_placements(
+        &self,
+        tick: u32,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+        worlds: &mut [Box<dyn BatchWorld>],
+        reference_worlds: &mut [Box<dyn BatchWorld>],
+        test_results: &mut [(usize, usize)],
+        test_failures: &mut [Option<AssertFailure>],
+        mismatches: &mut Vec<AssertMismatch>,
+    ) -> Result<Vec<(usize, AssertFailure)>> {
+        let mut failures = Vec::new();
+        for (test_idx, mismatch) in Self::confirm_world_placements(worlds)? {
+            let (test, offset) = &tests_with_offsets[test_idx];
+            let [x, y, z] = [0, 1, 2].map(|axis| mismatch.pos[axis] - offset[axis]);
+            let message = format!("placement at [{x}, {y}, {z}]: {}", mismatch.message());
+            test_results[test_idx].1 += 1;
+            if self.verbose {
+                println!(
+                    "    {} [{}] Tick {}: {}",
+                    "✗".red().bold(),
+                    test.name,
+                    tick,
+                    message.red()
+                );
+            }
+            mismatches.push(AssertMismatch {
+                test_name: test.name.clone(),
+                tick,
+                message,
+            });
+            let failure = || {
+                AssertFailure::new(
+                    tick,
+                    mismatch.expected(),
+                    mismatch.actual(),
+                    AssertPosition::Coordinate { x, y, z },
+                )
+            };
+            if test_failures[test_idx].is_none() {
+                test_failures[test_idx] = Some(failure());
+            }
+            failures.push((test_idx, failure()));
+        }
+
+        for (test_idx, mismatch) in Self::confirm_world_placements(reference_worlds)? {
+            if self.verbose {
+                let [x, y, z] = mismatch.pos;
+                println!(
+                    "    {} [{}] Tick {}: reference server: placement at [{x}, {y}, {z}]: {}",
+                    "!".yellow().bold(),
+                    tests_with_offsets[test_idx].0.name,
+                    tick,
+                    mismatch.message()
+                );
+            }
+        }
+        Ok(failures)
+    }
+
+    /// Stop deferring on every world and confirm what was queued, behind one fence
+    fn confirm_world_placements(
+        worlds: &mut [Box<dyn BatchWorld>],
+    ) -> Result<Vec<(usize, adapter::PlacementMismatch)>> {
+        let mut placements = Vec::new();
+        let mut owners = Vec::new();
+        for (test_idx, world) in worlds.iter_mut().enumerate() {
+            for placement in world.take_placements() {
+                owners.push((placement.0, test_idx));
+                placements.push(placement);
+            }
+        }
+        // Only the bot's own worlds defer placements
+        let Some(world) = worlds.first().and_then(|world| world.as_minecraft()) else {
+            return Ok(Vec::new());
+        };
+        let mismatches = adapter::confirm_placements(&world.bot, &placements)?;
+        Ok(mismatches
+            .into_iter()
+            .map(|mismatch| {
+                // The last placement at a position is the one that was checked
+                let test_idx = owners
+                    .iter()
+                    .rev()
+                    .find(|(owner_pos, _)| *owner_pos == mismatch.pos)
+                    .map_or(0, |(_, test_idx)| *test_idx);
+                (test_idx, mismatch)
+            })
+            .collect())
+    }
+
+    /// Prefetch the blocks checked by the assertions at the start of `entries`, up to
+    /// the next action
+    fn prefetch_assertions(
+        &self,
+        entries: &[(usize, &TimelineEntry, usize)],
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+    ) -> Result<()> {
+        let positions: Vec<[i32; 3]> = entries
+            .iter()
+            .take_while(|(_, entry, _)| is_assertion(entry))
+            .flat_map(|(test_idx, entry, _)| {
+                let offset = tests_with_offsets[*test_idx].1;
+                asserted_positions(entry)
+                    .into_iter()
+                    .map(move |pos| self.apply_offset(pos, offset))
+            })
+            .collect();
+        if !positions.is_empty() {
+            self.bot.prefetch_blocks(&positions)?;
+        }
+        Ok(())
+    }
+
+    /// Worlds and players for the batch on the reference server, which the bot joins
+    #[allow(clippy::type_complexity)]
+    fn create_reference_batch(
+        &self,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+    ) -> Result<(Vec<Box<dyn BatchWorld>>, Vec<Option<Box<dyn FlintPlayer>>>)> {
+        let mut worlds = adapter::AzaleaAdapter::new(self.bot.clone(), self.verify_server_side)
+            .batch_worlds(tests_with_offsets)?;
+        let players = Self::create_batch_players(&mut worlds, tests_with_offsets)?;
+        Ok((worlds, players))
+    }
+
     fn create_batch_players(
-        worlds: &mut [MinecraftWorld],
+        worlds: &mut [Box<dyn BatchWorld>],
         tests_with_offsets: &[(TestSpec, [i32; 3])],
     ) -> Result<Vec<Option<Box<dyn FlintPlayer>>>> {
         tests_with_offsets
@@ -481,37 +1028,169 @@ impl TestExecutor {
                     return Ok(None);
                 };
                 let mut player = worlds[index].create_player();
-                let minecraft_player = player
+                // The bot's players confirm every change; others only have the trait
+                match player
                     .as_any_mut()
                     .downcast_mut::<adapter::MinecraftPlayer>()
-                    .ok_or_else(|| anyhow::anyhow!("unsupported FlintPlayer implementation"))?;
-                for (slot, item) in &config.inventory {
-                    minecraft_player.set_slot_checked(*slot, Some(item))?;
+                {
+                    Some(minecraft_player) => {
+                        for (slot, item) in &config.inventory {
+                            minecraft_player.set_slot_checked(*slot, Some(item))?;
+                        }
+                        minecraft_player.select_hotbar_checked(config.selected_hotbar)?;
+                        minecraft_player.set_game_mode_checked(config.game_mode)?;
+                    }
+                    None => {
+                        for (slot, item) in &config.inventory {
+                            player.set_slot(*slot, Some(item))?;
+                        }
+                        player.select_hotbar(config.selected_hotbar)?;
+                        player.set_game_mode(config.game_mode)?;
+                    }
                 }
-                minecraft_player.select_hotbar_checked(config.selected_hotbar)?;
-                minecraft_player.set_game_mode_checked(config.game_mode)?;
                 Ok(Some(player))
             })
             .collect()
     }
 
-    fn test_max_ticks(aggregate: &TimelineAggregate<'_>, test_count: usize) -> Vec<u32> {
-        let mut max_ticks = vec![0; test_count];
-        for (tick_num, entries) in &aggregate.timeline {
-            for (test_idx, _, _) in entries {
-                max_ticks[*test_idx] = max_ticks[*test_idx].max(*tick_num);
+    /// Run tests in parallel with merged timeline. If the server drops a bot, it is
+    /// reconnected and the batch re-run from cleanup.
+    pub fn run_tests_parallel(
+        &mut self,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+        break_after_setup: bool,
+    ) -> Result<TestRunOutput> {
+        // Only the bot's own worlds are scanned for snapshots; a sidecar that can't be
+        // checked would otherwise pass unnoticed
+        if self.backend != Backend::Azalea
+            && let Some(path) = self
+                .snapshot_paths
+                .iter()
+                .flatten()
+                .find(|path| path.exists())
+                .cloned()
+        {
+            self.snapshot_paths.clear();
+            anyhow::bail!(
+                "snapshot {} can only be checked with --backend azalea",
+                path.display()
+            );
+        }
+        let output = self.run_with_reconnects(tests_with_offsets, break_after_setup);
+        // Batch files only describe the batch they were set for
+        self.snapshot_paths.clear();
+        output
+    }
+
+    fn run_with_reconnects(
+        &mut self,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+        break_after_setup: bool,
+    ) -> Result<TestRunOutput> {
+        let mut reconnects = Vec::new();
+        loop {
+            if !self.is_connected() {
+                self.reconnect()?;
+            }
+            let result = match self.backend {
+                Backend::Azalea => {
+                    let adapter =
+                        adapter::AzaleaAdapter::new(self.bot.clone(), self.verify_server_side);
+                    self.run_batch(&adapter, tests_with_offsets, break_after_setup)
+                }
+                Backend::Adapter => {
+                    let adapter = adapter::MinecraftAdapter::new(self.bot.clone());
+                    self.run_batch(&adapter, tests_with_offsets, break_after_setup)
+                }
+                Backend::Rcon => self.run_rcon_batch(tests_with_offsets),
+            };
+            match result {
+                Ok(mut output) => {
+                    output.reconnects = reconnects;
+                    return Ok(output);
+                }
+                Err(error) if !self.is_connected() && reconnects.len() < MAX_BATCH_RECONNECTS => {
+                    eprintln!(
+                        "{} Lost connection to the server ({error:#}); reconnecting and \
+                         re-running the batch",
+                        "!".yellow().bold()
+                    );
+                    reconnects.push(format!("{error:#}"));
+                }
+                Err(error) => return Err(error),
             }
         }
-        max_ticks
     }
 
-    /// Run tests in parallel with merged timeline
-    pub fn run_tests_parallel(
+    /// Run a batch through `RconAdapter`. Tests that need the player can't run without a
+    /// joined client, so they fail as unsupported instead.
+    fn run_rcon_batch(
         &mut self,
         tests_with_offsets: &[(TestSpec, [i32; 3])],
+    ) -> Result<TestRunOutput> {
+        let (unsupported, runnable): (Vec<_>, Vec<_>) = tests_with_offsets
+            .iter()
+            .cloned()
+            .partition(|(test, _)| rcon_backend::needs_player(test));
+
+        let mut output = if runnable.is_empty() {
+            TestRunOutput {
+                results: Vec::new(),
+                failures: Vec::new(),
+                mismatches: Vec::new(),
+                divergences: 0,
+                reconnects: Vec::new(),
+                desyncs: Vec::new(),
+            }
+        } else {
+            let adapter =
+                rcon_backend::RconAdapter::new(self.bot.clone(), runnable.iter().map(|(t, _)| t));
+            self.run_batch(&adapter, &runnable, false)?
+        };
+        output.results.extend(unsupported.iter().map(|(test, _)| {
+            TestResult::new(test.name.clone()).with_failure_reason(
+                "unsupported on --backend rcon: the test uses a player".to_string(),
+            )
+        }));
+        Ok(output)
+    }
+
+    /// Whether the bots for both servers are still in game
+    fn is_connected(&self) -> bool {
+        self.bot.is_connected() && self.reference.as_ref().is_none_or(TestBot::is_connected)
+    }
+
+    /// Reconnect whichever bot the server dropped, along with its RCON session
+    fn reconnect(&mut self) -> Result<()> {
+        if !self.bot.is_connected() {
+            // An RCON-only bot never joined: only its RCON session can have dropped
+            if !self.bot.is_rcon_only() {
+                self.bot.reconnect()?;
+            }
+            if let Some((addr, password)) = &self.rcon {
+                self.bot.connect_rcon(addr, password)?;
+            }
+        }
+        if let Some(reference) = self.reference.as_mut()
+            && !reference.is_connected()
+        {
+            reference.reconnect()?;
+        }
+        Ok(())
+    }
+
+    /// Run a batch on `adapter`'s worlds. Whatever the backend, the merged timeline,
+    /// cleanup timing, fail-fast and snapshots work the same; the bot's own worlds add
+    /// bulk-confirmed placements, breakpoints and the reference server.
+    fn run_batch(
+        &mut self,
+        adapter: &dyn BatchAdapter,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
         break_after_setup: bool,
     ) -> Result<TestRunOutput> {
         let verbose = self.verbose;
+        let on_server = adapter.on_server();
+        let has_client = self.has_client(adapter);
 
         let first = Self::validate_test_batch(tests_with_offsets)?;
 
@@ -563,26 +1242,56 @@ impl TestExecutor {
             println!();
         }
 
-        self.configure_batch_world(first)?;
+        if on_server {
+            self.configure_batch_world(first)?;
+        }
+        let mut worlds = adapter.batch_worlds(tests_with_offsets)?;
 
         // Clean all test areas before starting
         if verbose {
             println!("{} Cleaning all test areas...", "→".blue());
         }
-        for (test, offset) in tests_with_offsets.iter() {
-            self.cleanup_test_area(test, *offset)?;
+        for (test_idx, world) in worlds.iter_mut().enumerate() {
+            self.clean_test_area(adapter, tests_with_offsets, test_idx, world.as_mut())?;
+        }
+
+        if on_server {
+            self.forceload_regions(tests_with_offsets, true)?;
         }
 
-        self.forceload_regions(tests_with_offsets, true)?;
+        // Set the reference server up identically
+        self.on_reference(|this| {
+            this.configure_batch_world(first)?;
+            for (test, offset) in tests_with_offsets.iter() {
+                this.cleanup_test_area(test, *offset)?;
+            }
+            this.forceload_regions(tests_with_offsets, true)?;
+            this.bot.park_at(layout_center)
+        })?;
 
         // Break after setup if requested
         let mut stepping_mode = false;
+        let mut aborted = false;
+        let mut restart_to: Option<u32> = None;
         if break_after_setup {
-            let should_continue = tick::wait_for_step(
+            match tick::wait_for_step(
                 &mut self.bot,
+                self.control.as_ref(),
+                &self.allowed_players,
+                None,
                 "After test setup (cleanup complete, time frozen)",
-            )?;
-            stepping_mode = !should_continue;
+            )? {
+                tick::BreakpointAction::Step => stepping_mode = true,
+                tick::BreakpointAction::Continue => {}
+                tick::BreakpointAction::Abort => aborted = true,
+                tick::BreakpointAction::Goto(target) => {
+                    if self.can_replay() {
+                        restart_to = Some(target);
+                    } else {
+                        stepping_mode = true;
+                    }
+                }
+            }
         }
 
         // Emit `run_started` and pre-compute the scan AABB in world coords.
@@ -606,28 +1315,180 @@ impl TestExecutor {
         let mut test_failures: Vec<Option<AssertFailure>> =
             (0..tests_with_offsets.len()).map(|_| None).collect();
 
+        // Track every failed assertion across all tests
+        let mut mismatches: Vec<AssertMismatch> = Vec::new();
+        let mut desyncs: Vec<AssertMismatch> = Vec::new();
+
         // Track which tests have been cleaned up
         let mut tests_cleaned: Vec<bool> = vec![false; tests_with_offsets.len()];
 
         // Calculate max tick for each test
-        let test_max_ticks = Self::test_max_ticks(&aggregate, tests_with_offsets.len());
+        let test_max_ticks = schedule::test_max_ticks(&aggregate, tests_with_offsets.len());
 
         let show_progress = !verbose && !self.quiet;
         let fail_fast = self.fail_fast;
 
-        // Initialize per-test worlds and players using the trait model
-        let mut worlds = self.create_batch_worlds(tests_with_offsets);
+        // Snapshot assertions from the sidecar files next to each test
+        let mut snapshot_plans = tests_with_offsets
+            .iter()
+            .enumerate()
+            .map(
+                |(test_idx, _)| match self.snapshot_paths.get(test_idx).cloned().flatten() {
+                    Some(path) => snapshot::SnapshotPlan::load(
+                        path,
+                        test_max_ticks[test_idx],
+                        self.update_snapshots,
+                    ),
+                    None => Ok(None),
+                },
+            )
+            .collect::<Result<Vec<_>>>()?;
+        let snapshot_ticks: std::collections::BTreeSet<u32> = snapshot_plans
+            .iter()
+            .flatten()
+            .flat_map(|plan| plan.ticks.iter().copied())
+            .collect();
+
+        // Initialize per-test players using the trait model
         let mut players = Self::create_batch_players(&mut worlds, tests_with_offsets)?;
+        let (mut reference_worlds, mut reference_players) = self
+            .on_reference(|this| this.create_reference_batch(tests_with_offsets))?
+            .unwrap_or_default();
+        let mut divergences = 0;
 
         // Execute merged timeline
         let mut current_tick = 0;
-        while current_tick <= aggregate.max_tick {
+        let mut goto_target: Option<u32> = None;
+        while !aborted && current_tick <= aggregate.max_tick {
+            // `goto`: start the batch over and sprint to the target tick. A target past
+            // the end pauses at the last tick.
+            if let Some(target) = restart_to
+                .take()
+                .map(|target| target.min(aggregate.max_tick))
+            {
+                println!(
+                    "  {} Replaying from tick 0 to tick {}...",
+                    "→".blue(),
+                    target
+                );
+                self.restart_batch(
+                    adapter,
+                    tests_with_offsets,
+                    layout_center,
+                    &mut worlds,
+                    &mut players,
+                    &mut reference_worlds,
+                    &mut reference_players,
+                )?;
+                test_results.fill((0, 0));
+                test_failures.iter_mut().for_each(|failure| *failure = None);
+                mismatches.clear();
+                desyncs.clear();
+                tests_cleaned.fill(false);
+                divergences = 0;
+                for plan in snapshot_plans.iter_mut().flatten() {
+                    plan.captured.clear();
+                }
+                goto_target = Some(target);
+                stepping_mode = false;
+                current_tick = 0;
+            }
+
+            let mut asserted_tests: Vec<usize> = Vec::new();
             if let Some(entries) = aggregate.timeline.get(&current_tick) {
-                for (test_idx, entry, value_idx) in entries {
+                let mut prefetched = false;
+                let mut deferring = false;
+                for (entry_idx, (test_idx, entry, value_idx)) in entries.iter().enumerate() {
+                    // Placements go out as one burst, confirmed together before the
+                    // next entry that could observe them
+                    if is_placement(entry) {
+                        worlds[*test_idx].defer_placements();
+                        if let Some(reference_world) = reference_worlds.get_mut(*test_idx) {
+                            reference_world.defer_placements();
+                        }
+                        deferring = true;
+                    } else if deferring {
+                        let failures = self.confirm_placements(
+                            current_tick,
+                            tests_with_offsets,
+                            &mut worlds,
+                            &mut reference_worlds,
+                            &mut test_results,
+                            &mut test_failures,
+                            &mut mismatches,
+                        )?;
+                        deferring = false;
+                        if let Some((failed_idx, detail)) = failures.first() {
+                            if self.break_on_failure && goto_target.is_none() {
+                                let reason = format!(
+                                    "Placement failed in [{}] at tick {}: expected {}, got {}",
+                                    tests_with_offsets[*failed_idx].0.name,
+                                    current_tick,
+                                    String::from(detail.expected()),
+                                    String::from(detail.actual())
+                                );
+                                let action = self.pause_at_failure(
+                                    &reason,
+                                    detail,
+                                    *failed_idx,
+                                    tests_with_offsets,
+                                    &worlds,
+                                    &mut players,
+                                )?;
+                                self.resume_after_failure(
+                                    action,
+                                    &mut stepping_mode,
+                                    &mut aborted,
+                                    &mut restart_to,
+                                );
+                            }
+                            if fail_fast || aborted || restart_to.is_some() {
+                                break;
+                            }
+                        }
+                    }
+
+                    // Resolve the block checks of consecutive assertions, across all
+                    // tests, in one pass; any other action may change the world
+                    if is_assertion(entry) {
+                        if has_client && !prefetched {
+                            self.prefetch_assertions(&entries[entry_idx..], tests_with_offsets)?;
+                            prefetched = true;
+                        }
+                    } else if prefetched {
+                        self.bot.clear_block_cache();
+                        prefetched = false;
+                    }
+
                     let (test, _) = &tests_with_offsets[*test_idx];
-                    let world = &mut worlds[*test_idx];
+                    let world = worlds[*test_idx].as_mut();
                     let player = &mut players[*test_idx];
 
+                    // Mirror every action on the reference server; its assertion
+                    // outcomes are ignored in favour of the region comparison below.
+                    if let Some(reference_world) = reference_worlds.get_mut(*test_idx) {
+                        if let Err(e) = actions::execute_action(
+                            reference_world.as_mut(),
+                            &mut reference_players[*test_idx],
+                            current_tick,
+                            entry,
+                            *value_idx,
+                            false,
+                        ) && verbose
+                        {
+                            println!(
+                                "    {} [{}] Tick {}: reference server: {}",
+                                "!".yellow().bold(),
+                                test.name,
+                                current_tick,
+                                e
+                            );
+                        }
+                        if is_assertion(entry) && !asserted_tests.contains(test_idx) {
+                            asserted_tests.push(*test_idx);
+                        }
+                    }
+
                     match self.execute_action(world, player, current_tick, entry, *value_idx) {
                         Ok(ActionOutcome::AssertPassed) => {
                             test_results[*test_idx].0 += 1;
@@ -680,14 +1541,59 @@ impl TestExecutor {
                                     Some(&actual),
                                 )?;
                             }
+                            mismatches.push(AssertMismatch::from_failure(
+                                &test.name,
+                                current_tick,
+                                &detail,
+                            ));
+                            if self.break_on_failure && goto_target.is_none() {
+                                let reason = format!(
+                                    "Assertion failed in [{}] at tick {}: {}",
+                                    test.name,
+                                    current_tick,
+                                    mismatches.last().map_or("", |m| m.message.as_str())
+                                );
+                                let action = self.pause_at_failure(
+                                    &reason,
+                                    &detail,
+                                    *test_idx,
+                                    tests_with_offsets,
+                                    &worlds,
+                                    &mut players,
+                                )?;
+                                self.resume_after_failure(
+                                    action,
+                                    &mut stepping_mode,
+                                    &mut aborted,
+                                    &mut restart_to,
+                                );
+                            }
                             // Store first failure per test
                             if test_failures[*test_idx].is_none() {
                                 test_failures[*test_idx] = Some(detail);
                             }
-                            if fail_fast {
+                            if fail_fast || aborted || restart_to.is_some() {
                                 break;
                             }
                         }
+                        Err(e) if e.downcast_ref::<adapter::Desync>().is_some() => {
+                            // Not the test's fault: report it apart from the failures,
+                            // which main lists after the summary
+                            if verbose {
+                                println!(
+                                    "    {} [{}] Tick {}: {}",
+                                    "!".yellow().bold(),
+                                    test.name,
+                                    current_tick,
+                                    e
+                                );
+                            }
+                            desyncs.push(AssertMismatch {
+                                test_name: test.name.clone(),
+                                tick: current_tick,
+                                message: e.to_string(),
+                            });
+                        }
                         Err(e) => {
                             test_results[*test_idx].1 += 1;
                             if verbose {
@@ -699,6 +1605,11 @@ impl TestExecutor {
                                     e.to_string().red()
                                 );
                             }
+                            mismatches.push(AssertMismatch {
+                                test_name: test.name.clone(),
+                                tick: current_tick,
+                                message: e.to_string(),
+                            });
                             if fail_fast {
                                 break;
                             }
@@ -707,44 +1618,150 @@ impl TestExecutor {
                 }
             }
 
+            let failures = self.confirm_placements(
+                current_tick,
+                tests_with_offsets,
+                &mut worlds,
+                &mut reference_worlds,
+                &mut test_results,
+                &mut test_failures,
+                &mut mismatches,
+            )?;
+            self.bot.clear_block_cache();
+            if let Some((failed_idx, detail)) = failures.first()
+                && self.break_on_failure
+                && goto_target.is_none()
+                && !aborted
+                && restart_to.is_none()
+            {
+                let reason = format!(
+                    "Placement failed in [{}] at tick {}: expected {}, got {}",
+                    tests_with_offsets[*failed_idx].0.name,
+                    current_tick,
+                    String::from(detail.expected()),
+                    String::from(detail.actual())
+                );
+                let action = self.pause_at_failure(
+                    &reason,
+                    detail,
+                    *failed_idx,
+                    tests_with_offsets,
+                    &worlds,
+                    &mut players,
+                )?;
+                self.resume_after_failure(
+                    action,
+                    &mut stepping_mode,
+                    &mut aborted,
+                    &mut restart_to,
+                );
+            }
+
+            if aborted {
+                break;
+            }
+            if restart_to.is_some() {
+                continue;
+            }
+
+            if snapshot_ticks.contains(&current_tick) {
+                self.check_snapshots(
+                    current_tick,
+                    tests_with_offsets,
+                    &mut snapshot_plans,
+                    &mut test_results,
+                    &mut test_failures,
+                    &mut mismatches,
+                )?;
+            }
+
+            if !asserted_tests.is_empty() {
+                divergences += self.compare_with_reference(
+                    current_tick,
+                    tests_with_offsets,
+                    &asserted_tests,
+                    &mut test_results,
+                    &mut test_failures,
+                    &mut mismatches,
+                )?;
+            }
+
             // Break out of the timeline loop on first failure
             if fail_fast && test_results.iter().any(|(_, failed)| *failed > 0) {
                 break;
             }
 
             // Clean up tests that have completed
-            for test_idx in 0..tests_with_offsets.len() {
-                if !tests_cleaned[test_idx] && current_tick > test_max_ticks[test_idx] {
-                    let (test, offset) = &tests_with_offsets[test_idx];
-                    if verbose {
-                        println!(
-                            "\n{} Cleaning up test [{}] (completed at tick {})...",
-                            "→".blue(),
-                            test.name,
-                            test_max_ticks[test_idx]
-                        );
-                    }
-                    self.cleanup_test_area(test, *offset)?;
-                    tests_cleaned[test_idx] = true;
-                    players[test_idx] = None;
+            for test_idx in schedule::finished_tests(current_tick, &test_max_ticks, &tests_cleaned)
+            {
+                let (test, offset) = &tests_with_offsets[test_idx];
+                if verbose {
+                    println!(
+                        "\n{} Cleaning up test [{}] (completed at tick {})...",
+                        "→".blue(),
+                        test.name,
+                        test_max_ticks[test_idx]
+                    );
+                }
+                self.clean_test_area(
+                    adapter,
+                    tests_with_offsets,
+                    test_idx,
+                    worlds[test_idx].as_mut(),
+                )?;
+                tests_cleaned[test_idx] = true;
+                players[test_idx] = None;
+                if has_client {
                     self.bot.park_at(layout_center)?;
                 }
+                self.on_reference(|this| {
+                    this.cleanup_test_area(test, *offset)?;
+                    this.bot.park_at(layout_center)
+                })?;
+                if let Some(player) = reference_players.get_mut(test_idx) {
+                    *player = None;
+                }
             }
 
-            // Check for breakpoint
-            if (self.enable_breakpoints && aggregate.breakpoints.contains(&current_tick))
-                || stepping_mode
-            {
-                let should_continue = tick::wait_for_step(
+            // Check for breakpoint. While replaying to a `goto` target, only the
+            // target itself pauses.
+            let at_breakpoint = match goto_target {
+                Some(target) => target == current_tick,
+                None => {
+                    (self.enable_breakpoints && aggregate.breakpoints.contains(&current_tick))
+                        || stepping_mode
+                }
+            };
+            if at_breakpoint {
+                goto_target = None;
+                let mut inspector =
+                    inspect::Inspector::new(tests_with_offsets, &worlds, &mut players);
+                match tick::wait_for_step(
                     &mut self.bot,
+                    self.control.as_ref(),
+                    &self.allowed_players,
+                    Some(&mut inspector),
                     &format!("End of tick {} (before step to next tick)", current_tick),
-                )?;
-                stepping_mode = !should_continue;
+                )? {
+                    tick::BreakpointAction::Step => stepping_mode = true,
+                    tick::BreakpointAction::Continue => stepping_mode = false,
+                    tick::BreakpointAction::Abort => {
+                        aborted = true;
+                        break;
+                    }
+                    tick::BreakpointAction::Goto(target) => {
+                        if self.can_replay() {
+                            restart_to = Some(target);
+                            continue;
+                        }
+                        stepping_mode = true;
+                    }
+                }
             }
 
             // Advance to next tick.
             if let Some((scan_min, scan_max)) = scan_bounds {
-                tick::step_tick(&mut self.bot, verbose)?;
+                self.advance(adapter, &mut worlds, 1)?;
                 let world_blocks = self.scan_region(scan_min, scan_max)?;
                 if let Some(events) = self.events.as_mut() {
                     events.emit_tick(current_tick, world_blocks)?;
@@ -752,27 +1769,15 @@ impl TestExecutor {
                 current_tick += 1;
             } else if current_tick < aggregate.max_tick {
                 if stepping_mode {
-                    tick::step_tick(&mut self.bot, verbose)?;
+                    self.advance(adapter, &mut worlds, 1)?;
                     current_tick += 1;
                 } else {
-                    let next_event_tick = aggregate
-                        .next_event_tick(current_tick)
-                        .unwrap_or(aggregate.max_tick + 1);
-
-                    let ticks_to_sprint = if next_event_tick <= aggregate.max_tick {
-                        next_event_tick - current_tick
-                    } else {
-                        aggregate.max_tick - current_tick
-                    };
-
-                    if ticks_to_sprint == 1 {
-                        tick::step_tick(&mut self.bot, verbose)?
-                    } else if ticks_to_sprint > 1 {
-                        tick::sprint_ticks(&mut self.bot, ticks_to_sprint, verbose)?
-                    } else {
-                        0
-                    };
-
+                    let ticks_to_sprint = schedule::ticks_until_next_stop(
+                        &aggregate,
+                        current_tick,
+                        snapshot_ticks.iter().copied().chain(goto_target),
+                    );
+                    self.advance(adapter, &mut worlds, ticks_to_sprint)?;
                     current_tick += ticks_to_sprint;
                 }
             } else {
@@ -780,8 +1785,8 @@ impl TestExecutor {
             }
 
             // Update tick counts in the FlintWorld adapter instances
-            for world in &mut worlds {
-                world.current_tick = current_tick as u64;
+            for world in worlds.iter_mut().chain(&mut reference_worlds) {
+                world.set_current_tick(current_tick as u64);
             }
 
             // Update progress bar in non-verbose mode
@@ -795,6 +1800,26 @@ impl TestExecutor {
             println!();
         }
 
+        // Tests with ticks left when the batch was aborted did not pass
+        if aborted {
+            for (test_idx, (test, _)) in tests_with_offsets.iter().enumerate() {
+                if current_tick < test_max_ticks[test_idx] {
+                    test_results[test_idx].1 += 1;
+                    mismatches.push(AssertMismatch {
+                        test_name: test.name.clone(),
+                        tick: current_tick,
+                        message: "aborted at a breakpoint".to_string(),
+                    });
+                }
+            }
+        }
+
+        for plan in snapshot_plans.iter().flatten() {
+            if plan.save_captured()? && !self.quiet {
+                println!("{} Updated snapshot {}", "✓".green(), plan.path.display());
+            }
+        }
+
         // Emit run_completed
         if let Some(events) = self.events.as_mut() {
             let asserts_passed: u32 = test_results.iter().map(|(p, _)| *p as u32).sum();
@@ -813,70 +1838,57 @@ impl TestExecutor {
                         test.name
                     );
                 }
-                self.cleanup_test_area(test, *offset)?;
+                self.clean_test_area(
+                    adapter,
+                    tests_with_offsets,
+                    test_idx,
+                    worlds[test_idx].as_mut(),
+                )?;
                 tests_cleaned[test_idx] = true;
                 players[test_idx] = None;
-                self.bot.park_at(layout_center)?;
+                if has_client {
+                    self.bot.park_at(layout_center)?;
+                }
+                self.on_reference(|this| {
+                    this.cleanup_test_area(test, *offset)?;
+                    this.bot.park_at(layout_center)
+                })?;
+                if let Some(player) = reference_players.get_mut(test_idx) {
+                    *player = None;
+                }
             }
         }
 
         // Release the chunks only after cleanup has completed, then resume time.
-        self.forceload_regions(tests_with_offsets, false)?;
-        self.bot.send_command("tick unfreeze")?;
-
-        // Build results
-        let results: Vec<TestResult> = tests_with_offsets
-            .iter()
-            .enumerate()
-            .map(|(idx, (test, _))| {
-                let (passed, failed) = test_results[idx];
-                let success = failed == 0;
-
-                if verbose {
-                    println!();
-                    if success {
-                        println!(
-                            "  {} [{}] Test passed: {} assertions",
-                            "✓".green().bold(),
-                            test.name,
-                            passed
-                        );
-                    } else {
-                        println!(
-                            "  {} [{}] Test failed: {} passed, {} failed",
-                            "✗".red().bold(),
-                            test.name,
-                            passed,
-                            failed
-                        );
-                    }
-                }
-
-                if success {
-                    TestResult::new(test.name.clone())
-                } else {
-                    TestResult::new(test.name.clone())
-                        .with_failure_reason(format!("{} assertions failed", failed))
-                }
-            })
-            .collect();
-
-        // Send test results summary to chat
-        let total_passed = results.iter().filter(|r| r.success).count();
-        let total_failed = results.len() - total_passed;
-        let summary = format!(
-            "Tests complete: {}/{} passed, {} failed",
-            total_passed,
-            results.len(),
-            total_failed
-        );
-        self.bot.send_command_synced(&format!("say {}", summary))?;
+        if on_server {
+            self.forceload_regions(tests_with_offsets, false)?;
+            self.bot.send_command("tick unfreeze")?;
+        }
+        self.on_reference(|this| {
+            this.forceload_regions(tests_with_offsets, false)?;
+            this.bot.send_command("tick unfreeze")
+        })?;
+
+        let results = self.collect_results(tests_with_offsets, &test_results);
+
+        if on_server {
+            // Send test results summary to chat
+            let total_passed = results.iter().filter(|r| r.success).count();
+            let total_failed = results.len() - total_passed;
+            let summary = format!(
+                "Tests complete: {}/{} passed, {} failed",
+                total_passed,
+                results.len(),
+                total_failed
+            );
+            self.bot.send_command_synced(&format!("say {}", summary))?;
 
-        // Send individual test results to chat
-        for result in &results {
-            let status = if result.success { "PASS" } else { "FAIL" };
-            let msg = format!("say [{}] {}", status, result.test_name);
-            self.bot.send_command_synced(&msg)?;
+            // Send individual test results to chat
+            for result in &results {
+                let status = if result.success { "PASS" } else { "FAIL" };
+                let msg = format!("say [{}] {}", status, result.test_name);
+                self.bot.send_command_synced(&msg)?;
+            }
         }
 
         // Collect failure details
@@ -892,14 +1904,138 @@ impl TestExecutor {
 
         // A virtual player may have left the shared bot inside a test region. Park the
         // physical bot at the layout center after every run, including playerless runs.
-        self.bot.park_at(layout_center)?;
+        if has_client {
+            self.bot.park_at(layout_center)?;
+        }
+
+        Ok(TestRunOutput {
+            results,
+            failures,
+            mismatches,
+            divergences,
+            reconnects: Vec::new(),
+            desyncs,
+        })
+    }
+
+    /// Advance the batch `ticks` ticks: on a server through the bot, in lockstep with
+    /// the reference server, otherwise through the backend's sprint or one `do_tick` at
+    /// a time
+    fn advance(
+        &mut self,
+        adapter: &dyn BatchAdapter,
+        worlds: &mut [Box<dyn BatchWorld>],
+        ticks: u32,
+    ) -> Result<()> {
+        if adapter.on_server() {
+            if ticks == 1 {
+                self.step_tick(self.verbose)?;
+            } else if ticks > 1 {
+                self.sprint_ticks(ticks, self.verbose)?;
+            }
+            return Ok(());
+        }
+        match adapter.as_sprint() {
+            Some(sprint) if ticks > 1 => sprint.sprint(ticks),
+            _ => {
+                // The batch's worlds share their time, so ticking one ticks them all
+                if let Some(world) = worlds.first_mut() {
+                    for _ in 0..ticks {
+                        world.do_tick()?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Compare (or capture, when updating) the snapshots due at `tick`. A snapshot
+    /// counts as one assertion; every mismatched position is reported.
+    fn check_snapshots(
+        &mut self,
+        tick: u32,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+        plans: &mut [Option<snapshot::SnapshotPlan>],
+        test_results: &mut [(usize, usize)],
+        test_failures: &mut [Option<AssertFailure>],
+        mismatches: &mut Vec<AssertMismatch>,
+    ) -> Result<()> {
+        self.bot.sync_client_world()?;
+        for (test_idx, plan) in plans.iter_mut().enumerate() {
+            let Some(plan) = plan else {
+                continue;
+            };
+            if !plan.ticks.contains(&tick) {
+                continue;
+            }
 
-        Ok(TestRunOutput { results, failures })
+            let (test, offset) = &tests_with_offsets[test_idx];
+            let region = test.cleanup_region();
+            let world_blocks = self.scan_region(
+                self.apply_offset(region[0], *offset),
+                self.apply_offset(region[1], *offset),
+            )?;
+            let actual = snapshot::RegionSnapshot::from_world(tick, &world_blocks, *offset);
+
+            if plan.updating {
+                plan.captured.push(actual);
+                continue;
+            }
+            let Some(expected) = plan.expected_at(tick) else {
+                continue;
+            };
+
+            let diff = expected.diff(&actual);
+            if diff.is_empty() {
+                test_results[test_idx].0 += 1;
+                continue;
+            }
+
+            test_results[test_idx].1 += 1;
+            if test_failures[test_idx].is_none() {
+                // Snapshot positions are already test-local
+                let first = &diff[0];
+                let [x, y, z] = first.pos;
+                test_failures[test_idx] = Some(AssertFailure::new(
+                    tick,
+                    first.expected.clone(),
+                    first.actual.clone(),
+                    AssertPosition::Coordinate { x, y, z },
+                ));
+            }
+            eprintln!(
+                "    {} [{}] Tick {}: snapshot differs at {} position(s)",
+                "✗".red().bold(),
+                test.name,
+                tick,
+                diff.len()
+            );
+            for mismatch in diff {
+                let [x, y, z] = mismatch.pos;
+                eprintln!(
+                    "      [{}, {}, {}] expected {}, got {}",
+                    x,
+                    y,
+                    z,
+                    mismatch.expected.green(),
+                    mismatch.actual.red()
+                );
+                mismatches.push(AssertMismatch {
+                    test_name: test.name.clone(),
+                    tick,
+                    message: format!(
+                        "snapshot: expected {}, got {} at [{x}, {y}, {z}]",
+                        mismatch.expected, mismatch.actual
+                    ),
+                });
+            }
+        }
+        Ok(())
     }
 
     fn execute_action(
         &mut self,
-        world: &mut MinecraftWorld,
+        world: &mut dyn BatchWorld,
         player: &mut Option<Box<dyn FlintPlayer>>,
         tick: u32,
         entry: &TimelineEntry,
@@ -941,3 +2077,176 @@ pub fn format_number(n: u32) -> String {
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::fake::{assert_block, place, spec};
+    use super::*;
+    use crate::bot::mock_server::MockServer;
+
+    /// Join `server` and run `tests` through the bot's batch runner, ten blocks apart
+    /// on top of the origin platform
+    fn run(server: &MockServer, tests: &[TestSpec], fail_fast: bool) -> TestRunOutput {
+        let tests_with_offsets: Vec<_> = tests
+            .iter()
+            .cloned()
+            .zip((0..).map(|idx| [idx * 10, 64, 0]))
+            .collect();
+        let mut executor = TestExecutor::new();
+        executor.set_quiet(true);
+        executor.set_fail_fast(fail_fast);
+        executor.connect(server.game_addr()).unwrap();
+        executor
+            .run_tests_parallel(&tests_with_offsets, false)
+            .unwrap()
+    }
+
+    fn count_commands(server: &MockServer, command: &str) -> usize {
+        server
+            .commands()
+            .iter()
+            .filter(|sent| sent.as_str() == command)
+            .count()
+    }
+
+    #[test]
+    fn test_batch_places_and_asserts_through_the_bot() {
+        let server = MockServer::start().unwrap();
+        let tests = [
+            spec(
+                "passes",
+                vec![
+                    place(0, [1, 1, 1], "minecraft:stone"),
+                    assert_block(1, [1, 1, 1], "minecraft:stone"),
+                ],
+            ),
+            spec(
+                "fails",
+                vec![
+                    place(0, [1, 1, 1], "minecraft:stone"),
+                    assert_block(1, [1, 1, 1], "minecraft:dirt"),
+                ],
+            ),
+        ];
+        let output = run(&server, &tests, false);
+        assert!(output.results[0].success);
+        assert!(!output.results[1].success);
+        assert_eq!(output.failures.len(), 1);
+        assert_eq!(output.failures[0].0, "fails");
+        assert_eq!(output.mismatches.len(), 1);
+        assert_eq!(output.mismatches[0].tick, 1);
+        // Each area is cleared before the batch and once its test is done
+        assert_eq!(count_commands(&server, "fill 0 64 0 3 67 3 air"), 2);
+        assert_eq!(count_commands(&server, "fill 10 64 0 13 67 3 air"), 2);
+    }
+
+    #[test]
+    fn test_batch_sprints_through_gaps_and_steps_single_ticks() {
+        let server = MockServer::start().unwrap();
+        let tests = [spec(
+            "gaps",
+            vec![
+                assert_block(1, [0, 0, 0], "minecraft:air"),
+                assert_block(10, [0, 0, 0], "minecraft:air"),
+            ],
+        )];
+        let output = run(&server, &tests, false);
+        assert!(output.results[0].success);
+        assert_eq!(count_commands(&server, "tick step"), 1);
+        // `tick sprint N` runs N + 1 ticks
+        assert_eq!(count_commands(&server, "tick sprint 8"), 1);
+        assert_eq!(server.gametime(), 10);
+    }
+
+    #[test]
+    fn test_snapshots_follow_the_test_file_not_the_name() {
+        let server = MockServer::start().unwrap();
+        let dir = std::env::temp_dir().join(format!("flintmc-batch-files-{}", std::process::id()));
+        let files = ["stone", "air"].map(|sub| dir.join(sub).join("same.json"));
+        for (file, blocks) in files.iter().zip([
+            vec![snapshot::SnapshotBlock {
+                pos: [1, 1, 1],
+                id: "minecraft:stone".to_string(),
+            }],
+            vec![],
+        ]) {
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            snapshot::SnapshotFile {
+                snapshots: vec![snapshot::RegionSnapshot { tick: 1, blocks }],
+            }
+            .save(&snapshot::sidecar_path(file))
+            .unwrap();
+        }
+        let test = spec(
+            "same",
+            vec![
+                place(0, [1, 1, 1], "minecraft:stone"),
+                assert_block(1, [1, 1, 1], "minecraft:stone"),
+            ],
+        );
+
+        let mut executor = TestExecutor::new();
+        executor.set_quiet(true);
+        executor.connect(server.game_addr()).unwrap();
+        executor.set_batch_files(files.iter().cloned().map(Some).collect());
+        let output = executor
+            .run_tests_parallel(&[(test.clone(), [0, 64, 0]), (test, [10, 64, 0])], false)
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(output.results[0].success);
+        assert!(!output.results[1].success);
+        // The snapshot mismatch is the failure detail, like a failed assertion
+        assert_eq!(output.failures.len(), 1);
+        assert_eq!(output.failures[0].1.tick(), 1);
+        assert_eq!(output.mismatches.len(), 1);
+    }
+
+    #[test]
+    fn test_snapshot_sidecar_rejected_off_azalea() {
+        let dir = std::env::temp_dir().join(format!("flintmc-sidecar-{}", std::process::id()));
+        let file = dir.join("test.json");
+        std::fs::create_dir_all(&dir).unwrap();
+        snapshot::SnapshotFile { snapshots: vec![] }
+            .save(&snapshot::sidecar_path(&file))
+            .unwrap();
+
+        let mut executor = TestExecutor::new();
+        executor.set_quiet(true);
+        executor.set_backend(Backend::Adapter);
+        executor.set_batch_files(vec![Some(file)]);
+        let result = executor.run_tests_parallel(&[(spec("test", vec![]), [0, 64, 0])], false);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let err = result
+            .err()
+            .expect("a sidecar off azalea must not pass unchecked");
+        assert!(err.to_string().contains("--backend azalea"));
+    }
+
+    #[test]
+    fn test_fail_fast_stops_before_the_rest_of_the_batch() {
+        let server = MockServer::start().unwrap();
+        let tests = [
+            spec("fails", vec![assert_block(1, [1, 1, 1], "minecraft:stone")]),
+            spec("later", vec![assert_block(8, [1, 1, 1], "minecraft:air")]),
+        ];
+        let output = run(&server, &tests, true);
+        assert!(!output.results[0].success);
+        // The later test never reached its assertion, so nothing failed for it
+        assert!(output.results[1].success);
+        assert!(
+            output
+                .mismatches
+                .iter()
+                .all(|mismatch| mismatch.test_name == "fails")
+        );
+        assert_eq!(server.gametime(), 1);
+        assert!(
+            !server
+                .commands()
+                .iter()
+                .any(|command| command.starts_with("tick sprint"))
+        );
+    }
+}
@@ -0,0 +1,435 @@
+//! A backend for servers that can't accept the bot but expose RCON: every world
+//! operation is a command, and nothing is read from a client world.
+//!
+//! There is no command that prints a block state, so blocks are identified by probing
+//! `execute if block` with the states the batch's tests mention: the ids they place,
+//! fill and assert, then, value by value, the properties the tests give that id.
+
+use super::adapter::{self, MinecraftAdapter, MinecraftWorld};
+use super::backend::BatchAdapter;
+use super::block;
+use crate::bot::{NoScore, TestBot};
+use anyhow::Result;
+use flint_core::BlockPos;
+use flint_core::test_spec::{
+    ActionType, AssertType, Block, BlockSpec, EntityNbt, GameMode, Item, PlayerSlot, TestSpec,
+};
+use flint_core::traits::{EntityState, FlintAdapter, FlintPlayer, FlintWorld, ServerInfo};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Block ids, and per id the property values, a batch can be expected to contain
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Probes {
+    ids: BTreeSet<String>,
+    properties: BTreeMap<String, BTreeMap<String, BTreeSet<String>>>,
+}
+
+impl Probes {
+    fn from_tests<'a>(tests: impl IntoIterator<Item = &'a TestSpec>) -> Self {
+        let mut probes = Self::default();
+        probes.ids.insert("minecraft:air".to_string());
+        // `assert_state` doesn't name the block, so its values are added afterwards
+        let mut states: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for entry in tests.into_iter().flat_map(|test| &test.timeline) {
+            match &entry.action_type {
+                ActionType::PlaceEach { blocks } => {
+                    for placement in blocks {
+                        probes.add_block(&placement.block);
+                    }
+                }
+                ActionType::Fill { with, .. } => probes.add_block(with),
+                ActionType::Assert { checks } => {
+                    for check in checks {
+                        if let AssertType::Block(check) = check
+                            && let BlockSpec::Single(block) = &check.is
+                        {
+                            probes.add_block(block);
+                        }
+                    }
+                }
+                ActionType::AssertState { state, values, .. } => {
+                    states
+                        .entry(state.clone())
+                        .or_default()
+                        .extend(values.iter().cloned());
+                }
+                _ => {}
+            }
+        }
+        for (name, values) in states {
+            probes.add_state(&name, values);
+        }
+        probes
+    }
+
+    fn add_block(&mut self, block: &Block) {
+        self.ids.insert(block.id.clone());
+        let properties = self.properties.entry(block.id.clone()).or_default();
+        for (name, value) in &block.properties {
+            properties
+                .entry(name.clone())
+                .or_default()
+                .insert(value.clone());
+        }
+    }
+
+    /// Values of a property asserted on some block: they belong to the ids the tests
+    /// already give that property, or to every id when none does
+    fn add_state(&mut self, name: &str, values: BTreeSet<String>) {
+        let known = self
+            .properties
+            .values()
+            .any(|properties| properties.contains_key(name));
+        for id in &self.ids {
+            let properties = self.properties.entry(id.clone()).or_default();
+            if !known || properties.contains_key(name) {
+                properties
+                    .entry(name.to_string())
+                    .or_default()
+                    .extend(values.iter().cloned());
+            }
+        }
+    }
+}
+
+/// Whether a test acts as or asserts on the player, which needs a joined client
+pub(crate) fn needs_player(test: &TestSpec) -> bool {
+    test.setup
+        .as_ref()
+        .is_some_and(|setup| setup.player.is_some())
+        || test.timeline.iter().any(|entry| match &entry.action_type {
+            ActionType::Interact { .. } => true,
+            ActionType::Tp { entity_alias, .. } => entity_alias == "player",
+            ActionType::Assert { checks } => checks
+                .iter()
+                .any(|check| matches!(check, AssertType::Inventory(..))),
+            _ => false,
+        })
+}
+
+pub struct RconAdapter {
+    bot: TestBot,
+    /// Creates the worlds commands run in
+    adapter: MinecraftAdapter,
+    probes: Probes,
+}
+
+impl RconAdapter {
+    /// An adapter for worlds running `tests`, whose blocks are what it can recognise
+    pub fn new<'a>(bot: TestBot, tests: impl IntoIterator<Item = &'a TestSpec>) -> Self {
+        Self {
+            adapter: MinecraftAdapter::new(bot.clone()),
+            bot,
+            probes: Probes::from_tests(tests),
+        }
+    }
+}
+
+impl FlintAdapter for RconAdapter {
+    fn create_test_world(&self) -> Result<Box<dyn FlintWorld>> {
+        self.bot.send_command_synced("tick freeze")?;
+        Ok(Box::new(RconWorld {
+            world: self.adapter.world_at([0, 0, 0], None),
+            probes: self.probes.clone(),
+            placed: HashMap::new(),
+        }))
+    }
+
+    fn server_info(&self) -> ServerInfo {
+        self.adapter.server_info()
+    }
+}
+
+impl BatchAdapter for RconAdapter {
+    fn on_server(&self) -> bool {
+        true
+    }
+}
+
+/// Commands for everything; entities, time and ticks work as on `MinecraftWorld`,
+/// whose queries already run over RCON when the bot has no client
+pub struct RconWorld {
+    world: MinecraftWorld,
+    probes: Probes,
+    /// Last id placed per position, probed first
+    placed: HashMap<BlockPos, String>,
+}
+
+impl RconWorld {
+    fn holds(&self, pos: BlockPos, state: &str) -> Result<bool> {
+        self.world
+            .bot
+            .query_condition(&format!("block {} {} {} {state}", pos[0], pos[1], pos[2]))
+    }
+
+    fn probe_id(&self, pos: BlockPos) -> Result<String> {
+        let placed = self.placed.get(&pos);
+        for id in placed
+            .into_iter()
+            .chain(self.probes.ids.iter().filter(|id| Some(*id) != placed))
+        {
+            if self.holds(pos, id)? {
+                return Ok(id.clone());
+            }
+        }
+        anyhow::bail!(
+            "block at [{}, {}, {}] is none of the {} blocks the tests mention",
+            pos[0],
+            pos[1],
+            pos[2],
+            self.probes.ids.len()
+        )
+    }
+}
+
+impl FlintWorld for RconWorld {
+    fn do_tick(&mut self) -> Result<()> {
+        self.world.do_tick()
+    }
+
+    fn current_tick(&self) -> u64 {
+        self.world.current_tick()
+    }
+
+    fn get_time(&self) -> Result<u64> {
+        self.world.get_time()
+    }
+
+    fn get_block(&self, pos: BlockPos, requested_nbt: &[String]) -> Result<Block> {
+        let id = self.probe_id(pos)?;
+        let mut block = block::make_block(&id);
+        for (name, values) in self.probes.properties.get(&id).into_iter().flatten() {
+            for value in values {
+                let holds = match self.holds(pos, &format!("{id}[{name}={value}]")) {
+                    Ok(holds) => holds,
+                    // A property or value the block doesn't have fails to parse, so
+                    // the query stores nothing
+                    Err(error) if error.downcast_ref::<NoScore>().is_some() => false,
+                    Err(error) => return Err(error),
+                };
+                if holds {
+                    block.properties.insert(name.clone(), value.clone());
+                    break;
+                }
+            }
+        }
+        if !requested_nbt.is_empty() {
+            let mut values = Vec::with_capacity(requested_nbt.len());
+            for path in requested_nbt {
+                values.push((
+                    path.clone(),
+                    adapter::query_block_data(&self.world.bot, pos, path)?,
+                ));
+            }
+            block.nbt = Some(EntityNbt::from_string_values(values));
+        }
+        Ok(block)
+    }
+
+    fn set_block(&mut self, pos: BlockPos, block: &Block) -> Result<()> {
+        // RCON answers once the block is set; there's nothing to confirm
+        self.world.bot.send_command(&format!(
+            "setblock {} {} {} {}",
+            pos[0],
+            pos[1],
+            pos[2],
+            block.to_command()
+        ))?;
+        self.placed.insert(pos, block.id.clone());
+        Ok(())
+    }
+
+    fn summon_entity(
+        &mut self,
+        alias: &str,
+        entity_type: &str,
+        pos: [f64; 3],
+        nbt: Option<&EntityNbt>,
+    ) -> Result<()> {
+        self.world.summon_entity(alias, entity_type, pos, nbt)
+    }
+
+    fn teleport_entity(&mut self, alias: &str, pos: [f64; 3], rot: Option<[f32; 2]>) -> Result<()> {
+        self.world.teleport_entity(alias, pos, rot)
+    }
+
+    fn get_entity(&self, alias: &str, requested_nbt: &[String]) -> Result<Vec<EntityState>> {
+        self.world.get_entity(alias, requested_nbt)
+    }
+
+    fn find_entity(&self, entity_type: &str, requested_nbt: &[String]) -> Result<Vec<EntityState>> {
+        self.world.find_entity(entity_type, requested_nbt)
+    }
+
+    fn create_player(&mut self) -> Box<dyn FlintPlayer> {
+        Box::new(UnsupportedPlayer)
+    }
+
+    fn fill(&mut self, region: [[i32; 3]; 2], block: &Block) -> Result<()> {
+        self.placed.retain(|pos, _| {
+            (0..3).any(|axis| pos[axis] < region[0][axis] || pos[axis] > region[1][axis])
+        });
+        self.world.fill(region, block)
+    }
+}
+
+/// Stands in for the player on a server the bot hasn't joined
+struct UnsupportedPlayer;
+
+impl UnsupportedPlayer {
+    fn unsupported<T>(&self) -> Result<T> {
+        anyhow::bail!("player actions are not supported on the rcon backend")
+    }
+}
+
+impl FlintPlayer for UnsupportedPlayer {
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    fn set_slot(&mut self, _slot: PlayerSlot, _item: Option<&Item>) -> Result<()> {
+        self.unsupported()
+    }
+
+    fn get_slot(
+        &mut self,
+        _slot: PlayerSlot,
+        _requested_data: Vec<String>,
+    ) -> Result<Option<Item>> {
+        self.unsupported()
+    }
+
+    fn select_hotbar(&mut self, _slot: u8) -> Result<()> {
+        self.unsupported()
+    }
+
+    fn selected_hotbar(&self) -> u8 {
+        1
+    }
+
+    fn teleport(&mut self, _pos: [f64; 3], _rot: Option<[f32; 2]>) -> Result<()> {
+        self.unsupported()
+    }
+
+    fn interact(&mut self) -> Result<()> {
+        self.unsupported()
+    }
+
+    fn set_game_mode(&mut self, _mode: GameMode) -> Result<()> {
+        self.unsupported()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot::mock_server::MockServer;
+    use crate::executor::block::make_block;
+
+    fn world(server: &MockServer, ids: &[&str]) -> RconWorld {
+        let bot = server.rcon_only_bot().unwrap();
+        let mut probes = Probes::default();
+        for id in ids {
+            probes.add_block(&make_block(id));
+        }
+        RconWorld {
+            world: MinecraftAdapter::new(bot).world_at([0, 0, 0], None),
+            probes,
+            placed: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_blocks_are_read_by_probing() {
+        let server = MockServer::start().unwrap();
+        let mut world = world(
+            &server,
+            &[
+                "minecraft:air",
+                "minecraft:stone",
+                "minecraft:lever[powered=true]",
+                "minecraft:lever[powered=false]",
+            ],
+        );
+
+        world
+            .set_block([0, 64, 0], &make_block("minecraft:lever[powered=true]"))
+            .unwrap();
+        let block = world.get_block([0, 64, 0], &[]).unwrap();
+        assert_eq!(block.id, "minecraft:lever");
+        assert_eq!(
+            block.properties.get("powered").map(String::as_str),
+            Some("true")
+        );
+
+        assert_eq!(
+            world.get_block([1, 64, 0], &[]).unwrap().id,
+            "minecraft:air"
+        );
+        server.set_block([2, 64, 0], "minecraft:dirt");
+        assert!(world.get_block([2, 64, 0], &[]).is_err());
+    }
+
+    #[test]
+    fn test_only_the_blocks_own_properties_are_probed() {
+        let server = MockServer::start().unwrap();
+        let mut world = world(
+            &server,
+            &[
+                "minecraft:stone",
+                "minecraft:lever[powered=true]",
+                "minecraft:repeater[delay=2]",
+            ],
+        );
+        world
+            .set_block([0, 64, 0], &make_block("minecraft:lever[powered=true]"))
+            .unwrap();
+        let before = server.commands().len();
+        let block = world.get_block([0, 64, 0], &[]).unwrap();
+        assert_eq!(block.properties.len(), 1);
+        let probes: Vec<_> = server.commands()[before..]
+            .iter()
+            .filter(|command| command.contains('['))
+            .cloned()
+            .collect();
+        assert_eq!(probes.len(), 1, "{probes:?}");
+        assert!(probes[0].ends_with("minecraft:lever[powered=true]"));
+    }
+
+    #[test]
+    fn test_asserted_states_belong_to_blocks_with_that_property() {
+        let mut probes = Probes::default();
+        probes.add_block(&make_block("minecraft:lever[powered=true]"));
+        probes.add_block(&make_block("minecraft:stone"));
+        probes.add_state("powered", BTreeSet::from(["false".to_string()]));
+        probes.add_state("facing", BTreeSet::from(["north".to_string()]));
+        assert_eq!(
+            probes.properties["minecraft:lever"]["powered"],
+            BTreeSet::from(["false".to_string(), "true".to_string()])
+        );
+        assert!(!probes.properties["minecraft:stone"].contains_key("powered"));
+        // Nothing says which block has `facing`, so every block is probed for it
+        assert!(probes.properties["minecraft:stone"].contains_key("facing"));
+    }
+
+    #[test]
+    fn test_player_tests_are_detected() {
+        let mut test = TestSpec {
+            flint_version: None,
+            name: "interact".to_string(),
+            description: None,
+            tags: vec![],
+            minecraft_ids: vec![],
+            dependencies: vec![],
+            setup: None,
+            timeline: vec![],
+            breakpoints: vec![],
+        };
+        assert!(!needs_player(&test));
+        test.timeline.push(flint_core::test_spec::TimelineEntry {
+            at: flint_core::test_spec::TickSpec::Single(0),
+            action_type: ActionType::Interact { item: None },
+        });
+        assert!(needs_player(&test));
+    }
+}
@@ -1,5 +1,7 @@
 //! Recorded action types for the test recorder
 
+use flint_core::test_spec::{GameMode, Item, PlayerSlot};
+
 /// A recorded action in the timeline
 #[derive(Debug, Clone, PartialEq)]
 pub enum RecordedAction {
@@ -14,6 +16,16 @@ pub enum RecordedAction {
         pos: [i32; 3],
         block: String,
     },
+    /// Block state observed after a `!sprint` tick, collapsed into `assert_state` on save
+    StateSample {
+        pos: [i32; 3],
+        block: String,
+    },
+    /// Box of identical placements produced by compaction
+    Fill {
+        region: [[i32; 3]; 2],
+        block: String,
+    },
     Tp {
         pos: [f64; 3],
         rot: Option<[f32; 2]>,
@@ -21,6 +33,17 @@ pub enum RecordedAction {
     Interact {
         item: Option<String>,
     },
+    /// Entity that appeared in the scan region between snapshots
+    Summon {
+        alias: String,
+        entity_type: String,
+        pos: [f64; 3],
+    },
+    AssertEntity {
+        alias: String,
+        entity_type: String,
+        pos: [f64; 3],
+    },
 }
 
 /// A step in the recorded timeline
@@ -29,3 +52,11 @@ pub struct TimelineStep {
     pub tick: u32,
     pub actions: Vec<RecordedAction>,
 }
+
+/// Player state captured when a recording starts
+#[derive(Clone)]
+pub struct RecordedPlayer {
+    pub inventory: Vec<(PlayerSlot, Item)>,
+    pub selected_hotbar: u8,
+    pub game_mode: GameMode,
+}
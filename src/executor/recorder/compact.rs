@@ -0,0 +1,225 @@
+//! Post-processing that compacts a recorded timeline before it is saved
+
+use flint_core::test_spec::{
+    ActionType, AssertType, BlockCheck, BlockSpec, TickSpec, TimelineEntry,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::executor::block::make_block;
+
+use super::actions::{RecordedAction, TimelineStep};
+
+/// Smallest number of identical blocks worth turning into a `fill`
+const MIN_FILL_VOLUME: usize = 2;
+
+/// Compact the actions of a single recorded tick.
+///
+/// Assertions that only repeat the block placed earlier in the same tick are dropped,
+/// and runs of identical placements between player actions are merged into fills.
+pub fn compact_step(actions: &[RecordedAction]) -> Vec<RecordedAction> {
+    let mut placed: HashMap<[i32; 3], &str> = HashMap::new();
+    let mut kept = Vec::with_capacity(actions.len());
+    for action in actions {
+        match action {
+            RecordedAction::Place { pos, block } => {
+                placed.insert(*pos, block.as_str());
+            }
+            RecordedAction::Remove { pos } => {
+                placed.insert(*pos, "minecraft:air");
+            }
+            RecordedAction::Assert { pos, block } if placed.get(pos) == Some(&block.as_str()) => {
+                continue;
+            }
+            _ => {}
+        }
+        kept.push(action.clone());
+    }
+
+    let mut compacted = Vec::with_capacity(kept.len());
+    let mut segment = Vec::new();
+    for action in kept {
+        match action {
+            RecordedAction::Place { .. } | RecordedAction::Remove { .. } => segment.push(action),
+            RecordedAction::Tp { .. }
+            | RecordedAction::Interact { .. }
+            | RecordedAction::Summon { .. } => {
+                compacted.extend(merge_placements(std::mem::take(&mut segment)));
+                compacted.push(action);
+            }
+            other => compacted.push(other),
+        }
+    }
+    compacted.extend(merge_placements(segment));
+    compacted
+}
+
+/// Merge axis-aligned boxes of identical placements into fills. Placements that do not
+/// belong to a large enough box are returned unchanged in their original order.
+///
+/// Fills go first, so a position written more than once is never merged: all of its
+/// writes stay single placements and the last one still wins.
+fn merge_placements(placements: Vec<RecordedAction>) -> Vec<RecordedAction> {
+    let mut writes: HashMap<[i32; 3], usize> = HashMap::new();
+    for action in &placements {
+        if let RecordedAction::Place { pos, .. } | RecordedAction::Remove { pos } = action {
+            *writes.entry(*pos).or_default() += 1;
+        }
+    }
+
+    let mut by_block: BTreeMap<String, HashSet<[i32; 3]>> = BTreeMap::new();
+    for action in &placements {
+        match action {
+            RecordedAction::Place { pos, .. } | RecordedAction::Remove { pos }
+                if writes[pos] > 1 => {}
+            RecordedAction::Place { pos, block } => {
+                by_block.entry(block.clone()).or_default().insert(*pos);
+            }
+            RecordedAction::Remove { pos } => {
+                by_block
+                    .entry("minecraft:air".to_string())
+                    .or_default()
+                    .insert(*pos);
+            }
+            _ => {}
+        }
+    }
+
+    let mut fills = Vec::new();
+    let mut filled: HashSet<[i32; 3]> = HashSet::new();
+    for (block, positions) in by_block {
+        for region in merge_boxes(&positions) {
+            let volume = (0..3)
+                .map(|axis| (region[1][axis] - region[0][axis] + 1) as usize)
+                .product::<usize>();
+            if volume < MIN_FILL_VOLUME {
+                continue;
+            }
+            for x in region[0][0]..=region[1][0] {
+                for y in region[0][1]..=region[1][1] {
+                    for z in region[0][2]..=region[1][2] {
+                        filled.insert([x, y, z]);
+                    }
+                }
+            }
+            fills.push(RecordedAction::Fill {
+                region,
+                block: block.clone(),
+            });
+        }
+    }
+
+    fills.extend(placements.into_iter().filter(|action| match action {
+        RecordedAction::Place { pos, .. } | RecordedAction::Remove { pos } => !filled.contains(pos),
+        _ => true,
+    }));
+    fills
+}
+
+/// Greedily cover a set of positions with boxes: grow along X, then Z, then Y.
+fn merge_boxes(positions: &HashSet<[i32; 3]>) -> Vec<[[i32; 3]; 2]> {
+    let mut ordered: Vec<[i32; 3]> = positions.iter().copied().collect();
+    ordered.sort_by_key(|pos| (pos[1], pos[2], pos[0]));
+
+    let mut remaining = positions.clone();
+    let mut boxes = Vec::new();
+    for start in ordered {
+        if !remaining.contains(&start) {
+            continue;
+        }
+
+        let mut max = start;
+        while remaining.contains(&[max[0] + 1, start[1], start[2]]) {
+            max[0] += 1;
+        }
+        while (start[0]..=max[0]).all(|x| remaining.contains(&[x, start[1], max[2] + 1])) {
+            max[2] += 1;
+        }
+        while (start[0]..=max[0])
+            .all(|x| (start[2]..=max[2]).all(|z| remaining.contains(&[x, max[1] + 1, z])))
+        {
+            max[1] += 1;
+        }
+
+        for x in start[0]..=max[0] {
+            for y in start[1]..=max[1] {
+                for z in start[2]..=max[2] {
+                    remaining.remove(&[x, y, z]);
+                }
+            }
+        }
+        boxes.push([start, max]);
+    }
+    boxes
+}
+
+/// Collapse the per-tick samples recorded by `!sprint` into timeline entries.
+///
+/// A position whose block id stays the same across all samples becomes one full block
+/// assertion at the first sampled tick plus one `assert_state` per property that
+/// changes. Positions whose block id changes keep one block assertion per sample.
+pub fn collapse_state_samples(timeline: &[TimelineStep]) -> Vec<TimelineEntry> {
+    let mut samples: BTreeMap<[i32; 3], Vec<(u32, &str)>> = BTreeMap::new();
+    for step in timeline {
+        for action in &step.actions {
+            if let RecordedAction::StateSample { pos, block } = action {
+                samples
+                    .entry(*pos)
+                    .or_default()
+                    .push((step.tick, block.as_str()));
+            }
+        }
+    }
+
+    let mut entries = Vec::new();
+    for (pos, samples) in samples {
+        let blocks: Vec<_> = samples.iter().map(|(_, block)| make_block(block)).collect();
+        let same_id = blocks.iter().all(|block| block.id == blocks[0].id);
+        if samples.len() < 2 || !same_id {
+            for (tick, block) in &samples {
+                entries.push(block_assert(*tick, pos, block));
+            }
+            continue;
+        }
+
+        entries.push(block_assert(samples[0].0, pos, samples[0].1));
+
+        let mut properties: Vec<&String> = blocks
+            .iter()
+            .flat_map(|block| block.properties.keys())
+            .collect();
+        properties.sort();
+        properties.dedup();
+
+        let ticks: Vec<u32> = samples.iter().map(|(tick, _)| *tick).collect();
+        for property in properties {
+            let values: Vec<String> = blocks
+                .iter()
+                .map(|block| block.properties.get(property).cloned().unwrap_or_default())
+                .collect();
+            if values.iter().all(|value| *value == values[0]) {
+                continue;
+            }
+            entries.push(TimelineEntry {
+                at: TickSpec::Multiple(ticks.clone()),
+                action_type: ActionType::AssertState {
+                    pos,
+                    state: property.clone(),
+                    values,
+                },
+            });
+        }
+    }
+    entries
+}
+
+fn block_assert(tick: u32, pos: [i32; 3], block: &str) -> TimelineEntry {
+    TimelineEntry {
+        at: TickSpec::Single(tick),
+        action_type: ActionType::Assert {
+            checks: vec![AssertType::Block(BlockCheck {
+                pos,
+                is: BlockSpec::Single(make_block(block)),
+            })],
+        },
+    }
+}
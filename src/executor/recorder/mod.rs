@@ -2,8 +2,10 @@
 
 mod actions;
 mod bounding_box;
+mod compact;
 mod state;
 #[cfg(test)]
 mod tests;
 
+pub use actions::RecordedPlayer;
 pub use state::RecorderState;
@@ -2,20 +2,22 @@
 
 use anyhow::Result;
 use flint_core::test_spec::{
-    ActionType, AssertType, BlockCheck, BlockPlacement, BlockSpec, CleanupSpec, SetupSpec,
-    TestSpec, TickSpec, TimelineEntry,
+    ActionType, AssertType, BlockCheck, BlockPlacement, BlockSpec, CleanupSpec, EntityCheck,
+    PlayerSpec, SetupSpec, TestSpec, TickSpec, TimelineEntry,
 };
 use std::collections::HashMap;
 use std::path::PathBuf;
 
 use crate::executor::block::make_block;
 
-use super::actions::{RecordedAction, TimelineStep};
+use super::actions::{RecordedAction, RecordedPlayer, TimelineStep};
 use super::bounding_box::BoundingBox;
+use super::compact;
 
 // Constants
 const DEFAULT_SCAN_RADIUS: i32 = 16;
 const DEFAULT_CLEANUP_REGION: [[i32; 3]; 2] = [[0, 0, 0], [10, 10, 10]];
+const ENTITY_POSITION_TOLERANCE: f64 = 0.5;
 
 /// State for an active recording session
 pub struct RecorderState {
@@ -39,6 +41,10 @@ pub struct RecorderState {
     pub scan_center: Option<[i32; 3]>,
     /// Scan radius around player to detect block changes
     pub scan_radius: i32,
+    /// Aliases of recorded entities (alias -> entity type)
+    pub entity_aliases: HashMap<String, String>,
+    /// Inventory and hotbar of the tracked player when recording started
+    pub player_setup: Option<RecordedPlayer>,
 }
 
 impl RecorderState {
@@ -68,6 +74,8 @@ impl RecorderState {
             player_name: None,
             scan_center: None,
             scan_radius: DEFAULT_SCAN_RADIUS,
+            entity_aliases: HashMap::new(),
+            player_setup: None,
         }
     }
 
@@ -190,6 +198,22 @@ impl RecorderState {
         });
     }
 
+    /// Record the block observed at a position after a `!sprint` tick
+    pub fn add_state_sample(&mut self, world_pos: [i32; 3], block: &str) {
+        if self.origin.is_none() {
+            self.set_origin(world_pos);
+        }
+
+        let local_pos = self.to_local(world_pos);
+        self.bounds.expand(local_pos);
+
+        let step = self.get_or_create_current_step();
+        step.actions.push(RecordedAction::StateSample {
+            pos: local_pos,
+            block: block.to_string(),
+        });
+    }
+
     /// Record a player use action at an exact world position and rotation.
     pub fn record_use(&mut self, world_pos: [f64; 3], rot: Option<[f32; 2]>, item: Option<String>) {
         let block_pos = [
@@ -212,6 +236,58 @@ impl RecorderState {
         step.actions.push(RecordedAction::Interact { item });
     }
 
+    /// Pick a fresh alias for an entity type, e.g. `pig_1`, `pig_2`
+    #[must_use]
+    pub fn next_entity_alias(&self, entity_type: &str) -> String {
+        let base = entity_type
+            .trim_start_matches("minecraft:")
+            .replace(':', "_");
+        (1..)
+            .map(|index| format!("{base}_{index}"))
+            .find(|alias| !self.entity_aliases.contains_key(alias))
+            .unwrap()
+    }
+
+    /// Record an entity that appeared in the scan region
+    pub fn record_summon(&mut self, alias: &str, entity_type: &str, world_pos: [f64; 3]) {
+        let block_pos = [
+            world_pos[0].floor() as i32,
+            world_pos[1].floor() as i32,
+            world_pos[2].floor() as i32,
+        ];
+        if self.origin.is_none() {
+            self.set_origin(block_pos);
+        }
+
+        let local_pos = self.to_local_f64(world_pos);
+        self.bounds.expand(self.to_local(block_pos));
+        self.entity_aliases
+            .insert(alias.to_string(), entity_type.to_string());
+
+        let step = self.get_or_create_current_step();
+        step.actions.push(RecordedAction::Summon {
+            alias: alias.to_string(),
+            entity_type: entity_type.to_string(),
+            pos: local_pos,
+        });
+    }
+
+    /// Add an assertion for a recorded entity at its current world position
+    pub fn add_entity_assertion(&mut self, alias: &str, world_pos: [f64; 3]) -> Result<()> {
+        let Some(entity_type) = self.entity_aliases.get(alias).cloned() else {
+            anyhow::bail!("unknown entity alias: {alias}");
+        };
+        let local_pos = self.to_local_f64(world_pos);
+
+        let step = self.get_or_create_current_step();
+        step.actions.push(RecordedAction::AssertEntity {
+            alias: alias.to_string(),
+            entity_type,
+            pos: local_pos,
+        });
+        Ok(())
+    }
+
     /// Convert all Place/Remove actions in the current tick to Assertions
     pub fn convert_actions_to_asserts(&mut self) -> usize {
         let mut converted_count = 0;
@@ -236,12 +312,28 @@ impl RecorderState {
                         });
                         converted_count += 1;
                     }
+                    RecordedAction::Fill { region, block } => {
+                        for x in region[0][0]..=region[1][0] {
+                            for y in region[0][1]..=region[1][1] {
+                                for z in region[0][2]..=region[1][2] {
+                                    new_actions.push(RecordedAction::Assert {
+                                        pos: [x, y, z],
+                                        block: block.clone(),
+                                    });
+                                    converted_count += 1;
+                                }
+                            }
+                        }
+                    }
                     // Keep existing asserts unchanged
-                    assert_action @ RecordedAction::Assert { .. } => {
+                    assert_action @ (RecordedAction::Assert { .. }
+                    | RecordedAction::StateSample { .. }) => {
                         new_actions.push(assert_action);
                     }
                     player_action @ (RecordedAction::Tp { .. }
-                    | RecordedAction::Interact { .. }) => {
+                    | RecordedAction::Interact { .. }
+                    | RecordedAction::Summon { .. }
+                    | RecordedAction::AssertEntity { .. }) => {
                         new_actions.push(player_action);
                     }
                 }
@@ -258,6 +350,32 @@ impl RecorderState {
         self.current_tick += 1;
     }
 
+    /// Diff a fresh scan of the recorded region against the snapshot and assert every
+    /// changed position at the current tick. The scan becomes the new snapshot, so the
+    /// observed changes are not later mistaken for player placements.
+    pub fn observe_changes(&mut self, current: HashMap<[i32; 3], String>) -> usize {
+        let mut changed: Vec<([i32; 3], String)> = current
+            .iter()
+            .filter(|(pos, block)| self.snapshot.get(*pos) != Some(*block))
+            .map(|(pos, block)| (*pos, block.clone()))
+            .collect();
+        changed.extend(
+            self.snapshot
+                .iter()
+                .filter(|(pos, block)| {
+                    !current.contains_key(*pos) && !block.to_lowercase().contains("air")
+                })
+                .map(|(pos, _)| (*pos, "minecraft:air".to_string())),
+        );
+        changed.sort();
+
+        for (pos, block) in &changed {
+            self.add_assertion(*pos, block);
+        }
+        self.snapshot = current;
+        changed.len()
+    }
+
     /// Generate a TestSpec from the recorded data
     #[must_use]
     pub fn generate_test_spec(&self) -> TestSpec {
@@ -272,7 +390,7 @@ impl RecorderState {
 
         for step in &self.timeline {
             let mut placements: Vec<BlockPlacement> = Vec::new();
-            let mut checks: Vec<BlockCheck> = Vec::new();
+            let mut checks: Vec<AssertType> = Vec::new();
 
             let flush_placements =
                 |timeline_entries: &mut Vec<TimelineEntry>,
@@ -288,22 +406,18 @@ impl RecorderState {
                 };
 
             let flush_checks = |timeline_entries: &mut Vec<TimelineEntry>,
-                                checks: &mut Vec<BlockCheck>| {
+                                checks: &mut Vec<AssertType>| {
                 if !checks.is_empty() {
-                    let assert_checks = std::mem::take(checks)
-                        .into_iter()
-                        .map(AssertType::Block)
-                        .collect();
                     timeline_entries.push(TimelineEntry {
                         at: TickSpec::Single(step.tick),
                         action_type: ActionType::Assert {
-                            checks: assert_checks,
+                            checks: std::mem::take(checks),
                         },
                     });
                 }
             };
 
-            for action in &step.actions {
+            for action in &compact::compact_step(&step.actions) {
                 match action {
                     RecordedAction::Place { pos, block } => {
                         placements.push(BlockPlacement {
@@ -317,10 +431,51 @@ impl RecorderState {
                             block: make_block("minecraft:air"),
                         });
                     }
+                    RecordedAction::Fill { region, block } => {
+                        timeline_entries.push(TimelineEntry {
+                            at: TickSpec::Single(step.tick),
+                            action_type: ActionType::Fill {
+                                region: *region,
+                                with: make_block(block),
+                            },
+                        });
+                    }
+                    // Collapsed across ticks below
+                    RecordedAction::StateSample { .. } => {}
                     RecordedAction::Assert { pos, block } => {
-                        checks.push(BlockCheck {
+                        checks.push(AssertType::Block(BlockCheck {
                             pos: *pos,
                             is: BlockSpec::Single(make_block(block)),
+                        }));
+                    }
+                    RecordedAction::AssertEntity {
+                        alias,
+                        entity_type,
+                        pos,
+                    } => {
+                        checks.push(AssertType::Entity(EntityCheck {
+                            entity_alias: Some(alias.clone()),
+                            entity_type: Some(entity_type.clone()),
+                            pos: Some(*pos),
+                            position_tolerance: Some(ENTITY_POSITION_TOLERANCE),
+                            ..Default::default()
+                        }));
+                    }
+                    RecordedAction::Summon {
+                        alias,
+                        entity_type,
+                        pos,
+                    } => {
+                        flush_placements(&mut timeline_entries, &mut placements);
+                        flush_checks(&mut timeline_entries, &mut checks);
+                        timeline_entries.push(TimelineEntry {
+                            at: TickSpec::Single(step.tick),
+                            action_type: ActionType::Summon {
+                                entity_alias: alias.clone(),
+                                entity_type: entity_type.clone(),
+                                pos: *pos,
+                                nbt: None,
+                            },
                         });
                     }
                     RecordedAction::Tp { pos, rot } => {
@@ -350,6 +505,8 @@ impl RecorderState {
             flush_checks(&mut timeline_entries, &mut checks);
         }
 
+        timeline_entries.extend(compact::collapse_state_samples(&self.timeline));
+
         TestSpec {
             flint_version: Some("1.0.0".to_string()),
             name: self.test_name.replace('/', "_"),
@@ -361,7 +518,11 @@ impl RecorderState {
                 cleanup: Some(CleanupSpec {
                     region: cleanup_region,
                 }),
-                player: None,
+                player: self.player_setup.as_ref().map(|player| PlayerSpec {
+                    inventory: player.inventory.iter().cloned().collect(),
+                    selected_hotbar: player.selected_hotbar,
+                    game_mode: player.game_mode,
+                }),
                 world: Default::default(),
             }),
             timeline: timeline_entries,
@@ -1,6 +1,8 @@
 //! Tests for the recorder module
 
+use super::actions::RecordedAction;
 use super::bounding_box::BoundingBox;
+use super::compact::compact_step;
 use super::state::RecorderState;
 use flint_core::test_spec::ActionType;
 
@@ -61,3 +63,177 @@ fn test_record_use_emits_tp_then_interact() {
         other => panic!("expected interact, got {other:?}"),
     }
 }
+
+#[test]
+fn test_save_merges_identical_placements_into_fill() {
+    let mut recorder = RecorderState::new("test", std::path::Path::new("/tmp"));
+    for x in 0..4 {
+        for z in 0..2 {
+            recorder.record_place([100 + x, 64, 200 + z], "minecraft:stone");
+        }
+    }
+    recorder.record_place([110, 64, 200], "minecraft:dirt");
+
+    let spec = recorder.generate_test_spec();
+    assert_eq!(spec.timeline.len(), 2);
+
+    match &spec.timeline[0].action_type {
+        ActionType::Fill { region, with } => {
+            assert_eq!(*region, [[0, 0, 0], [3, 0, 1]]);
+            assert_eq!(with.id, "minecraft:stone");
+        }
+        other => panic!("expected fill, got {other:?}"),
+    }
+
+    match &spec.timeline[1].action_type {
+        ActionType::PlaceEach { blocks } => {
+            assert_eq!(blocks.len(), 1);
+            assert_eq!(blocks[0].pos, [10, 0, 0]);
+        }
+        other => panic!("expected place_each, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_overwritten_positions_are_not_merged_into_fills() {
+    let place = |x: i32| RecordedAction::Place {
+        pos: [x, 0, 0],
+        block: "minecraft:stone".to_string(),
+    };
+    let actions = vec![
+        place(0),
+        place(1),
+        place(2),
+        place(3),
+        RecordedAction::Remove { pos: [1, 0, 0] },
+    ];
+
+    assert_eq!(
+        compact_step(&actions),
+        vec![
+            RecordedAction::Fill {
+                region: [[2, 0, 0], [3, 0, 0]],
+                block: "minecraft:stone".to_string(),
+            },
+            place(0),
+            place(1),
+            RecordedAction::Remove { pos: [1, 0, 0] },
+        ]
+    );
+}
+
+#[test]
+fn test_save_drops_asserts_repeating_same_tick_placement() {
+    let mut recorder = RecorderState::new("test", std::path::Path::new("/tmp"));
+    recorder.record_place([100, 64, 200], "minecraft:stone");
+    recorder.add_assertion([100, 64, 200], "minecraft:stone");
+    recorder.add_assertion([101, 64, 200], "minecraft:air");
+
+    let spec = recorder.generate_test_spec();
+    let checks: usize = spec
+        .timeline
+        .iter()
+        .map(|entry| match &entry.action_type {
+            ActionType::Assert { checks } => checks.len(),
+            _ => 0,
+        })
+        .sum();
+    assert_eq!(checks, 1);
+}
+
+#[test]
+fn test_sprint_samples_collapse_into_assert_state() {
+    let mut recorder = RecorderState::new("test", std::path::Path::new("/tmp"));
+    recorder.set_origin([100, 64, 200]);
+    for powered in ["false", "true", "true"] {
+        recorder.next_tick();
+        recorder.add_state_sample(
+            [100, 64, 200],
+            &format!("minecraft:repeater[delay=1,facing=north,powered={powered}]"),
+        );
+    }
+
+    let spec = recorder.generate_test_spec();
+    assert_eq!(spec.timeline.len(), 2);
+    assert!(matches!(
+        spec.timeline[0].action_type,
+        ActionType::Assert { .. }
+    ));
+    match &spec.timeline[1].action_type {
+        ActionType::AssertState { pos, state, values } => {
+            assert_eq!(*pos, [0, 0, 0]);
+            assert_eq!(state, "powered");
+            assert_eq!(values, &["false", "true", "true"]);
+        }
+        other => panic!("expected assert_state, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_observe_changes_asserts_diff_and_updates_snapshot() {
+    let mut recorder = RecorderState::new("test", std::path::Path::new("/tmp"));
+    recorder.set_origin([100, 64, 200]);
+    recorder.snapshot = std::collections::HashMap::from([
+        ([100, 64, 200], "minecraft:lever[powered=false]".to_string()),
+        ([101, 64, 200], "minecraft:redstone_torch".to_string()),
+    ]);
+
+    recorder.next_tick();
+    let current = std::collections::HashMap::from([
+        ([100, 64, 200], "minecraft:lever[powered=true]".to_string()),
+        ([102, 64, 200], "minecraft:redstone_wire".to_string()),
+    ]);
+    assert_eq!(recorder.observe_changes(current.clone()), 3);
+    assert_eq!(recorder.snapshot, current);
+
+    recorder.next_tick();
+    assert_eq!(recorder.observe_changes(current), 0);
+
+    let spec = recorder.generate_test_spec();
+    assert_eq!(spec.timeline.len(), 1);
+    match &spec.timeline[0].action_type {
+        ActionType::Assert { checks } => assert_eq!(checks.len(), 3),
+        other => panic!("expected assert, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_recorded_entities_become_summon_and_entity_assert() {
+    let mut recorder = RecorderState::new("test", std::path::Path::new("/tmp"));
+    recorder.set_origin([100, 64, 200]);
+
+    let alias = recorder.next_entity_alias("minecraft:pig");
+    assert_eq!(alias, "pig_1");
+    recorder.record_summon(&alias, "minecraft:pig", [101.5, 64.0, 200.5]);
+    assert_eq!(recorder.next_entity_alias("minecraft:pig"), "pig_2");
+
+    recorder.next_tick();
+    recorder
+        .add_entity_assertion("pig_1", [103.5, 64.0, 200.5])
+        .unwrap();
+    assert!(
+        recorder
+            .add_entity_assertion("cow_1", [0.0, 0.0, 0.0])
+            .is_err()
+    );
+
+    let spec = recorder.generate_test_spec();
+    assert_eq!(spec.timeline.len(), 2);
+    match &spec.timeline[0].action_type {
+        ActionType::Summon {
+            entity_alias,
+            entity_type,
+            pos,
+            ..
+        } => {
+            assert_eq!(entity_alias, "pig_1");
+            assert_eq!(entity_type, "minecraft:pig");
+            assert_eq!(*pos, [1.5, 0.0, 0.5]);
+        }
+        other => panic!("expected summon, got {other:?}"),
+    }
+    match &spec.timeline[1].action_type {
+        ActionType::Assert { checks } => assert_eq!(checks.len(), 1),
+        other => panic!("expected assert, got {other:?}"),
+    }
+}
@@ -0,0 +1,143 @@
+//! Differential runs: drive a second server in lockstep and compare test regions.
+//!
+//! The reference server receives exactly the same setup, actions and ticks as the
+//! server under test. Its assertion outcomes are ignored; instead, whenever a test
+//! asserts, its whole cleanup region is scanned on both servers and every position
+//! that differs is reported as a divergence.
+
+use anyhow::Result;
+use colored::Colorize;
+use flint_core::results::{AssertFailure, AssertPosition};
+use flint_core::test_spec::TestSpec;
+
+use super::snapshot::RegionSnapshot;
+use super::{AssertMismatch, TestExecutor, tick};
+use crate::bot::TestBot;
+
+impl TestExecutor {
+    /// Connect the bot for the reference server used by differential runs.
+    pub fn connect_reference(&mut self, server: &str) -> Result<()> {
+        let mut bot = TestBot::new();
+        bot.connect(server)?;
+        self.reference = Some(bot);
+        Ok(())
+    }
+
+    /// Step one tick on the server under test and, in lockstep, on the reference.
+    pub(super) fn step_tick(&mut self, verbose: bool) -> Result<u64> {
+        let elapsed = tick::step_tick(&mut self.bot, verbose)?;
+        if let Some(reference) = self.reference.as_mut() {
+            tick::step_tick(reference, false)?;
+        }
+        Ok(elapsed)
+    }
+
+    /// Sprint ticks on the server under test and, in lockstep, on the reference.
+    pub(super) fn sprint_ticks(&mut self, ticks: u32, verbose: bool) -> Result<u64> {
+        let elapsed = tick::sprint_ticks(&mut self.bot, ticks, verbose)?;
+        if let Some(reference) = self.reference.as_mut() {
+            tick::sprint_ticks(reference, ticks, false)?;
+        }
+        Ok(elapsed)
+    }
+
+    /// Run `f` with the reference bot swapped in as `self.bot`, so every helper that
+    /// talks to the server can be reused unchanged. Does nothing without a reference.
+    pub(super) fn on_reference<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<Option<T>> {
+        let Some(mut reference) = self.reference.take() else {
+            return Ok(None);
+        };
+        std::mem::swap(&mut self.bot, &mut reference);
+        let result = f(self);
+        std::mem::swap(&mut self.bot, &mut reference);
+        self.reference = Some(reference);
+        result.map(Some)
+    }
+
+    /// Compare the cleanup regions of the given tests between both servers. Each test
+    /// that diverges counts one failed assertion, and its first divergence becomes the
+    /// test's failure unless it already has one; every divergent position is reported.
+    /// Returns the number of divergent positions.
+    pub(super) fn compare_with_reference(
+        &mut self,
+        tick: u32,
+        tests_with_offsets: &[(TestSpec, [i32; 3])],
+        test_indices: &[usize],
+        test_results: &mut [(usize, usize)],
+        test_failures: &mut [Option<AssertFailure>],
+        mismatches: &mut Vec<AssertMismatch>,
+    ) -> Result<usize> {
+        let scan = |this: &mut Self| -> Result<Vec<RegionSnapshot>> {
+            this.bot.sync_client_world()?;
+            test_indices
+                .iter()
+                .map(|test_idx| {
+                    let (test, offset) = &tests_with_offsets[*test_idx];
+                    let region = test.cleanup_region();
+                    let blocks = this.scan_region(
+                        this.apply_offset(region[0], *offset),
+                        this.apply_offset(region[1], *offset),
+                    )?;
+                    Ok(RegionSnapshot::from_world(tick, &blocks, *offset))
+                })
+                .collect()
+        };
+        let ours = scan(self)?;
+        let Some(reference) = self.on_reference(scan)? else {
+            return Ok(0);
+        };
+
+        let mut divergent_positions = 0;
+        for ((test_idx, ours), reference) in test_indices.iter().zip(ours).zip(reference) {
+            let diff = reference.diff(&ours);
+            if diff.is_empty() {
+                continue;
+            }
+
+            let test = &tests_with_offsets[*test_idx].0;
+            test_results[*test_idx].1 += 1;
+            if test_failures[*test_idx].is_none() {
+                // Test-local, like the positions of failed assertions
+                let first = &diff[0];
+                let [x, y, z] = first.pos;
+                test_failures[*test_idx] = Some(AssertFailure::new(
+                    tick,
+                    first.expected.clone(),
+                    first.actual.clone(),
+                    AssertPosition::Coordinate { x, y, z },
+                ));
+            }
+            divergent_positions += diff.len();
+            eprintln!(
+                "    {} [{}] Tick {}: diverges from reference at {} position(s)",
+                "≠".red().bold(),
+                test.name,
+                tick,
+                diff.len()
+            );
+            for divergence in diff {
+                let [x, y, z] = divergence.pos;
+                eprintln!(
+                    "      [{}, {}, {}] reference {}, got {}",
+                    x,
+                    y,
+                    z,
+                    divergence.expected.green(),
+                    divergence.actual.red()
+                );
+                mismatches.push(AssertMismatch {
+                    test_name: test.name.clone(),
+                    tick,
+                    message: format!(
+                        "divergence: reference has {}, got {} at [{x}, {y}, {z}]",
+                        divergence.expected, divergence.actual
+                    ),
+                });
+            }
+        }
+        Ok(divergent_positions)
+    }
+}
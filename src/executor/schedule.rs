@@ -0,0 +1,108 @@
+//! Tick scheduling shared by every way of running a batch: how far to advance between
+//! events and when a test is finished.
+
+use flint_core::timeline::TimelineAggregate;
+
+/// Last tick with a timeline entry, per test
+pub(super) fn test_max_ticks(aggregate: &TimelineAggregate<'_>, test_count: usize) -> Vec<u32> {
+    let mut max_ticks = vec![0; test_count];
+    for (tick_num, entries) in &aggregate.timeline {
+        for (test_idx, _, _) in entries {
+            max_ticks[*test_idx] = max_ticks[*test_idx].max(*tick_num);
+        }
+    }
+    max_ticks
+}
+
+/// Ticks from `current_tick` to the next tick that needs attention: the next timeline
+/// event, one of `stops` (snapshot ticks, a `goto` target) or the end of the timeline.
+/// One tick is stepped; more are sprinted.
+pub(super) fn ticks_until_next_stop(
+    aggregate: &TimelineAggregate<'_>,
+    current_tick: u32,
+    stops: impl IntoIterator<Item = u32>,
+) -> u32 {
+    let next_event_tick = aggregate
+        .next_event_tick(current_tick)
+        .unwrap_or(aggregate.max_tick + 1);
+    let next_stop = stops
+        .into_iter()
+        .filter(|tick| *tick > current_tick)
+        .fold(next_event_tick, u32::min);
+
+    if next_stop <= aggregate.max_tick {
+        next_stop - current_tick
+    } else {
+        aggregate.max_tick.saturating_sub(current_tick)
+    }
+}
+
+/// Tests past their last entry whose areas haven't been cleaned up yet
+pub(super) fn finished_tests(
+    current_tick: u32,
+    test_max_ticks: &[u32],
+    tests_cleaned: &[bool],
+) -> Vec<usize> {
+    (0..test_max_ticks.len())
+        .filter(|test_idx| !tests_cleaned[*test_idx] && current_tick > test_max_ticks[*test_idx])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flint_core::test_spec::{ActionType, TestSpec, TickSpec, TimelineEntry};
+
+    fn test_with_ticks(name: &str, ticks: &[u32]) -> (TestSpec, [i32; 3]) {
+        let timeline = ticks
+            .iter()
+            .map(|tick| TimelineEntry {
+                at: TickSpec::Single(*tick),
+                action_type: ActionType::Assert { checks: vec![] },
+            })
+            .collect();
+        let test = TestSpec {
+            flint_version: None,
+            name: name.to_string(),
+            description: None,
+            tags: vec![],
+            minecraft_ids: vec![],
+            dependencies: vec![],
+            setup: None,
+            timeline,
+            breakpoints: vec![],
+        };
+        (test, [0, 0, 0])
+    }
+
+    #[test]
+    fn test_max_ticks_per_test() {
+        let tests = [test_with_ticks("a", &[0, 5]), test_with_ticks("b", &[2])];
+        let aggregate = TimelineAggregate::from_tests(&tests);
+        assert_eq!(test_max_ticks(&aggregate, 2), vec![5, 2]);
+    }
+
+    #[test]
+    fn test_advance_to_next_event_or_stop() {
+        let tests = [test_with_ticks("a", &[0, 10, 40])];
+        let aggregate = TimelineAggregate::from_tests(&tests);
+        // Sprint to the next event, unless a stop comes first
+        assert_eq!(ticks_until_next_stop(&aggregate, 0, []), 10);
+        assert_eq!(ticks_until_next_stop(&aggregate, 0, [4]), 4);
+        assert_eq!(ticks_until_next_stop(&aggregate, 10, [4, 30]), 20);
+        // A single tick is a step
+        assert_eq!(ticks_until_next_stop(&aggregate, 39, []), 1);
+        assert_eq!(ticks_until_next_stop(&aggregate, 40, []), 0);
+    }
+
+    #[test]
+    fn test_finished_tests_skip_cleaned() {
+        let max_ticks = [3, 8, 1];
+        assert_eq!(finished_tests(3, &max_ticks, &[false; 3]), vec![2]);
+        assert_eq!(
+            finished_tests(4, &max_ticks, &[false, false, true]),
+            vec![0]
+        );
+        assert!(finished_tests(9, &max_ticks, &[true; 3]).is_empty());
+    }
+}
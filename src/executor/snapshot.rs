@@ -0,0 +1,249 @@
+//! Golden snapshots of a test's cleanup region.
+//!
+//! A snapshot lives in a sidecar file next to the test (`foo.json` → `foo.snapshot`,
+//! which the test loader doesn't pick up as a test) and holds the non-air blocks of
+//! the cleanup region at one or more ticks, in test-local coordinates. Missing
+//! positions are air.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+const AIR: &str = "minecraft:air";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SnapshotFile {
+    pub snapshots: Vec<RegionSnapshot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegionSnapshot {
+    pub tick: u32,
+    pub blocks: Vec<SnapshotBlock>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotBlock {
+    pub pos: [i32; 3],
+    pub id: String,
+}
+
+/// A position whose block differs from the snapshot
+pub struct SnapshotMismatch {
+    pub pos: [i32; 3],
+    pub expected: String,
+    pub actual: String,
+}
+
+/// Path of the snapshot sidecar for a test file
+pub fn sidecar_path(test_file: &Path) -> PathBuf {
+    test_file.with_extension("snapshot")
+}
+
+impl SnapshotFile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("reading snapshot {}", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("parsing snapshot {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json_str = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json_str)
+            .with_context(|| format!("writing snapshot {}", path.display()))
+    }
+}
+
+impl RegionSnapshot {
+    /// Build a snapshot from scanned world blocks, converting them to test-local space
+    pub fn from_world(
+        tick: u32,
+        world_blocks: &HashMap<[i32; 3], String>,
+        offset: [i32; 3],
+    ) -> Self {
+        let mut blocks: Vec<SnapshotBlock> = world_blocks
+            .iter()
+            .map(|(pos, id)| SnapshotBlock {
+                pos: [pos[0] - offset[0], pos[1] - offset[1], pos[2] - offset[2]],
+                id: id.clone(),
+            })
+            .collect();
+        blocks.sort_by_key(|block| block.pos);
+        Self { tick, blocks }
+    }
+
+    /// Every position where `actual` differs from this snapshot, sorted by position
+    pub fn diff(&self, actual: &RegionSnapshot) -> Vec<SnapshotMismatch> {
+        let expected: HashMap<[i32; 3], &str> = self
+            .blocks
+            .iter()
+            .map(|block| (block.pos, block.id.as_str()))
+            .collect();
+        let actual: HashMap<[i32; 3], &str> = actual
+            .blocks
+            .iter()
+            .map(|block| (block.pos, block.id.as_str()))
+            .collect();
+
+        let positions: BTreeSet<[i32; 3]> = expected.keys().chain(actual.keys()).copied().collect();
+        positions
+            .into_iter()
+            .filter_map(|pos| {
+                let expected = expected.get(&pos).copied().unwrap_or(AIR);
+                let actual = actual.get(&pos).copied().unwrap_or(AIR);
+                (expected != actual).then(|| SnapshotMismatch {
+                    pos,
+                    expected: expected.to_string(),
+                    actual: actual.to_string(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Snapshot work for one test in a run: either compare against the sidecar or capture
+/// a new one when updating.
+pub struct SnapshotPlan {
+    pub path: PathBuf,
+    pub ticks: Vec<u32>,
+    /// Expected snapshots, empty when updating
+    pub expected: Vec<RegionSnapshot>,
+    pub updating: bool,
+    pub captured: Vec<RegionSnapshot>,
+}
+
+impl SnapshotPlan {
+    /// Load the plan for a test. Without `update`, tests without a sidecar have no plan.
+    /// A new sidecar captures the final tick; an existing one keeps its ticks.
+    pub fn load(path: PathBuf, final_tick: u32, update: bool) -> Result<Option<Self>> {
+        let existing = if path.exists() {
+            Some(SnapshotFile::load(&path)?)
+        } else {
+            None
+        };
+        if existing.is_none() && !update {
+            return Ok(None);
+        }
+
+        let expected = existing.map(|file| file.snapshots).unwrap_or_default();
+        let mut ticks: Vec<u32> = expected.iter().map(|snapshot| snapshot.tick).collect();
+        if ticks.is_empty() {
+            ticks.push(final_tick);
+        }
+        ticks.sort_unstable();
+        ticks.dedup();
+        if let Some(tick) = ticks.iter().find(|tick| **tick > final_tick) {
+            anyhow::bail!(
+                "snapshot {} is taken at tick {tick}, after the last tick of the test ({final_tick})",
+                path.display()
+            );
+        }
+
+        Ok(Some(Self {
+            path,
+            ticks,
+            expected: if update { Vec::new() } else { expected },
+            updating: update,
+            captured: Vec::new(),
+        }))
+    }
+
+    pub fn expected_at(&self, tick: u32) -> Option<&RegionSnapshot> {
+        self.expected.iter().find(|snapshot| snapshot.tick == tick)
+    }
+
+    /// Write the captured snapshots if every tick was reached
+    pub fn save_captured(&self) -> Result<bool> {
+        if !self.updating || self.captured.len() != self.ticks.len() {
+            return Ok(false);
+        }
+        SnapshotFile {
+            snapshots: self.captured.clone(),
+        }
+        .save(&self.path)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sidecar_path_replaces_extension() {
+        assert_eq!(
+            sidecar_path(Path::new("tests/redstone/lamp.json")),
+            PathBuf::from("tests/redstone/lamp.snapshot")
+        );
+    }
+
+    #[test]
+    fn test_loader_skips_sidecars() {
+        let dir = std::env::temp_dir().join(format!("flintmc-sidecar-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let test_file = dir.join("basic_placement.json");
+        std::fs::copy(
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/example_tests/basic_placement.json"
+            ),
+            &test_file,
+        )
+        .unwrap();
+        SnapshotFile::default()
+            .save(&sidecar_path(&test_file))
+            .unwrap();
+
+        let loader = flint_core::loader::TestLoader::new(&dir, false).unwrap();
+        let files = loader.collect_all_test_files().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(files, vec![test_file]);
+    }
+
+    #[test]
+    fn test_diff_reports_changed_missing_and_extra_blocks() {
+        let expected = RegionSnapshot::from_world(
+            5,
+            &HashMap::from([
+                ([10, 64, 10], "minecraft:stone".to_string()),
+                (
+                    [11, 64, 10],
+                    "minecraft:redstone_lamp[lit=true]".to_string(),
+                ),
+            ]),
+            [10, 64, 10],
+        );
+        let actual = RegionSnapshot::from_world(
+            5,
+            &HashMap::from([
+                (
+                    [11, 64, 10],
+                    "minecraft:redstone_lamp[lit=false]".to_string(),
+                ),
+                ([12, 64, 10], "minecraft:sand".to_string()),
+            ]),
+            [10, 64, 10],
+        );
+
+        let mismatches = expected.diff(&actual);
+        let summary: Vec<_> = mismatches
+            .iter()
+            .map(|m| (m.pos, m.expected.as_str(), m.actual.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ([0, 0, 0], "minecraft:stone", AIR),
+                (
+                    [1, 0, 0],
+                    "minecraft:redstone_lamp[lit=true]",
+                    "minecraft:redstone_lamp[lit=false]"
+                ),
+                ([2, 0, 0], AIR, "minecraft:sand"),
+            ]
+        );
+        assert!(expected.diff(&expected.clone()).is_empty());
+    }
+}
@@ -1,16 +1,19 @@
 //! Tick management - gametime queries, stepping, and sprinting
 
-use crate::bot::TestBot;
+use super::control::ControlChannel;
+use super::inspect::{INSPECT_HELP, Inspector};
+use crate::bot::{BOT_NAME, TestBot};
 use anyhow::Result;
 use colored::Colorize;
 
 // Constants for tick timing
 pub const CHAT_DRAIN_TIMEOUT_MS: u64 = 10;
 pub const CHAT_POLL_TIMEOUT_MS: u64 = 100;
-pub const GAMETIME_QUERY_TIMEOUT_SECS: u64 = 5;
 pub const TICK_STEP_TIMEOUT_SECS: u64 = 5;
 pub const SPRINT_TIMEOUT_SECS: u64 = 30;
-pub const MIN_RETRY_DELAY_MS: u64 = 200;
+/// Pause between game-time checks while a step or sprint runs. Every check is a
+/// scoreboard query of several commands, so polling flat out floods the server.
+pub const GAMETIME_POLL_MS: u64 = 20;
 
 /// Drain old chat messages from the bot's queue
 pub fn drain_chat_messages(bot: &mut TestBot) {
@@ -22,8 +25,40 @@ pub fn drain_chat_messages(bot: &mut TestBot) {
     }
 }
 
-/// Returns true to continue, false to step to next tick only
-pub fn wait_for_step(bot: &mut TestBot, reason: &str) -> Result<bool> {
+/// What to do after a breakpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakpointAction {
+    /// Run one tick, then pause again
+    Step,
+    /// Run to the next breakpoint
+    Continue,
+    /// Stop the batch and clean up
+    Abort,
+    /// Start the batch over and pause at the end of this tick
+    Goto(u32),
+}
+
+impl BreakpointAction {
+    fn describe(self) -> String {
+        match self {
+            Self::Step => "Stepping".to_string(),
+            Self::Continue => "Continuing".to_string(),
+            Self::Abort => "Aborting".to_string(),
+            Self::Goto(tick) => format!("Replaying to tick {tick}"),
+        }
+    }
+}
+
+/// Wait for a step, continue or abort command. With an inspector, inspection
+/// commands are answered while waiting. Chat is only obeyed from `allowed_players`
+/// (everyone when empty); the terminal and control socket are always trusted.
+pub fn wait_for_step(
+    bot: &mut TestBot,
+    control: Option<&ControlChannel>,
+    allowed_players: &[String],
+    mut inspector: Option<&mut Inspector<'_>>,
+    reason: &str,
+) -> Result<BreakpointAction> {
     println!(
         "\n{} {} {}",
         "⏸".yellow().bold(),
@@ -32,24 +67,78 @@ pub fn wait_for_step(bot: &mut TestBot, reason: &str) -> Result<bool> {
     );
 
     println!(
-        "  Waiting for in-game chat command: {} = step, {} = continue",
+        "  Waiting for in-game chat command: {} = step, {} = continue, {} = abort",
         "s".cyan().bold(),
-        "c".cyan().bold()
+        "c".cyan().bold(),
+        "a".cyan().bold()
     );
+    println!(
+        "  {} <tick> replays the batch from the start up to that tick",
+        "goto".cyan().bold()
+    );
+    if control.is_some() {
+        println!(
+            "  Or type {} / {} / {} here or on the control socket",
+            "step".cyan().bold(),
+            "continue".cyan().bold(),
+            "abort".cyan().bold()
+        );
+    }
+    if inspector.is_some() {
+        println!("  {INSPECT_HELP}");
+    }
 
     // Send chat message to inform player
-    bot.send_command("say Waiting for step/continue (s = step, c = continue)")?;
+    bot.send_command("say Waiting for step/continue (s = step, c = continue, a = abort)")?;
 
     // First, drain any old messages from the chat queue
     drain_chat_messages(bot);
 
-    // Now wait for a fresh chat command
+    // Now wait for a fresh chat or control command
     loop {
-        if let Some((_, message)) =
+        if let Some(command) = control.and_then(ControlChannel::try_recv) {
+            match parse_step_command(command.line.trim()) {
+                Some(action) => {
+                    let _ = command.reply.send(action.describe());
+                    return Ok(action);
+                }
+                None => {
+                    let replies = inspector
+                        .as_deref_mut()
+                        .and_then(|inspector| {
+                            inspector.handle(&super::control::as_chat_command(&command.line))
+                        })
+                        .unwrap_or_else(|| {
+                            vec![
+                                "Paused at a breakpoint: send step, continue, abort or goto <tick>"
+                                    .to_string(),
+                            ]
+                        });
+                    for reply in replies {
+                        let _ = command.reply.send(reply);
+                    }
+                }
+            }
+        }
+
+        if let Some((sender, message)) =
             bot.recv_chat_timeout(std::time::Duration::from_millis(CHAT_POLL_TIMEOUT_MS))
         {
-            // Skip messages from the bot itself (contains "Waiting for step/continue")
-            if message.contains("Waiting for step/continue") {
+            // Inspector replies come back as chat; a slice row like "a" must not abort
+            if is_own_message(sender.as_deref(), &message) {
+                continue;
+            }
+            if !super::is_allowed_sender(allowed_players, sender.as_deref()) {
+                continue;
+            }
+
+            if let Some(lines) = inspector
+                .as_deref_mut()
+                .and_then(|inspector| inspector.handle(&message))
+            {
+                for line in lines {
+                    bot.send_command(&format!("say {line}"))?;
+                }
                 continue;
             }
 
@@ -57,61 +146,70 @@ pub fn wait_for_step(bot: &mut TestBot, reason: &str) -> Result<bool> {
             let msg_lower = message.to_lowercase();
             let trimmed = msg_lower.trim();
 
-            // Match the message ending with just "s" or "c" (player commands)
-            if trimmed.ends_with(" s")
-                || trimmed == "s"
-                || trimmed.ends_with(" step")
-                || trimmed == "step"
-            {
+            // Match the message ending with just "s", "c" or "a" (player commands)
+            let is_command = |short: &str, long: &str| {
+                trimmed == short
+                    || trimmed == long
+                    || trimmed.ends_with(&format!(" {short}"))
+                    || trimmed.ends_with(&format!(" {long}"))
+            };
+            if is_command("s", "step") {
                 println!("  {} Received 's' from chat", "→".blue());
-                return Ok(false); // Step mode
-            } else if trimmed.ends_with(" c")
-                || trimmed == "c"
-                || trimmed.ends_with(" continue")
-                || trimmed == "continue"
-            {
+                return Ok(BreakpointAction::Step);
+            } else if is_command("c", "continue") {
                 println!("  {} Received 'c' from chat", "→".blue());
-                return Ok(true); // Continue mode
+                return Ok(BreakpointAction::Continue);
+            } else if is_abort(trimmed) {
+                println!("  {} Received 'a' from chat", "→".blue());
+                return Ok(BreakpointAction::Abort);
+            } else if let Some(tick) = parse_goto(trimmed) {
+                println!("  {} Received 'goto {}' from chat", "→".blue(), tick);
+                return Ok(BreakpointAction::Goto(tick));
             }
         }
     }
 }
 
-/// Query the current game time from the server
-/// Returns the game time in ticks
-pub fn query_gametime(bot: &mut TestBot) -> Result<u32> {
-    // Clear any pending chat messages
-    drain_chat_messages(bot);
-
-    // Send the time query command
-    bot.send_command("time query gametime")?;
+/// Whether a chat message is the bot's own `say` echoed back: signed chat from the bot,
+/// or the system message a `say` over RCON turns into
+fn is_own_message(sender: Option<&str>, message: &str) -> bool {
+    sender.is_some_and(|sender| sender.eq_ignore_ascii_case(BOT_NAME))
+        || message.starts_with(&format!("[{BOT_NAME}] "))
+        || message.starts_with("[Rcon] ")
+}
 
-    // Wait for response: "The time is <number>"
-    let timeout = std::time::Duration::from_secs(GAMETIME_QUERY_TIMEOUT_SECS);
-    let start = std::time::Instant::now();
+/// Whether a chat line is an abort command and nothing else. Unlike step and continue,
+/// a line that merely ends in " a" ("this is a") must not stop the batch.
+fn is_abort(message: &str) -> bool {
+    let content = message
+        .strip_prefix('<')
+        .and_then(|rest| rest.split_once("> "))
+        .map_or(message, |(_, content)| content);
+    parse_step_command(content) == Some(BreakpointAction::Abort)
+}
 
-    while start.elapsed() < timeout {
-        if let Some((_, message)) =
-            bot.recv_chat_timeout(std::time::Duration::from_millis(CHAT_POLL_TIMEOUT_MS))
-        {
-            // Look for "The time is" message
-            if message.contains("The game time is") {
-                // Extract the time value
-                if let Some(time_str) = message.split("The game time is ").nth(1) {
-                    // Parse the number (might have formatting)
-                    let time_clean = time_str
-                        .chars()
-                        .filter(|c| c.is_ascii_digit())
-                        .collect::<String>();
-                    if let Ok(time) = time_clean.parse::<u32>() {
-                        return Ok(time);
-                    }
-                }
-            }
-        }
+/// Parse a step/continue/abort command from the control channel
+fn parse_step_command(line: &str) -> Option<BreakpointAction> {
+    match line.trim_start_matches('!').to_lowercase().as_str() {
+        "s" | "step" => Some(BreakpointAction::Step),
+        "c" | "continue" => Some(BreakpointAction::Continue),
+        "a" | "abort" => Some(BreakpointAction::Abort),
+        line => parse_goto(line).map(BreakpointAction::Goto),
     }
+}
 
-    anyhow::bail!("Failed to query game time: timeout waiting for response")
+/// Parse `goto <tick>` at the end of a message, with or without the `!` prefix
+pub fn parse_goto(message: &str) -> Option<u32> {
+    let mut words = message.split_whitespace().rev();
+    let tick = words.next()?.parse().ok()?;
+    (words.next()?.trim_start_matches('!') == "goto").then_some(tick)
+}
+
+/// Query the current game time from the server
+/// Returns the game time in ticks
+pub fn query_gametime(bot: &mut TestBot) -> Result<u32> {
+    let time = bot.query_score("run time query gametime")?;
+    u32::try_from(time).map_err(|_| anyhow::anyhow!("Failed to query game time: got {time}"))
 }
 
 /// Step a single tick using /tick step and verify completion
@@ -124,8 +222,6 @@ pub fn step_tick(bot: &mut TestBot, verbose: bool) -> Result<u64> {
     // scheduled tick itself completed by observing game time advance.
     bot.send_command_synced("tick step")?;
 
-    // Each game-time query blocks until its server response arrives, so no polling
-    // delay is needed between verification attempts.
     let timeout = std::time::Duration::from_secs(TICK_STEP_TIMEOUT_SECS);
     let poll_start = std::time::Instant::now();
 
@@ -150,65 +246,99 @@ pub fn step_tick(bot: &mut TestBot, verbose: bool) -> Result<u64> {
         if poll_start.elapsed() >= timeout {
             anyhow::bail!("Tick step verification timeout: game time did not advance");
         }
+        std::thread::sleep(std::time::Duration::from_millis(GAMETIME_POLL_MS));
     }
 }
 
-/// Sprint ticks and capture the time taken from server output
-/// Returns the ms per tick from the server's sprint completion message
+/// Sprint ticks and verify completion by observing game time advance
+/// Returns the time taken in ms
 /// NOTE: Accounts for Minecraft's off-by-one bug where "tick sprint N" executes N+1 ticks
 pub fn sprint_ticks(bot: &mut TestBot, ticks: u32, verbose: bool) -> Result<u64> {
-    // Clear any pending chat messages
-    drain_chat_messages(bot);
-
-    // Account for Minecraft's off-by-one bug: "tick sprint N" executes N+1 ticks
-    // So to execute `ticks` ticks, we request ticks-1
-    let ticks_to_request = ticks - 1;
-
-    // Send the sprint command
-    bot.send_command(&format!("tick sprint {}", ticks_to_request))?;
-
-    // Wait for the "Sprint completed" message
-    // Server message format: "Sprint completed with X ticks per second, or Y ms per tick"
-    let timeout = std::time::Duration::from_secs(SPRINT_TIMEOUT_SECS);
+    let before = query_gametime(bot)?;
     let start = std::time::Instant::now();
+    bot.send_command(&format!("tick sprint {}", ticks - 1))?;
 
-    while start.elapsed() < timeout {
-        if let Some((_, message)) =
-            bot.recv_chat_timeout(std::time::Duration::from_millis(CHAT_POLL_TIMEOUT_MS))
-        {
-            // Look for "Sprint completed" message
-            if message.contains("Sprint completed") {
-                bot.sync_client_world()?;
-                // Try to extract ms per tick
-                // Format: "... or X ms per tick"
-                if let Some(ms_part) = message.split("or ").nth(1)
-                    && let Some(ms_str) = ms_part.split(" ms per tick").next()
-                    && let Ok(ms) = ms_str.trim().parse::<f64>()
-                {
-                    let ms_rounded = ms.ceil() as u64;
-                    if verbose {
-                        println!(
-                            "    {} Sprint {} ticks completed in {} ms per tick",
-                            "⚡".dimmed(),
-                            ticks,
-                            ms_rounded
-                        );
-                    }
-                    // Return total time: ms per tick * number of ticks
-                    return Ok(ms_rounded * ticks as u64);
-                }
-                // If we found the message but couldn't parse, use default
-                if verbose {
-                    println!(
-                        "    {} Sprint {} ticks completed (timing not parsed)",
-                        "⚡".dimmed(),
-                        ticks
-                    );
-                }
-                return Ok(MIN_RETRY_DELAY_MS);
+    let timeout = std::time::Duration::from_secs(SPRINT_TIMEOUT_SECS);
+    loop {
+        let after = query_gametime(bot)?;
+        if after >= before + ticks {
+            bot.sync_client_world()?;
+            let elapsed = start.elapsed().as_millis() as u64;
+            if verbose {
+                println!(
+                    "    {} Sprint {} ticks completed (verified: {} -> {}) in {} ms",
+                    "⚡".dimmed(),
+                    ticks,
+                    before,
+                    after,
+                    elapsed
+                );
             }
+            return Ok(elapsed);
         }
+        if start.elapsed() >= timeout {
+            anyhow::bail!("Sprint verification timeout: game time did not reach {ticks} ticks");
+        }
+        std::thread::sleep(std::time::Duration::from_millis(GAMETIME_POLL_MS));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_step_command() {
+        assert_eq!(parse_step_command("s"), Some(BreakpointAction::Step));
+        assert_eq!(
+            parse_step_command("!continue"),
+            Some(BreakpointAction::Continue)
+        );
+        assert_eq!(parse_step_command("abort"), Some(BreakpointAction::Abort));
+        assert_eq!(
+            parse_step_command("goto 12"),
+            Some(BreakpointAction::Goto(12))
+        );
+        assert_eq!(parse_step_command("inspect 0 0 0"), None);
+    }
+
+    #[test]
+    fn test_abort_needs_the_whole_message() {
+        assert!(is_abort("a"));
+        assert!(is_abort("<steve> abort"));
+        assert!(is_abort("<steve> !a"));
+        assert!(!is_abort("<steve> this is a"));
+        assert!(!is_abort("<steve> please abort"));
     }
 
-    anyhow::bail!("Sprint verification timeout: no completion message for {ticks} ticks")
+    #[test]
+    fn test_own_messages_are_not_commands() {
+        // A one-wide inspector slice row, echoed back by the server
+        assert!(is_own_message(Some("flintmc_testbot"), "a"));
+        assert!(is_own_message(None, "[flintmc_testbot] a"));
+        assert!(is_own_message(None, "[Rcon] c"));
+        assert!(!is_own_message(Some("Steve"), "a"));
+        assert!(!is_own_message(None, "<Steve> s"));
+    }
+
+    #[test]
+    fn test_parse_goto_from_chat() {
+        assert_eq!(parse_goto("<steve> !goto 5"), Some(5));
+        assert_eq!(parse_goto("goto 0"), Some(0));
+        assert_eq!(parse_goto("<steve> goto"), None);
+        assert_eq!(parse_goto("<steve> goto -1"), None);
+        assert_eq!(parse_goto("<steve> region 5"), None);
+    }
+
+    #[test]
+    fn test_step_and_sprint_verify_game_time() {
+        let mut server = crate::bot::mock_server::MockServer::start().unwrap();
+        let mut bot = server.bot().unwrap();
+        step_tick(&mut bot, false).unwrap();
+        assert_eq!(server.gametime(), 1);
+        // `tick sprint N` runs N + 1 ticks
+        sprint_ticks(&mut bot, 10, false).unwrap();
+        assert_eq!(server.gametime(), 11);
+        assert!(server.commands().contains(&"tick sprint 9".to_string()));
+    }
 }
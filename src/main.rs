@@ -1,11 +1,17 @@
 mod bot;
 mod executor;
+mod rcon;
+mod report;
+mod serve;
+mod server;
 mod spatial_batch;
+mod trace;
 
 use anyhow::{Context, Result};
-use clap::{CommandFactory, Parser, ValueEnum};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use clap_complete::Shell;
 use colored::Colorize;
+use executor::Backend;
 use flint_core::format;
 use flint_core::format::{format_number, print_concise_summary, print_test_summary};
 use flint_core::loader::TestLoader;
@@ -35,6 +41,10 @@ enum OutputFormat {
 // Constants
 const CHUNK_SIZE: usize = 100;
 const SEPARATOR_WIDTH: usize = 60;
+/// Vanilla view and simulation distance, used when no client can read the real ones
+const RCON_CHUNK_DISTANCE: u32 = 10;
+/// Exit status when every test passed but some assertions hit a desync
+const DESYNC_EXIT_CODE: i32 = 3;
 
 /// Print a separator line
 fn print_separator() {
@@ -69,6 +79,12 @@ struct Args {
     #[arg(short, long)]
     server: Option<String>,
 
+    /// Start this server jar, or the server in this directory, for the run and stop
+    /// it afterwards. Its output is included in the report. With --backend rcon, RCON
+    /// is enabled on it.
+    #[arg(long, value_name = "JAR|DIR", conflicts_with_all = ["server", "rcon"])]
+    launch_server: Option<PathBuf>,
+
     /// Recursively search directories for test files
     #[arg(short, long)]
     recursive: bool,
@@ -77,6 +93,10 @@ struct Args {
     #[arg(long)]
     break_after_setup: bool,
 
+    /// Pause at each failed assertion, before the test area is cleaned up
+    #[arg(long)]
+    break_on_failure: bool,
+
     /// Filter tests by tags (can be specified multiple times)
     #[arg(short = 't', long = "tag")]
     tags: Vec<String>,
@@ -89,6 +109,16 @@ struct Args {
     #[arg(long, value_name = "NAME")]
     record: Option<String>,
 
+    /// Also accept interactive commands and step/continue as lines on this local TCP
+    /// address (a bare port binds to 127.0.0.1)
+    #[arg(long, value_name = "ADDR")]
+    control: Option<String>,
+
+    /// Only accept interactive commands from this player (can be specified multiple times).
+    /// Without it, any player on the server can control the session
+    #[arg(long = "allow-player", value_name = "NAME")]
+    allowed_players: Vec<String>,
+
     /// Verbose output: show all per-action details during test execution
     #[arg(short, long)]
     verbose: bool,
@@ -122,6 +152,187 @@ struct Args {
     /// Coordinates are emitted in test-local space.
     #[arg(long, value_name = "PATH")]
     emit_events: Option<PathBuf>,
+
+    /// Rewrite snapshot sidecars (<test>.snapshot) from the server instead of
+    /// comparing against them
+    #[arg(long)]
+    update_snapshots: bool,
+
+    /// Run every batch in lockstep on this server too and report any block that
+    /// differs from it whenever a test asserts
+    #[arg(long, value_name = "ADDR")]
+    reference_server: Option<String>,
+
+    /// Send commands and queries over RCON at this address (default port 25575)
+    /// instead of chat
+    #[arg(long, value_name = "ADDR", requires = "rcon_password")]
+    rcon: Option<String>,
+
+    /// RCON password (rcon.password in server.properties); read from
+    /// FLINTMC_RCON_PASSWORD when not given, to keep it out of the process list
+    #[arg(
+        long,
+        value_name = "PASSWORD",
+        env = "FLINTMC_RCON_PASSWORD",
+        hide_env_values = true,
+        requires = "rcon"
+    )]
+    rcon_password: Option<String>,
+
+    /// How tests are run against the server
+    #[arg(long, value_enum, default_value_t = Backend::Azalea)]
+    backend: Backend,
+
+    /// Confirm every block an assertion reads on the server with `execute if block`;
+    /// disagreements with the client's world are reported as desyncs
+    #[arg(long)]
+    verify_server_side: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Turn an --emit-events trace into golden block assertions in a test file
+    TraceToTest {
+        /// Trace written by --emit-events
+        #[arg(value_name = "EVENTS")]
+        events: PathBuf,
+
+        /// Test file to create, or to add the assertions to if it exists
+        #[arg(value_name = "TEST")]
+        test: PathBuf,
+
+        /// Ticks to assert at (comma separated). Defaults to every tick with changes
+        #[arg(long, value_delimiter = ',')]
+        ticks: Vec<u32>,
+
+        /// Only keep positions that hold this block at some point (can be specified multiple times)
+        #[arg(long = "filter", value_name = "BLOCK")]
+        filters: Vec<String>,
+    },
+
+    /// Compare two --emit-events traces and report where their block states diverge
+    TraceDiff {
+        /// First trace
+        #[arg(value_name = "A")]
+        left: PathBuf,
+
+        /// Second trace
+        #[arg(value_name = "B")]
+        right: PathBuf,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Serve JSON-RPC on a local port so editors can list, run and debug tests
+    Serve {
+        /// Port to listen on (localhost only)
+        #[arg(long, default_value_t = 7879)]
+        port: u16,
+    },
+}
+
+fn run_command(args: &Args, command: &Command) -> Result<()> {
+    match command {
+        Command::TraceToTest {
+            events,
+            test,
+            ticks,
+            filters,
+        } => {
+            let options = trace::TraceToTestOptions {
+                ticks: ticks.clone(),
+                filters: filters.clone(),
+            };
+            let checks = trace::trace_to_test(events, test, &options)?;
+            println!(
+                "{} Wrote {} assertions to {}",
+                "✓".green(),
+                format_number(checks),
+                test.display()
+            );
+            Ok(())
+        }
+        Command::TraceDiff { left, right, json } => {
+            let diff = trace::diff_traces(&trace::Trace::read(left)?, &trace::Trace::read(right)?);
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&diff)?);
+            } else {
+                print_trace_diff(&diff, left, right);
+            }
+            if diff.diverged {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Command::Serve { port } => {
+            let server = args
+                .server
+                .as_deref()
+                .context("--server is required for serve")?;
+            if args.emit_events.is_some() {
+                anyhow::bail!("--emit-events cannot be used with serve");
+            }
+            let loader = create_test_loader(args)?;
+            let mut executor = configured_executor(args, 0, true)?;
+            println!("{} Connecting to {}...", "→".blue(), server);
+            executor.connect(server)?;
+            println!("{} Connected successfully", "✓".green());
+            serve::Server::new(executor, loader).serve(*port)
+        }
+    }
+}
+
+fn print_trace_diff(diff: &trace::TraceDiff, left: &Path, right: &Path) {
+    let Some(first) = diff.first_divergence.as_ref() else {
+        if diff.unmatched_ticks.is_empty() {
+            println!(
+                "{} Traces agree on all {} ticks",
+                "✓".green(),
+                diff.ticks_compared
+            );
+        }
+        print_unmatched_ticks(diff);
+        return;
+    };
+
+    let [x, y, z] = first.divergence.pos;
+    println!(
+        "{} Traces diverge after tick {} at [{}, {}, {}]",
+        "✗".red().bold(),
+        first.tick,
+        x,
+        y,
+        z
+    );
+    println!("    {}: {}", left.display(), first.divergence.left.green());
+    println!("    {}: {}", right.display(), first.divergence.right.red());
+    println!();
+    println!(
+        "{} of {} compared ticks diverge:",
+        diff.divergent_ticks.len(),
+        diff.ticks_compared
+    );
+    for tick in &diff.divergent_ticks {
+        println!("  tick {}: {} position(s)", tick.tick, tick.positions.len());
+    }
+    print_unmatched_ticks(diff);
+}
+
+fn print_unmatched_ticks(diff: &trace::TraceDiff) {
+    if diff.unmatched_ticks.is_empty() {
+        return;
+    }
+    let ticks: Vec<String> = diff.unmatched_ticks.iter().map(u32::to_string).collect();
+    println!(
+        "{} Ticks present in only one trace: {}",
+        "!".yellow().bold(),
+        ticks.join(", ")
+    );
 }
 
 fn initialize_logging() {
@@ -267,6 +478,36 @@ fn print_dry_run(test_files: &[PathBuf]) {
     }
 }
 
+/// Report where a launched server's output was saved, with the output itself when a
+/// test failed. TAP owns stdout, so it goes to stderr there; JSON and JUnit carry the
+/// output in the report instead.
+fn print_server_log(log: &server::ServerLog, failed: bool, format: OutputFormat) {
+    let header = format!(
+        "{} Server output saved to {}",
+        "→".blue(),
+        log.path.display()
+    );
+    let omitted = (failed && log.omitted > 0).then(|| {
+        format!(
+            "  {}",
+            format!("({} earlier lines only in the file)", log.omitted).dimmed()
+        )
+    });
+    let lines = std::iter::once(header).chain(omitted).chain(
+        log.lines
+            .iter()
+            .filter(|_| failed)
+            .map(|line| format!("  {}", line.dimmed())),
+    );
+    for line in lines {
+        if matches!(format, OutputFormat::Pretty) {
+            println!("{line}");
+        } else {
+            eprintln!("{line}");
+        }
+    }
+}
+
 fn configured_executor(
     args: &Args,
     test_count: usize,
@@ -277,6 +518,40 @@ fn configured_executor(
     executor.set_quiet(args.quiet || !matches!(args.format, OutputFormat::Pretty));
     executor.set_fail_fast(args.fail_fast);
     executor.set_enable_breakpoints(interactive);
+    executor.set_break_on_failure(args.break_on_failure);
+    executor.set_update_snapshots(args.update_snapshots);
+    executor.set_allowed_players(args.allowed_players.clone());
+    if args.backend != Backend::Azalea {
+        let backend = match args.backend {
+            Backend::Adapter => "adapter",
+            _ => "rcon",
+        };
+        let unsupported = [
+            (interactive, "--interactive"),
+            (args.break_after_setup, "--break-after-setup"),
+            (args.break_on_failure, "--break-on-failure"),
+            (args.reference_server.is_some(), "--reference-server"),
+            (args.emit_events.is_some(), "--emit-events"),
+            (args.verify_server_side, "--verify-server-side"),
+            (args.update_snapshots, "--update-snapshots"),
+        ];
+        if let Some((_, flag)) = unsupported.iter().find(|(set, _)| *set) {
+            anyhow::bail!("{flag} is not supported with --backend {backend}");
+        }
+    }
+    if args.backend == Backend::Rcon && args.rcon.is_none() && args.launch_server.is_none() {
+        anyhow::bail!("--backend rcon requires --rcon and --rcon-password, or --launch-server");
+    }
+    executor.set_backend(args.backend);
+    executor.set_verify_server_side(args.verify_server_side);
+    if let (Some(addr), Some(password)) = (&args.rcon, &args.rcon_password) {
+        executor.set_rcon(addr.clone(), password.clone());
+    }
+    // The terminal REPL only makes sense when something may wait for commands.
+    executor.enable_control(
+        interactive || args.break_after_setup || args.break_on_failure,
+        args.control.as_deref(),
+    )?;
     if let Some(path) = args.emit_events.clone() {
         if test_count != 1 {
             anyhow::bail!("--emit-events requires exactly one test file (got {test_count})");
@@ -297,7 +572,8 @@ fn run_interactive_mode(
         "→".yellow().bold()
     );
     println!("  Commands: !search, !run, !run-all, !run-tags, !list, !reload, !help, !stop");
-    println!("  During tests: type 's' to step, 'c' to continue\n");
+    println!("  During tests: type 's' to step, 'c' to continue");
+    println!("  The same commands can be typed here, without the '!' prefix\n");
     println!("{} Connecting to {}...", "→".blue(), server);
     executor.connect(server)?;
     println!("{} Connected successfully\n", "✓".green());
@@ -316,6 +592,10 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
+    if let Some(command) = &args.command {
+        return run_command(&args, command);
+    }
+
     let verbose = args.verbose;
 
     if verbose {
@@ -342,11 +622,33 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
-    let server = args
-        .server
-        .as_deref()
-        .context("--server is required when running tests")?;
     let mut executor = configured_executor(&args, test_files.len(), interactive_mode)?;
+    let mut launched = match args.launch_server.as_deref() {
+        Some(path) => {
+            if verbose {
+                println!("{} Launching server {}...", "→".blue(), path.display());
+            } else {
+                eprintln!("Launching server {}...", path.display());
+            }
+            let launched = server::LocalServer::launch(path, args.backend == Backend::Rcon)?;
+            if verbose {
+                println!("{} Server ready at {}", "✓".green(), launched.address());
+            }
+            if let Some((addr, password)) = launched.rcon() {
+                executor.set_rcon(addr, password);
+            }
+            Some(launched)
+        }
+        None => None,
+    };
+    let server = match &launched {
+        Some(launched) => launched.address(),
+        None => args
+            .server
+            .clone()
+            .context("--server or --launch-server is required when running tests")?,
+    };
+    let server = server.as_str();
 
     // Interactive mode: enter command loop
     if interactive_mode {
@@ -357,12 +659,28 @@ fn main() -> Result<()> {
         println!("{} Connecting to {}...", "→".blue(), server);
     }
     executor.connect(server)?;
-    let effective_chunk_distance = executor.bot.effective_chunk_distance()?;
+    if let Some(reference) = args.reference_server.as_deref() {
+        if verbose {
+            println!(
+                "{} Connecting to reference server {}...",
+                "→".blue(),
+                reference
+            );
+        }
+        executor.connect_reference(reference)?;
+    }
+    // Without a client the server never advertises its distances; assume the defaults
+    let effective_chunk_distance = if executor.bot.is_rcon_only() {
+        RCON_CHUNK_DISTANCE
+    } else {
+        executor.bot.effective_chunk_distance()?
+    };
     let (view_distance, simulation_distance) = executor.bot.detected_distances();
     if verbose {
         println!(
-            "{} Connected successfully (view: {}, simulation: {}, effective: {})\n",
+            "{} Connected to Minecraft {} (view: {}, simulation: {}, effective: {})\n",
             "✓".green(),
+            executor.server_info().minecraft_version,
             view_distance,
             simulation_distance,
             effective_chunk_distance
@@ -393,6 +711,10 @@ fn main() -> Result<()> {
     let start_time = Instant::now();
     let mut all_results = Vec::new();
     let mut all_failures: Vec<(String, AssertFailure)> = Vec::new();
+    let mut all_mismatches = Vec::new();
+    let mut total_divergences = 0;
+    let mut reconnects: Vec<String> = Vec::new();
+    let mut desyncs = Vec::new();
     let mut test_specs_map = std::collections::HashMap::new();
 
     for (chunk_idx, chunk) in chunks.iter().enumerate() {
@@ -406,7 +728,7 @@ fn main() -> Result<()> {
             match TestSpec::from_file(test_file, false) {
                 Ok(test) => {
                     test_specs_map.insert(test.name.clone(), (test.clone(), test_file.clone()));
-                    chunk_specs.push(test);
+                    chunk_specs.push((test, test_file.clone()));
                 }
                 Err(e) => {
                     eprintln!(
@@ -415,6 +737,8 @@ fn main() -> Result<()> {
                         test_file.display(),
                         e
                     );
+                    // Exiting skips destructors, so stop the server first
+                    drop(launched.take());
                     std::process::exit(1);
                 }
             }
@@ -446,11 +770,16 @@ fn main() -> Result<()> {
                 );
             }
 
-            executor.bot.reset_to_test_origin()?;
-            let offsets = calculate_test_offsets_for_batch_default(sim_batch);
-            let bot_position = executor.bot.get_position()?;
+            let specs: Vec<TestSpec> = sim_batch.iter().map(|(test, _)| test.clone()).collect();
+            let offsets = calculate_test_offsets_for_batch_default(&specs);
+            let bot_position = if executor.bot.is_rcon_only() {
+                [0, 0, 0]
+            } else {
+                executor.bot.reset_to_test_origin()?;
+                executor.bot.get_position()?
+            };
             tests_with_offsets.clear();
-            for (test_index, (test, offset)) in sim_batch.iter().cloned().zip(offsets).enumerate() {
+            for (test_index, (test, offset)) in specs.into_iter().zip(offsets).enumerate() {
                 let offset = [
                     offset[0] + bot_position[0],
                     offset[1],
@@ -473,11 +802,21 @@ fn main() -> Result<()> {
                 println!();
             }
 
+            executor.set_batch_files(
+                sim_batch
+                    .iter()
+                    .map(|(_, file)| Some(file.clone()))
+                    .collect(),
+            );
             let output =
                 executor.run_tests_parallel(&tests_with_offsets, args.break_after_setup)?;
 
             all_results.extend(output.results);
             all_failures.extend(output.failures);
+            all_mismatches.extend(output.mismatches);
+            total_divergences += output.divergences;
+            reconnects.extend(output.reconnects);
+            desyncs.extend(output.desyncs);
 
             if args.fail_fast && !all_failures.is_empty() {
                 break;
@@ -500,6 +839,17 @@ fn main() -> Result<()> {
     }
 
     let elapsed = start_time.elapsed();
+    let server_log = launched
+        .take()
+        .map(|launched| launched.stop())
+        .transpose()?;
+    let any_failed = all_results.iter().any(|r| !r.success);
+    let report = report::Report {
+        results: &all_results,
+        mismatches: &all_mismatches,
+        elapsed,
+        server_log: server_log.as_ref(),
+    };
 
     match args.format {
         OutputFormat::Pretty => {
@@ -509,12 +859,74 @@ fn main() -> Result<()> {
                 print_concise_summary(&all_results, elapsed);
             }
         }
-        OutputFormat::Json => format::print_json(&all_results, elapsed),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report.json())?),
         OutputFormat::Tap => format::print_tap(&all_results),
-        OutputFormat::Junit => format::print_junit(&all_results, elapsed),
+        OutputFormat::Junit => print!("{}", report.junit()),
+    }
+
+    if args.reference_server.is_some() && matches!(args.format, OutputFormat::Pretty) {
+        if total_divergences == 0 {
+            println!("{} No divergences from the reference server", "✓".green());
+        } else {
+            println!(
+                "{} {} block(s) diverged from the reference server",
+                "≠".red().bold(),
+                format_number(total_divergences)
+            );
+        }
+    }
+
+    if !reconnects.is_empty() {
+        // Structured formats own stdout, so the note goes to stderr for them
+        let note = format!(
+            "{} Reconnected {} time(s); the interrupted batches were re-run from cleanup:",
+            "⟳".yellow().bold(),
+            reconnects.len()
+        );
+        let lines = std::iter::once(note).chain(
+            reconnects
+                .iter()
+                .map(|reason| format!("  {}", reason.dimmed())),
+        );
+        for line in lines {
+            if matches!(args.format, OutputFormat::Pretty) {
+                println!("{line}");
+            } else {
+                eprintln!("{line}");
+            }
+        }
+    }
+
+    if let Some(log) = &server_log
+        && matches!(args.format, OutputFormat::Pretty | OutputFormat::Tap)
+    {
+        print_server_log(log, any_failed, args.format);
+    }
+
+    if !desyncs.is_empty() {
+        let note = format!(
+            "{} {} assertion(s) were skipped because the client and server disagreed:",
+            "!".yellow().bold(),
+            desyncs.len()
+        );
+        let lines = std::iter::once(note).chain(desyncs.iter().map(|desync| {
+            format!(
+                "  [{}] tick {}: {}",
+                desync.test_name,
+                desync.tick,
+                desync.message.dimmed()
+            )
+        }));
+        for line in lines {
+            if matches!(args.format, OutputFormat::Pretty) {
+                println!("{line}");
+            } else {
+                eprintln!("{line}");
+            }
+        }
     }
 
-    if all_results.iter().any(|r| !r.success) {
+    if any_failed {
         if matches!(args.format, OutputFormat::Pretty) && !all_failures.is_empty() {
             println!("{}", "═".repeat(SEPARATOR_WIDTH).dimmed());
             println!("{}", "Flint Visualizer Links:".cyan().bold());
@@ -539,6 +951,10 @@ fn main() -> Result<()> {
         }
         std::process::exit(1);
     }
+    if !desyncs.is_empty() {
+        // Passing tests can't be trusted when some of their checks went unread
+        std::process::exit(DESYNC_EXIT_CODE);
+    }
 
     Ok(())
 }
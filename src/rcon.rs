@@ -0,0 +1,198 @@
+//! Minimal RCON client (Source RCON protocol, as implemented by Minecraft servers).
+//!
+//! Commands run on the server thread and their output comes back in the response, so
+//! nothing has to be scraped from chat. Responses longer than one packet are split by
+//! the server; each command is followed by a sentinel request whose answer marks the
+//! end of the output.
+
+use anyhow::{Context, Result};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+const DEFAULT_PORT: u16 = 25575;
+
+pub(crate) const TYPE_RESPONSE: i32 = 0;
+pub(crate) const TYPE_EXEC: i32 = 2;
+pub(crate) const TYPE_AUTH: i32 = 3;
+
+/// Responses and requests are capped at 4096 bytes of body by the server
+const MAX_PACKET_SIZE: i32 = 4096 + 10;
+
+const RESPONSE_TIMEOUT_SECS: u64 = 10;
+
+pub struct RconClient {
+    stream: TcpStream,
+    next_id: i32,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Packet {
+    pub(crate) id: i32,
+    pub(crate) kind: i32,
+    pub(crate) body: String,
+}
+
+impl RconClient {
+    /// Connect and authenticate. A bare host uses the default RCON port.
+    pub fn connect(addr: &str, password: &str) -> Result<Self> {
+        let addr = if addr.contains(':') {
+            addr.to_string()
+        } else {
+            format!("{addr}:{DEFAULT_PORT}")
+        };
+        let stream =
+            TcpStream::connect(&addr).with_context(|| format!("connecting to RCON at {addr}"))?;
+        stream.set_read_timeout(Some(Duration::from_secs(RESPONSE_TIMEOUT_SECS)))?;
+        stream.set_nodelay(true)?;
+
+        let mut client = Self { stream, next_id: 1 };
+        let id = client.send(TYPE_AUTH, password)?;
+        // Some servers send an empty response before the auth result
+        loop {
+            let packet = client.receive()?;
+            if packet.id == -1 {
+                anyhow::bail!("RCON authentication failed for {addr}: wrong password");
+            }
+            if packet.id == id && packet.kind == TYPE_EXEC {
+                return Ok(client);
+            }
+        }
+    }
+
+    /// Run a command and return its output
+    pub fn exec(&mut self, command: &str) -> Result<String> {
+        let command = command.strip_prefix('/').unwrap_or(command);
+        let id = self.send(TYPE_EXEC, command)?;
+        let sentinel = self.send(TYPE_RESPONSE, "")?;
+
+        let mut output = String::new();
+        loop {
+            let packet = self.receive()?;
+            if packet.id == sentinel {
+                return Ok(output);
+            }
+            if packet.id == id {
+                output.push_str(&packet.body);
+            }
+        }
+    }
+
+    fn send(&mut self, kind: i32, body: &str) -> Result<i32> {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+        let packet = encode(&Packet {
+            id,
+            kind,
+            body: body.to_string(),
+        });
+        self.stream
+            .write_all(&packet)
+            .context("writing to RCON connection")?;
+        Ok(id)
+    }
+
+    fn receive(&mut self) -> Result<Packet> {
+        decode(&mut self.stream)
+    }
+}
+
+pub(crate) fn encode(packet: &Packet) -> Vec<u8> {
+    let body = packet.body.as_bytes();
+    let length = (body.len() + 10) as i32;
+    let mut bytes = Vec::with_capacity(body.len() + 14);
+    bytes.extend_from_slice(&length.to_le_bytes());
+    bytes.extend_from_slice(&packet.id.to_le_bytes());
+    bytes.extend_from_slice(&packet.kind.to_le_bytes());
+    bytes.extend_from_slice(body);
+    bytes.extend_from_slice(&[0, 0]);
+    bytes
+}
+
+pub(crate) fn decode(reader: &mut impl Read) -> Result<Packet> {
+    let mut word = [0; 4];
+    reader
+        .read_exact(&mut word)
+        .context("reading from RCON connection")?;
+    let length = i32::from_le_bytes(word);
+    if !(10..=MAX_PACKET_SIZE).contains(&length) {
+        anyhow::bail!("invalid RCON packet length {length}");
+    }
+
+    let mut rest = vec![0; length as usize];
+    reader
+        .read_exact(&mut rest)
+        .context("reading from RCON connection")?;
+    let id = i32::from_le_bytes(rest[0..4].try_into()?);
+    let kind = i32::from_le_bytes(rest[4..8].try_into()?);
+    let body = String::from_utf8_lossy(&rest[8..rest.len() - 2]).into_owned();
+    Ok(Packet { id, kind, body })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_packet_round_trip() {
+        let packet = Packet {
+            id: 7,
+            kind: TYPE_EXEC,
+            body: "time query gametime".to_string(),
+        };
+        let bytes = encode(&packet);
+        assert_eq!(&bytes[..4], &(19 + 10i32).to_le_bytes());
+        assert_eq!(decode(&mut bytes.as_slice()).unwrap(), packet);
+    }
+
+    /// A server that answers auth, splits command output over two packets and echoes
+    /// the sentinel like a vanilla server ("Unknown request 0").
+    #[test]
+    fn test_exec_collects_split_output() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let auth = decode(&mut stream).unwrap();
+            assert_eq!((auth.kind, auth.body.as_str()), (TYPE_AUTH, "secret"));
+            for packet in [
+                Packet {
+                    id: auth.id,
+                    kind: TYPE_RESPONSE,
+                    body: String::new(),
+                },
+                Packet {
+                    id: auth.id,
+                    kind: TYPE_EXEC,
+                    body: String::new(),
+                },
+            ] {
+                stream.write_all(&encode(&packet)).unwrap();
+            }
+
+            let exec = decode(&mut stream).unwrap();
+            let sentinel = decode(&mut stream).unwrap();
+            for (id, body) in [
+                (exec.id, "The game time "),
+                (exec.id, "is 1234"),
+                (sentinel.id, "Unknown request 0"),
+            ] {
+                let packet = Packet {
+                    id,
+                    kind: TYPE_RESPONSE,
+                    body: body.to_string(),
+                };
+                stream.write_all(&encode(&packet)).unwrap();
+            }
+            exec.body
+        });
+
+        let mut client = RconClient::connect(&addr, "secret").unwrap();
+        assert_eq!(
+            client.exec("/time query gametime").unwrap(),
+            "The game time is 1234"
+        );
+        assert_eq!(server.join().unwrap(), "time query gametime");
+    }
+}
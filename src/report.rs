@@ -0,0 +1,217 @@
+//! JSON and JUnit reports.
+//!
+//! They're built here rather than printed by `flint_core`, so a launched server's
+//! output can go in them: a `server_log` member in JSON, the suite's `<system-out>` in
+//! JUnit.
+
+use crate::executor::AssertMismatch;
+use crate::server::ServerLog;
+use flint_core::results::TestResult;
+use std::time::Duration;
+
+/// What a report covers
+pub struct Report<'a> {
+    pub results: &'a [TestResult],
+    /// Every failed assertion, in the order it happened
+    pub mismatches: &'a [AssertMismatch],
+    pub elapsed: Duration,
+    pub server_log: Option<&'a ServerLog>,
+}
+
+impl Report<'_> {
+    fn failed(&self) -> usize {
+        self.results.iter().filter(|result| !result.success).count()
+    }
+
+    pub fn json(&self) -> serde_json::Value {
+        let failed = self.failed();
+        let mut report = serde_json::json!({
+            "summary": {
+                "total": self.results.len(),
+                "passed": self.results.len() - failed,
+                "failed": failed,
+                "duration_secs": self.elapsed.as_secs_f64(),
+            },
+            "tests": self
+                .results
+                .iter()
+                .map(|result| serde_json::json!({
+                    "name": result.test_name,
+                    "success": result.success,
+                }))
+                .collect::<Vec<_>>(),
+            "failures": self
+                .mismatches
+                .iter()
+                .map(|mismatch| serde_json::json!({
+                    "test": mismatch.test_name,
+                    "tick": mismatch.tick,
+                    "message": mismatch.message,
+                }))
+                .collect::<Vec<_>>(),
+        });
+        if let Some(log) = self.server_log {
+            report["server_log"] = serde_json::json!({
+                "path": log.path.display().to_string(),
+                "omitted_lines": log.omitted,
+                "lines": log.lines,
+            });
+        }
+        report
+    }
+
+    pub fn junit(&self) -> String {
+        let failed = self.failed();
+        let time = format!("{:.3}", self.elapsed.as_secs_f64());
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuites tests=\"{}\" failures=\"{failed}\" time=\"{time}\">\n",
+            self.results.len()
+        ));
+        xml.push_str(&format!(
+            "  <testsuite name=\"flintmc\" tests=\"{}\" failures=\"{failed}\" time=\"{time}\">\n",
+            self.results.len()
+        ));
+        for result in self.results {
+            let name = escape_xml(&result.test_name);
+            if result.success {
+                xml.push_str(&format!(
+                    "    <testcase classname=\"\" name=\"{name}\" />\n"
+                ));
+                continue;
+            }
+            let failures: Vec<String> = self
+                .mismatches
+                .iter()
+                .filter(|mismatch| mismatch.test_name == result.test_name)
+                .map(|mismatch| format!("tick {}: {}", mismatch.tick, mismatch.message))
+                .collect();
+            let message = failures.first().map_or("test failed", String::as_str);
+            xml.push_str(&format!("    <testcase classname=\"\" name=\"{name}\">\n"));
+            xml.push_str(&format!(
+                "      <failure message=\"{}\">{}</failure>\n",
+                escape_xml(message),
+                escape_xml(&failures.join("\n"))
+            ));
+            xml.push_str("    </testcase>\n");
+        }
+        if let Some(log) = self.server_log {
+            xml.push_str(&format!(
+                "    <system-out>{}</system-out>\n",
+                escape_xml(&log_text(log))
+            ));
+        }
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+}
+
+fn log_text(log: &ServerLog) -> String {
+    let mut text = format!("Server output saved to {}\n", log.path.display());
+    if log.omitted > 0 {
+        text.push_str(&format!(
+            "({} earlier lines only in the file)\n",
+            log.omitted
+        ));
+    }
+    for line in &log.lines {
+        text.push_str(line);
+        text.push('\n');
+    }
+    text
+}
+
+/// Text safe inside an XML element or attribute. Control characters XML can't hold,
+/// like the escapes of colored log lines, are dropped.
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\t' | '\n' | '\r' => escaped.push(ch),
+            ch if ch.is_control() => {}
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn log() -> ServerLog {
+        ServerLog {
+            lines: vec!["[Server] <b> & \u{1b}[0mdone".to_string()],
+            omitted: 0,
+            path: PathBuf::from("server/flintmc-server.log"),
+        }
+    }
+
+    fn report<'a>(
+        results: &'a [TestResult],
+        mismatches: &'a [AssertMismatch],
+        server_log: Option<&'a ServerLog>,
+    ) -> Report<'a> {
+        Report {
+            results,
+            mismatches,
+            elapsed: Duration::from_millis(1500),
+            server_log,
+        }
+    }
+
+    #[test]
+    fn test_junit_report_lists_failures_and_server_output() {
+        let results = [
+            TestResult::new("a".to_string()),
+            TestResult::new("b".to_string()).with_failure_reason("1 failed".to_string()),
+        ];
+        let mismatches = [AssertMismatch {
+            test_name: "b".to_string(),
+            tick: 2,
+            message: "expected \"stone\", got air".to_string(),
+        }];
+        let log = log();
+        assert_eq!(
+            report(&results, &mismatches, Some(&log)).junit(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <testsuites tests=\"2\" failures=\"1\" time=\"1.500\">\n  \
+             <testsuite name=\"flintmc\" tests=\"2\" failures=\"1\" time=\"1.500\">\n    \
+             <testcase classname=\"\" name=\"a\" />\n    \
+             <testcase classname=\"\" name=\"b\">\n      \
+             <failure message=\"tick 2: expected &quot;stone&quot;, got air\">\
+             tick 2: expected &quot;stone&quot;, got air</failure>\n    \
+             </testcase>\n    \
+             <system-out>Server output saved to server/flintmc-server.log\n\
+             [Server] &lt;b&gt; &amp; [0mdone\n</system-out>\n  \
+             </testsuite>\n</testsuites>\n"
+        );
+        assert!(
+            !report(&results, &mismatches, None)
+                .junit()
+                .contains("<system-out>")
+        );
+    }
+
+    #[test]
+    fn test_json_report_carries_the_server_log() {
+        let results = [TestResult::new("a".to_string())];
+        let log = log();
+        let json = report(&results, &[], Some(&log)).json();
+        assert_eq!(json["summary"]["passed"], 1);
+        assert_eq!(json["tests"][0]["name"], "a");
+        assert_eq!(json["server_log"]["path"], "server/flintmc-server.log");
+        assert_eq!(json["server_log"]["omitted_lines"], 0);
+        assert!(
+            report(&results, &[], None)
+                .json()
+                .get("server_log")
+                .is_none()
+        );
+    }
+}
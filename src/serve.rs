@@ -0,0 +1,453 @@
+//! `flintmc serve`: a JSON-RPC 2.0 control server for editor integration.
+//!
+//! One client at a time talks to the server over a persistent TCP connection, one JSON
+//! message per line. Methods:
+//!
+//! - `listTests` `{tags?}` → `[{name, file, tags}]`
+//! - `run` `{tests?, tags?}` → `{passed, failed}`, after a `testResult` notification
+//!   per test as each batch finishes
+//! - `setBreakpoints` `{test, ticks}` → `{test, ticks}`; applied to later runs
+//! - `step` / `continue` / `abort` → `{message}`; resumes or stops a run paused at a
+//!   breakpoint
+//! - `queryBlock` `{pos: [x, y, z]}` → `{pos, block}` in world coordinates
+//!
+//! Runs execute on the main thread. `step`, `continue`, `abort` and `queryBlock` are
+//! answered by the connection thread, so they work while a run is paused.
+
+use anyhow::{Context, Result};
+use flint_core::loader::TestLoader;
+use flint_core::test_spec::TestSpec;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+use crate::bot::TestBot;
+use crate::executor::block::extract_block_id;
+use crate::executor::control::{ControlChannel, ControlLine};
+use crate::executor::{TestExecutor, TestRunOutput};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+/// How often the idle loop checks for stray step/continue/abort commands
+const IDLE_POLL_MS: u64 = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    #[serde(default)]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct RunParams {
+    #[serde(default)]
+    tests: Vec<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListParams {
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BreakpointParams {
+    test: String,
+    ticks: Vec<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct QueryBlockParams {
+    pos: [i32; 3],
+}
+
+/// A JSON-RPC error, sent back to the client instead of aborting the server
+#[derive(Debug)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(error: anyhow::Error) -> Self {
+        Self::new(SERVER_ERROR, format!("{error:#}"))
+    }
+}
+
+/// Parse one line from the client
+pub fn parse_request(line: &str) -> Result<Request, RpcError> {
+    let value: Value = serde_json::from_str(line)
+        .map_err(|error| RpcError::new(PARSE_ERROR, format!("parse error: {error}")))?;
+    serde_json::from_value(value)
+        .map_err(|error| RpcError::new(INVALID_REQUEST, format!("invalid request: {error}")))
+}
+
+fn params<T: for<'de> Deserialize<'de>>(request: &Request) -> Result<T, RpcError> {
+    // Methods without required params also accept a missing `params`
+    let params = if request.params.is_null() {
+        json!({})
+    } else {
+        request.params.clone()
+    };
+    serde_json::from_value(params)
+        .map_err(|error| RpcError::new(INVALID_PARAMS, format!("invalid params: {error}")))
+}
+
+pub fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": error.code, "message": error.message },
+        }),
+    }
+}
+
+pub fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+/// Messages to the client come from the main thread and the connection thread
+#[derive(Clone)]
+struct Outbox(Arc<parking_lot::Mutex<TcpStream>>);
+
+impl Outbox {
+    fn send(&self, message: &Value) {
+        let mut stream = self.0.lock();
+        if writeln!(stream, "{message}").is_err() {
+            tracing::warn!("Failed to write to serve client");
+        }
+    }
+
+    /// Answer a request; notifications (no id) get no response
+    fn respond(&self, id: Option<Value>, result: Result<Value, RpcError>) {
+        if let Some(id) = id {
+            self.send(&response(id, result));
+        }
+    }
+}
+
+pub struct Server {
+    executor: TestExecutor,
+    loader: TestLoader,
+    control: ControlChannel,
+    /// Breakpoint ticks per test name, applied on top of the test file
+    breakpoints: HashMap<String, Vec<u32>>,
+}
+
+impl Server {
+    pub fn new(mut executor: TestExecutor, loader: TestLoader) -> Self {
+        executor.set_enable_breakpoints(true);
+        let control = executor.control_channel();
+        Self {
+            executor,
+            loader,
+            control,
+            breakpoints: HashMap::new(),
+        }
+    }
+
+    /// Accept clients on `port` on localhost, one at a time, until the process ends.
+    pub fn serve(&mut self, port: u16) -> Result<()> {
+        let addr = format!("127.0.0.1:{port}");
+        let listener =
+            TcpListener::bind(&addr).with_context(|| format!("binding serve socket on {addr}"))?;
+        println!("Serving JSON-RPC on {addr}");
+
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(error) => {
+                    tracing::warn!("Failed to accept serve client: {error}");
+                    continue;
+                }
+            };
+            let peer = stream
+                .peer_addr()
+                .map(|addr| addr.to_string())
+                .unwrap_or_default();
+            tracing::info!("Serve client connected: {peer}");
+            if let Err(error) = self.handle_client(stream) {
+                tracing::warn!("Serve client {peer} failed: {error:#}");
+            }
+            tracing::info!("Serve client disconnected: {peer}");
+        }
+        Ok(())
+    }
+
+    fn handle_client(&mut self, stream: TcpStream) -> Result<()> {
+        let outbox = Outbox(Arc::new(parking_lot::Mutex::new(stream.try_clone()?)));
+        let (request_tx, request_rx) = mpsc::channel();
+        spawn_reader(
+            stream,
+            request_tx,
+            outbox.clone(),
+            self.control.clone(),
+            self.executor.bot.clone(),
+        );
+        self.dispatch(&request_rx, &outbox);
+        Ok(())
+    }
+
+    /// Handle requests on the main thread until the client disconnects
+    fn dispatch(&mut self, requests: &Receiver<Request>, outbox: &Outbox) {
+        loop {
+            match requests.recv_timeout(Duration::from_millis(IDLE_POLL_MS)) {
+                Ok(request) => {
+                    let result = self.handle(&request, outbox);
+                    outbox.respond(request.id, result);
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            }
+            // Nothing waits on step/continue/abort between runs
+            while let Some(command) = self.control.try_recv() {
+                let _ = command.reply.send("Not paused at a breakpoint".to_string());
+            }
+        }
+    }
+
+    fn handle(&mut self, request: &Request, outbox: &Outbox) -> Result<Value, RpcError> {
+        match request.method.as_str() {
+            "listTests" => self.list_tests(params(request)?),
+            "run" => self.run(params(request)?, outbox),
+            "setBreakpoints" => {
+                let BreakpointParams { test, ticks } = params(request)?;
+                self.breakpoints.insert(test.clone(), ticks.clone());
+                Ok(json!({ "test": test, "ticks": ticks }))
+            }
+            method => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method: {method}"),
+            )),
+        }
+    }
+
+    fn test_files(&self, tags: &[String]) -> Result<Vec<PathBuf>> {
+        if tags.is_empty() {
+            self.loader.collect_all_test_files()
+        } else {
+            Ok(self.loader.collect_by_tags(tags))
+        }
+    }
+
+    fn list_tests(&mut self, ListParams { tags }: ListParams) -> Result<Value, RpcError> {
+        self.loader.verify_and_rebuild_index()?;
+        let tests: Vec<Value> = self
+            .test_files(&tags)?
+            .iter()
+            .filter_map(|file| {
+                let test = TestSpec::from_file(file, false).ok()?;
+                Some(json!({
+                    "name": test.name,
+                    "file": file.display().to_string(),
+                    "tags": test.tags,
+                }))
+            })
+            .collect();
+        Ok(Value::Array(tests))
+    }
+
+    fn run(&mut self, params: RunParams, outbox: &Outbox) -> Result<Value, RpcError> {
+        self.loader.verify_and_rebuild_index()?;
+        let mut specs = Vec::new();
+        for file in self.test_files(&params.tags)? {
+            let mut test = TestSpec::from_file(&file, false)
+                .with_context(|| format!("loading {}", file.display()))?;
+            if !params.tests.is_empty() && !params.tests.contains(&test.name) {
+                continue;
+            }
+            if let Some(ticks) = self.breakpoints.get(&test.name) {
+                test.breakpoints = ticks.clone();
+            }
+            specs.push((test, file));
+        }
+        if let Some(missing) = params
+            .tests
+            .iter()
+            .find(|name| !specs.iter().any(|(test, _)| &test.name == *name))
+        {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                format!("no test named {missing}"),
+            ));
+        }
+        if specs.is_empty() {
+            return Err(RpcError::new(INVALID_PARAMS, "no tests matched"));
+        }
+
+        let (passed, failed) = self.executor.run_specs(specs, |output| {
+            for result in test_results(output) {
+                outbox.send(&notification("testResult", result));
+            }
+            Ok(())
+        })?;
+        Ok(json!({ "passed": passed, "failed": failed }))
+    }
+}
+
+/// One `testResult` notification body per test in a finished batch
+fn test_results(output: &TestRunOutput) -> Vec<Value> {
+    output
+        .results
+        .iter()
+        .map(|result| {
+            let mismatches: Vec<Value> = output
+                .mismatches
+                .iter()
+                .filter(|mismatch| mismatch.test_name == result.test_name)
+                .map(|mismatch| json!({ "tick": mismatch.tick, "message": mismatch.message }))
+                .collect();
+            json!({
+                "name": result.test_name,
+                "success": result.success,
+                "mismatches": mismatches,
+            })
+        })
+        .collect()
+}
+
+/// Read requests from the client. `step`, `continue` and `queryBlock` are answered
+/// here; everything else is queued for the main thread.
+fn spawn_reader(
+    stream: TcpStream,
+    requests: Sender<Request>,
+    outbox: Outbox,
+    control: ControlChannel,
+    bot: TestBot,
+) {
+    std::thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let request = match parse_request(&line) {
+                Ok(request) => request,
+                Err(error) => {
+                    outbox.send(&response(Value::Null, Err(error)));
+                    continue;
+                }
+            };
+
+            match request.method.as_str() {
+                "step" | "continue" | "abort" => forward_step(request, &control, &outbox),
+                "queryBlock" => {
+                    let result = params::<QueryBlockParams>(&request).and_then(|params| {
+                        let block = bot
+                            .get_block(params.pos)?
+                            .map(|block| extract_block_id(&block));
+                        Ok(json!({ "pos": params.pos, "block": block }))
+                    });
+                    outbox.respond(request.id, result);
+                }
+                _ => {
+                    if requests.send(request).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Hand `step`/`continue`/`abort` to whatever is waiting at a breakpoint and answer with its
+/// reply once it arrives.
+fn forward_step(request: Request, control: &ControlChannel, outbox: &Outbox) {
+    let (reply_tx, reply_rx) = mpsc::channel::<String>();
+    control.push(ControlLine {
+        line: request.method.clone(),
+        reply: reply_tx,
+    });
+    let outbox = outbox.clone();
+    std::thread::spawn(move || {
+        let result = reply_rx
+            .recv()
+            .map(|message| json!({ "message": message }))
+            .map_err(|_| RpcError::new(SERVER_ERROR, "no reply from the test run"));
+        outbox.respond(request.id, result);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request_and_params() {
+        let request = parse_request(
+            r#"{"jsonrpc":"2.0","id":3,"method":"run","params":{"tags":["redstone"]}}"#,
+        )
+        .unwrap();
+        assert_eq!(request.id, Some(json!(3)));
+        assert_eq!(request.method, "run");
+        let run: RunParams = params(&request).unwrap();
+        assert!(run.tests.is_empty());
+        assert_eq!(run.tags, vec!["redstone".to_string()]);
+
+        let request = parse_request(r#"{"jsonrpc":"2.0","id":4,"method":"listTests"}"#).unwrap();
+        let list: ListParams = params(&request).unwrap();
+        assert!(list.tags.is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors_use_json_rpc_codes() {
+        assert_eq!(parse_request("{not json").unwrap_err().code, PARSE_ERROR);
+        assert_eq!(
+            parse_request(r#"{"id":1}"#).unwrap_err().code,
+            INVALID_REQUEST
+        );
+
+        let request =
+            parse_request(r#"{"id":1,"method":"queryBlock","params":{"pos":[1,2]}}"#).unwrap();
+        assert_eq!(
+            params::<QueryBlockParams>(&request).unwrap_err().code,
+            INVALID_PARAMS
+        );
+    }
+
+    #[test]
+    fn test_response_shapes() {
+        assert_eq!(
+            response(json!(1), Ok(json!({ "passed": 2 }))),
+            json!({ "jsonrpc": "2.0", "id": 1, "result": { "passed": 2 } })
+        );
+        assert_eq!(
+            response(
+                json!(2),
+                Err(RpcError::new(METHOD_NOT_FOUND, "unknown method: x"))
+            ),
+            json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "error": { "code": METHOD_NOT_FOUND, "message": "unknown method: x" },
+            })
+        );
+    }
+}
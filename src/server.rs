@@ -0,0 +1,443 @@
+//! `--launch-server`: run a local Java server as a child process for the test run.
+//!
+//! The server directory gets an accepted EULA and a `server.properties` set up for
+//! testing (offline mode so the bot can join, a flat world, a free port, and RCON on
+//! another free port when asked). Startup is complete at the "Done" log line; the bot
+//! is then made an operator through the console. Everything the server prints is
+//! written to a log file as it arrives and the latest lines are kept for the report.
+//! The server is stopped with `stop` when the run ends, or killed if it doesn't exit
+//! in time.
+
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::bot::BOT_NAME;
+
+const STARTUP_TIMEOUT_SECS: u64 = 300;
+const CONSOLE_TIMEOUT_SECS: u64 = 10;
+const SHUTDOWN_TIMEOUT_SECS: u64 = 60;
+const SHUTDOWN_POLL_MS: u64 = 100;
+const OUTPUT_POLL_MS: u64 = 50;
+/// View and simulation distance of launched servers
+pub const LAUNCH_CHUNK_DISTANCE: u32 = 10;
+const SERVER_LOG: &str = "flintmc-server.log";
+/// Lines of output kept in memory for the report; the log file has all of them
+const MAX_OUTPUT_LINES: usize = 10_000;
+
+/// A server started by `flintmc`, stopped when dropped
+pub struct LocalServer {
+    child: Child,
+    stdin: ChildStdin,
+    output: Arc<parking_lot::Mutex<Output>>,
+    /// Lines of `output` already searched by `wait_for_line`, counted from the start
+    seen: usize,
+    dir: PathBuf,
+    port: u16,
+    rcon: Option<Rcon>,
+}
+
+/// RCON settings written to `server.properties`
+struct Rcon {
+    port: u16,
+    password: String,
+}
+
+/// What the server printed: every line goes to the log file, the latest ones are kept
+struct Output {
+    lines: VecDeque<String>,
+    total: usize,
+    log: LineWriter<File>,
+}
+
+impl Output {
+    fn push(&mut self, line: String) {
+        if let Err(error) = writeln!(self.log, "{line}") {
+            tracing::warn!("Failed to write the server log: {}", error);
+        }
+        if self.lines.len() == MAX_OUTPUT_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+        self.total += 1;
+    }
+
+    /// Kept lines printed after the first `seen`
+    fn since(&self, seen: usize) -> impl Iterator<Item = &String> {
+        let dropped = self.total - self.lines.len();
+        self.lines.iter().skip(seen.saturating_sub(dropped))
+    }
+}
+
+impl LocalServer {
+    /// Start the server jar at `path`, or the one in the directory `path`, and wait
+    /// until it accepts players. With `enable_rcon`, RCON listens on another free port
+    /// with a fresh password.
+    pub fn launch(path: &Path, enable_rcon: bool) -> Result<Self> {
+        let (dir, jar) = resolve_jar(path)?;
+        let [port, rcon_port] = free_ports()?;
+        let rcon = enable_rcon.then(|| Rcon {
+            port: rcon_port,
+            password: random_password(),
+        });
+        std::fs::write(dir.join("eula.txt"), "eula=true\n").context("accepting the EULA")?;
+        let properties_path = dir.join("server.properties");
+        let existing = std::fs::read_to_string(&properties_path).unwrap_or_default();
+        std::fs::write(
+            &properties_path,
+            update_properties(&existing, &test_properties(port, rcon.as_ref())),
+        )
+        .with_context(|| format!("writing {}", properties_path.display()))?;
+
+        // The server runs in its directory, so a relative jar path would miss
+        let jar = jar
+            .canonicalize()
+            .with_context(|| format!("resolving {}", jar.display()))?;
+        let mut child = Command::new(java_executable())
+            .arg("-jar")
+            .arg(&jar)
+            .arg("nogui")
+            .current_dir(&dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("starting {}", jar.display()))?;
+
+        let log_path = dir.join(SERVER_LOG);
+        let log =
+            File::create(&log_path).with_context(|| format!("creating {}", log_path.display()))?;
+        let output = Arc::new(parking_lot::Mutex::new(Output {
+            lines: VecDeque::new(),
+            total: 0,
+            log: LineWriter::new(log),
+        }));
+        let stdout = child.stdout.take().context("server stdout")?;
+        let stderr = child.stderr.take().context("server stderr")?;
+        for stream in [
+            Box::new(stdout) as Box<dyn std::io::Read + Send>,
+            Box::new(stderr),
+        ] {
+            let output = output.clone();
+            std::thread::spawn(move || {
+                for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                    output.lock().push(line);
+                }
+            });
+        }
+        let stdin = child.stdin.take().context("server stdin")?;
+
+        let mut server = Self {
+            child,
+            stdin,
+            output,
+            seen: 0,
+            dir,
+            port,
+            rcon,
+        };
+        server
+            .wait_for_line(Duration::from_secs(STARTUP_TIMEOUT_SECS), |line| {
+                line.contains("Done (")
+            })
+            .context("waiting for the server to start")?;
+        server.console(&format!("op {BOT_NAME}"))?;
+        server
+            .wait_for_line(Duration::from_secs(CONSOLE_TIMEOUT_SECS), |line| {
+                line.contains(&format!("Made {BOT_NAME} a server operator"))
+                    || line.contains("Nothing changed")
+            })
+            .context("making the bot an operator")?;
+        Ok(server)
+    }
+
+    /// Address the bot connects to
+    pub fn address(&self) -> String {
+        format!("localhost:{}", self.port)
+    }
+
+    /// RCON address and password, when the server was launched with RCON
+    pub fn rcon(&self) -> Option<(String, String)> {
+        self.rcon
+            .as_ref()
+            .map(|rcon| (format!("localhost:{}", rcon.port), rcon.password.clone()))
+    }
+
+    /// Run a command on the server console
+    fn console(&mut self, command: &str) -> Result<()> {
+        writeln!(self.stdin, "{command}")
+            .and_then(|()| self.stdin.flush())
+            .context("writing to the server console")
+    }
+
+    /// Wait for a line matching `matches`, failing early if the server exits
+    fn wait_for_line(&mut self, timeout: Duration, matches: impl Fn(&str) -> bool) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            {
+                let output = self.output.lock();
+                let found = output.since(self.seen).any(|line| matches(line));
+                self.seen = output.total;
+                if found {
+                    return Ok(());
+                }
+            }
+            if let Some(status) = self.child.try_wait()? {
+                anyhow::bail!("server exited with {status}{}", self.tail());
+            }
+            if Instant::now() >= deadline {
+                anyhow::bail!("timed out after {}s{}", timeout.as_secs(), self.tail());
+            }
+            std::thread::sleep(Duration::from_millis(OUTPUT_POLL_MS));
+        }
+    }
+
+    /// The last lines of output, for errors
+    fn tail(&self) -> String {
+        let output = self.output.lock();
+        output
+            .lines
+            .iter()
+            .skip(output.lines.len().saturating_sub(10))
+            .map(|line| format!("\n  {line}"))
+            .collect()
+    }
+
+    /// Stop the server and return what it printed: the latest lines, with all of them
+    /// in `flintmc-server.log` in the server directory
+    pub fn stop(mut self) -> Result<ServerLog> {
+        self.shut_down()?;
+        let mut output = self.output.lock();
+        output.log.flush().context("writing the server log")?;
+        let lines: Vec<String> = std::mem::take(&mut output.lines).into();
+        Ok(ServerLog {
+            omitted: output.total - lines.len(),
+            lines,
+            path: self.dir.join(SERVER_LOG),
+        })
+    }
+
+    fn shut_down(&mut self) -> Result<()> {
+        if self.child.try_wait()?.is_some() {
+            return Ok(());
+        }
+        // The console may already be gone; the kill below still ends the server
+        let _ = self.console("stop");
+        let deadline = Instant::now() + Duration::from_secs(SHUTDOWN_TIMEOUT_SECS);
+        while Instant::now() < deadline {
+            if self.child.try_wait()?.is_some() {
+                return Ok(());
+            }
+            std::thread::sleep(Duration::from_millis(SHUTDOWN_POLL_MS));
+        }
+        self.child.kill().context("killing the server")?;
+        self.child.wait()?;
+        Ok(())
+    }
+}
+
+impl Drop for LocalServer {
+    fn drop(&mut self) {
+        if let Err(error) = self.shut_down() {
+            tracing::warn!("Failed to stop the launched server: {:#}", error);
+        }
+    }
+}
+
+/// What a launched server printed over its lifetime
+pub struct ServerLog {
+    /// The latest lines, at most `MAX_OUTPUT_LINES`
+    pub lines: Vec<String>,
+    /// Earlier lines only found in the log file
+    pub omitted: usize,
+    pub path: PathBuf,
+}
+
+/// The server directory and jar for `path`: a jar, or a directory holding
+/// `server.jar` or exactly one other jar
+fn resolve_jar(path: &Path) -> Result<(PathBuf, PathBuf)> {
+    if path.is_file() {
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        return Ok((dir, path.to_path_buf()));
+    }
+    if !path.is_dir() {
+        anyhow::bail!("{} is neither a server jar nor a directory", path.display());
+    }
+    let default = path.join("server.jar");
+    if default.is_file() {
+        return Ok((path.to_path_buf(), default));
+    }
+    let jars: Vec<_> = std::fs::read_dir(path)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|entry| entry.extension().is_some_and(|ext| ext == "jar"))
+        .collect();
+    match jars.as_slice() {
+        [jar] => Ok((path.to_path_buf(), jar.clone())),
+        [] => anyhow::bail!("no server jar in {}", path.display()),
+        _ => anyhow::bail!(
+            "{} holds several jars; name the server jar server.jar or pass it directly",
+            path.display()
+        ),
+    }
+}
+
+fn java_executable() -> PathBuf {
+    match std::env::var_os("JAVA_HOME") {
+        Some(home) => Path::new(&home).join("bin").join("java"),
+        None => PathBuf::from("java"),
+    }
+}
+
+/// Distinct ports nothing listens on right now
+fn free_ports<const N: usize>() -> Result<[u16; N]> {
+    // Every listener stays open until all ports are picked, so none repeats
+    let listeners = (0..N)
+        .map(|_| std::net::TcpListener::bind("127.0.0.1:0").context("finding a free port"))
+        .collect::<Result<Vec<_>>>()?;
+    let mut ports = [0; N];
+    for (port, listener) in ports.iter_mut().zip(&listeners) {
+        *port = listener.local_addr()?.port();
+    }
+    Ok(ports)
+}
+
+/// A password for this run's RCON listener
+fn random_password() -> String {
+    let state = std::collections::hash_map::RandomState::new();
+    let mut hasher = state.build_hasher();
+    hasher.write_u32(std::process::id());
+    format!(
+        "{:016x}{:016x}",
+        hasher.finish(),
+        state.hash_one(hasher.finish())
+    )
+}
+
+fn test_properties(port: u16, rcon: Option<&Rcon>) -> Vec<(&'static str, String)> {
+    let mut properties = vec![
+        ("online-mode", "false".to_string()),
+        ("level-type", "minecraft\\:flat".to_string()),
+        ("generate-structures", "false".to_string()),
+        ("spawn-protection", "0".to_string()),
+        ("view-distance", LAUNCH_CHUNK_DISTANCE.to_string()),
+        ("simulation-distance", LAUNCH_CHUNK_DISTANCE.to_string()),
+        ("server-port", port.to_string()),
+    ];
+    if let Some(rcon) = rcon {
+        properties.extend([
+            ("enable-rcon", "true".to_string()),
+            ("rcon.port", rcon.port.to_string()),
+            ("rcon.password", rcon.password.clone()),
+        ]);
+    }
+    properties
+}
+
+/// `existing` properties with `overrides` set, keeping every other line as it was
+fn update_properties(existing: &str, overrides: &[(&str, String)]) -> String {
+    let mut pending: Vec<_> = overrides.iter().collect();
+    let mut updated = String::new();
+    for line in existing.lines() {
+        let key = line.split_once('=').map(|(key, _)| key.trim());
+        match pending.iter().position(|(name, _)| Some(*name) == key) {
+            Some(index) => {
+                let (name, value) = pending.remove(index);
+                updated.push_str(&format!("{name}={value}\n"));
+            }
+            None => {
+                updated.push_str(line);
+                updated.push('\n');
+            }
+        }
+    }
+    for (name, value) in pending {
+        updated.push_str(&format!("{name}={value}\n"));
+    }
+    updated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_properties_keeps_other_settings() {
+        let existing = "#Minecraft server properties\nmotd=hello\nonline-mode=true\n";
+        let updated = update_properties(
+            existing,
+            &[
+                ("online-mode", "false".to_string()),
+                ("server-port", "25570".to_string()),
+            ],
+        );
+        assert_eq!(
+            updated,
+            "#Minecraft server properties\nmotd=hello\nonline-mode=false\nserver-port=25570\n"
+        );
+    }
+
+    #[test]
+    fn test_output_keeps_the_latest_lines_and_logs_all() {
+        let path = std::env::temp_dir().join(format!("flintmc-output-test-{}", std::process::id()));
+        let mut output = Output {
+            lines: VecDeque::new(),
+            total: 0,
+            log: LineWriter::new(File::create(&path).unwrap()),
+        };
+        for line in 0..MAX_OUTPUT_LINES + 2 {
+            output.push(line.to_string());
+        }
+        assert_eq!(output.lines.len(), MAX_OUTPUT_LINES);
+        assert_eq!(output.lines.front().map(String::as_str), Some("2"));
+        // Lines already dropped are skipped; later ones are still found
+        assert_eq!(output.since(1).next().map(String::as_str), Some("2"));
+        assert_eq!(output.since(MAX_OUTPUT_LINES + 1).count(), 1);
+        output.log.flush().unwrap();
+        let logged = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(logged.lines().count(), MAX_OUTPUT_LINES + 2);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_rcon_is_enabled_when_asked() {
+        let rcon = Rcon {
+            port: 25576,
+            password: "secret".to_string(),
+        };
+        let properties = test_properties(25565, Some(&rcon));
+        assert!(properties.contains(&("enable-rcon", "true".to_string())));
+        assert!(properties.contains(&("rcon.port", "25576".to_string())));
+        assert!(properties.contains(&("rcon.password", "secret".to_string())));
+        assert!(
+            !test_properties(25565, None)
+                .iter()
+                .any(|(key, _)| key.starts_with("rcon") || *key == "enable-rcon")
+        );
+    }
+
+    #[test]
+    fn test_resolve_jar_in_directory() {
+        let dir = std::env::temp_dir().join(format!("flintmc-server-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(resolve_jar(&dir).is_err());
+
+        std::fs::write(dir.join("paper-1.21.jar"), "").unwrap();
+        assert_eq!(resolve_jar(&dir).unwrap().1, dir.join("paper-1.21.jar"));
+
+        std::fs::write(dir.join("other.jar"), "").unwrap();
+        assert!(resolve_jar(&dir).is_err());
+        std::fs::write(dir.join("server.jar"), "").unwrap();
+        assert_eq!(resolve_jar(&dir).unwrap().1, dir.join("server.jar"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -2,15 +2,41 @@
 
 use flint_core::spatial::calculate_test_offsets_for_batch_default;
 use flint_core::test_spec::TestSpec;
+use std::path::{Path, PathBuf};
+
+/// A test to lay out: a spec alone, or with the file it was loaded from
+pub trait BatchTest {
+    fn spec(&self) -> &TestSpec;
+
+    fn file(&self) -> Option<&Path> {
+        None
+    }
+}
+
+impl BatchTest for TestSpec {
+    fn spec(&self) -> &TestSpec {
+        self
+    }
+}
+
+impl BatchTest for (TestSpec, PathBuf) {
+    fn spec(&self) -> &TestSpec {
+        &self.0
+    }
+
+    fn file(&self) -> Option<&Path> {
+        Some(&self.1)
+    }
+}
 
 /// Partition tests by their resolved world configuration while preserving the order in
 /// which each configuration and test first appeared.
-pub fn group_tests_by_world_config(tests: Vec<TestSpec>) -> Vec<Vec<TestSpec>> {
-    let mut groups: Vec<Vec<TestSpec>> = Vec::new();
+pub fn group_tests_by_world_config<T: BatchTest>(tests: Vec<T>) -> Vec<Vec<T>> {
+    let mut groups: Vec<Vec<T>> = Vec::new();
     for test in tests {
         if let Some(group) = groups
             .iter_mut()
-            .find(|group| group[0].world_config() == test.world_config())
+            .find(|group| group[0].spec().world_config() == test.spec().world_config())
         {
             group.push(test);
         } else {
@@ -42,22 +68,23 @@ pub fn max_extent_from_origin(tests: &[(TestSpec, [i32; 3])]) -> i32 {
 
 /// Split a batch so every sub-batch fits within the server's simulation distance from the
 /// layout center (origin). Tests stay parallel within each sub-batch.
-pub fn split_tests_by_simulation_distance(
-    tests: Vec<TestSpec>,
+pub fn split_tests_by_simulation_distance<T: BatchTest>(
+    tests: Vec<T>,
     simulation_distance: u32,
-) -> Vec<Vec<TestSpec>> {
+) -> Vec<Vec<T>> {
     if tests.is_empty() {
         return Vec::new();
     }
 
     let max_radius = simulation_radius_blocks(simulation_distance);
-    let mut batches: Vec<Vec<TestSpec>> = Vec::new();
-    let mut current: Vec<TestSpec> = Vec::new();
+    let mut batches: Vec<Vec<T>> = Vec::new();
+    let mut current: Vec<T> = Vec::new();
 
     for test in tests {
         current.push(test);
-        let offsets = calculate_test_offsets_for_batch_default(&current);
-        let paired: Vec<(TestSpec, [i32; 3])> = current.iter().cloned().zip(offsets).collect();
+        let specs: Vec<TestSpec> = current.iter().map(|test| test.spec().clone()).collect();
+        let offsets = calculate_test_offsets_for_batch_default(&specs);
+        let paired: Vec<(TestSpec, [i32; 3])> = specs.into_iter().zip(offsets).collect();
 
         if max_extent_from_origin(&paired) > max_radius && current.len() > 1 {
             let overflow = current.pop().expect("current has at least two tests");
@@ -144,6 +171,25 @@ mod tests {
         assert_eq!(groups[1][0].name, "night");
     }
 
+    #[test]
+    fn grouping_keeps_each_test_with_its_file() {
+        let day = test_spec("same", [[0, 0, 0], [1, 1, 1]]);
+        let mut night = test_spec("same", [[0, 0, 0], [1, 1, 1]]);
+        night.setup.as_mut().unwrap().world.time = "minecraft:night".to_string();
+
+        let groups = group_tests_by_world_config(vec![
+            (night, PathBuf::from("b/same.json")),
+            (day, PathBuf::from("a/same.json")),
+        ]);
+
+        assert_eq!(groups[0][0].file(), Some(Path::new("b/same.json")));
+        assert_eq!(
+            groups[0][0].spec().setup.as_ref().unwrap().world.time,
+            "minecraft:night"
+        );
+        assert_eq!(groups[1][0].file(), Some(Path::new("a/same.json")));
+    }
+
     #[test]
     fn split_keeps_player_timelines_in_parallel_batch() {
         let mut first = test_spec("first", [[0, 0, 0], [1, 1, 1]]);
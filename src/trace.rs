@@ -0,0 +1,513 @@
+//! Offline tools for `--emit-events` traces.
+//!
+//! A trace is the JSONL written by `executor::events`. Every `tick` event holds the
+//! block deltas observed after that tick was stepped, in test-local coordinates, so
+//! the state it describes is what a test sees at the following tick.
+
+use anyhow::{Context, Result};
+use flint_core::test_spec::{
+    ActionType, AssertType, BlockCheck, BlockSpec, CleanupSpec, SetupSpec, TestSpec, TickSpec,
+    TimelineEntry,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+use crate::executor::block::make_block;
+
+const AIR: &str = "minecraft:air";
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TraceEvent {
+    RunStarted {
+        test: String,
+        region: [[i32; 3]; 2],
+    },
+    Tick {
+        tick: u32,
+        set: Vec<TraceBlock>,
+        removed: Vec<[i32; 3]>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct TraceBlock {
+    pos: [i32; 3],
+    id: String,
+}
+
+/// Block changes observed after one tick. Removed blocks are reported as air.
+pub struct TickDelta {
+    pub tick: u32,
+    pub changes: Vec<([i32; 3], String)>,
+}
+
+/// A parsed event trace
+pub struct Trace {
+    pub test: Option<String>,
+    pub region: Option<[[i32; 3]; 2]>,
+    pub ticks: Vec<TickDelta>,
+}
+
+impl Trace {
+    pub fn read(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("reading trace {}", path.display()))?;
+        Self::parse(&contents).with_context(|| format!("parsing trace {}", path.display()))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut trace = Trace {
+            test: None,
+            region: None,
+            ticks: Vec::new(),
+        };
+
+        for (line_idx, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let event: TraceEvent = serde_json::from_str(line)
+                .with_context(|| format!("invalid event on line {}", line_idx + 1))?;
+            match event {
+                TraceEvent::RunStarted { test, region } => {
+                    trace.test = Some(test);
+                    trace.region = Some(region);
+                }
+                TraceEvent::Tick { tick, set, removed } => {
+                    let mut changes: Vec<_> =
+                        set.into_iter().map(|block| (block.pos, block.id)).collect();
+                    changes.extend(removed.into_iter().map(|pos| (pos, AIR.to_string())));
+                    changes.sort();
+                    trace.ticks.push(TickDelta { tick, changes });
+                }
+                TraceEvent::Other => {}
+            }
+        }
+
+        if trace.ticks.is_empty() {
+            anyhow::bail!("trace contains no tick events");
+        }
+        Ok(trace)
+    }
+}
+
+/// A position whose block differs between two traces
+#[derive(Debug, Clone, Serialize)]
+pub struct Divergence {
+    pub pos: [i32; 3],
+    pub left: String,
+    pub right: String,
+}
+
+/// Every divergent position after one tick
+#[derive(Debug, Serialize)]
+pub struct TickDivergence {
+    pub tick: u32,
+    pub positions: Vec<Divergence>,
+}
+
+/// Result of aligning two traces tick by tick
+#[derive(Debug, Serialize)]
+pub struct TraceDiff {
+    pub diverged: bool,
+    /// Ticks present in both traces
+    pub ticks_compared: usize,
+    /// Ticks present in only one of the traces
+    pub unmatched_ticks: Vec<u32>,
+    pub first_divergence: Option<FirstDivergence>,
+    pub divergent_ticks: Vec<TickDivergence>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FirstDivergence {
+    pub tick: u32,
+    #[serde(flatten)]
+    pub divergence: Divergence,
+}
+
+/// Align the `tick` events of two traces and report where their block states diverge.
+///
+/// Each trace's state is rebuilt from its deltas, so a divergence persists until both
+/// traces agree on the position again.
+pub fn diff_traces(left: &Trace, right: &Trace) -> TraceDiff {
+    let mut ticks: BTreeMap<u32, (Option<&TickDelta>, Option<&TickDelta>)> = BTreeMap::new();
+    for delta in &left.ticks {
+        ticks.entry(delta.tick).or_default().0 = Some(delta);
+    }
+    for delta in &right.ticks {
+        ticks.entry(delta.tick).or_default().1 = Some(delta);
+    }
+
+    let mut left_state: HashMap<[i32; 3], &str> = HashMap::new();
+    let mut right_state: HashMap<[i32; 3], &str> = HashMap::new();
+    let mut divergent: BTreeSet<[i32; 3]> = BTreeSet::new();
+
+    let mut ticks_compared = 0;
+    let mut unmatched_ticks = Vec::new();
+    let mut divergent_ticks = Vec::new();
+    for (tick, (left_delta, right_delta)) in ticks {
+        let mut touched = Vec::new();
+        for (delta, state) in [
+            (left_delta, &mut left_state),
+            (right_delta, &mut right_state),
+        ] {
+            for (pos, block) in delta.iter().flat_map(|delta| &delta.changes) {
+                state.insert(*pos, block.as_str());
+                touched.push(*pos);
+            }
+        }
+        for pos in touched {
+            let left_block = left_state.get(&pos).copied().unwrap_or(AIR);
+            let right_block = right_state.get(&pos).copied().unwrap_or(AIR);
+            if left_block == right_block {
+                divergent.remove(&pos);
+            } else {
+                divergent.insert(pos);
+            }
+        }
+
+        if left_delta.is_none() || right_delta.is_none() {
+            unmatched_ticks.push(tick);
+            continue;
+        }
+        ticks_compared += 1;
+        if divergent.is_empty() {
+            continue;
+        }
+        divergent_ticks.push(TickDivergence {
+            tick,
+            positions: divergent
+                .iter()
+                .map(|pos| Divergence {
+                    pos: *pos,
+                    left: left_state.get(pos).copied().unwrap_or(AIR).to_string(),
+                    right: right_state.get(pos).copied().unwrap_or(AIR).to_string(),
+                })
+                .collect(),
+        });
+    }
+
+    let first_divergence = divergent_ticks.first().map(|tick| FirstDivergence {
+        tick: tick.tick,
+        divergence: tick.positions[0].clone(),
+    });
+    TraceDiff {
+        diverged: !divergent_ticks.is_empty() || !unmatched_ticks.is_empty(),
+        ticks_compared,
+        unmatched_ticks,
+        first_divergence,
+        divergent_ticks,
+    }
+}
+
+/// Options for `trace-to-test`
+pub struct TraceToTestOptions {
+    /// Test ticks to assert at. Empty means every tick at which something changed.
+    pub ticks: Vec<u32>,
+    /// Keep only positions that hold one of these blocks at some point in the trace
+    pub filters: Vec<String>,
+}
+
+/// Generate golden block assertions from a trace.
+///
+/// Tick events diff against the region as the run started: cleared, so all air. The
+/// first one is asserted like the rest. Without explicit ticks, each tick that changed
+/// something asserts the positions it changed; with explicit ticks, every changed
+/// position is asserted at each of them.
+pub fn golden_assertions(
+    trace: &Trace,
+    options: &TraceToTestOptions,
+) -> Result<Vec<TimelineEntry>> {
+    let filters: Vec<String> = options
+        .filters
+        .iter()
+        .map(|filter| normalize_block_id(filter))
+        .collect();
+
+    // Every state each position takes, for filtering
+    let mut history: HashMap<[i32; 3], Vec<&str>> = HashMap::new();
+    for delta in &trace.ticks {
+        for (pos, block) in &delta.changes {
+            history.entry(*pos).or_default().push(block.as_str());
+        }
+    }
+    let keep = |pos: &[i32; 3]| {
+        filters.is_empty()
+            || history[pos].iter().any(|block| {
+                filters
+                    .iter()
+                    .any(|filter| block_id(block) == filter.as_str())
+            })
+    };
+
+    let changed: BTreeSet<[i32; 3]> = trace
+        .ticks
+        .iter()
+        .flat_map(|delta| delta.changes.iter().map(|(pos, _)| *pos))
+        .filter(keep)
+        .collect();
+
+    let mut entries = Vec::new();
+    if options.ticks.is_empty() {
+        for delta in &trace.ticks {
+            let checks: Vec<_> = delta
+                .changes
+                .iter()
+                .filter(|(pos, _)| changed.contains(pos))
+                .map(|(pos, block)| block_check(*pos, block))
+                .collect();
+            if !checks.is_empty() {
+                entries.push(assert_entry(delta.tick + 1, checks));
+            }
+        }
+        return Ok(entries);
+    }
+
+    let first_tick = trace.ticks[0].tick;
+    let mut ticks = options.ticks.clone();
+    ticks.sort_unstable();
+    ticks.dedup();
+
+    let mut state: HashMap<[i32; 3], &str> = HashMap::new();
+    let mut deltas = trace.ticks.iter().peekable();
+    for tick in ticks {
+        if tick <= first_tick {
+            anyhow::bail!(
+                "tick {tick} is not covered by the trace (first observed state is tick {})",
+                first_tick + 1
+            );
+        }
+        while let Some(delta) = deltas.next_if(|delta| delta.tick < tick) {
+            for (pos, block) in &delta.changes {
+                state.insert(*pos, block.as_str());
+            }
+        }
+        let checks: Vec<_> = changed
+            .iter()
+            .map(|pos| block_check(*pos, state.get(pos).copied().unwrap_or(AIR)))
+            .collect();
+        if !checks.is_empty() {
+            entries.push(assert_entry(tick, checks));
+        }
+    }
+    Ok(entries)
+}
+
+/// Write the golden assertions of `trace_path` into `test_path`, creating the test if
+/// it does not exist. Returns the number of block checks written.
+pub fn trace_to_test(
+    trace_path: &Path,
+    test_path: &Path,
+    options: &TraceToTestOptions,
+) -> Result<usize> {
+    let trace = Trace::read(trace_path)?;
+    let entries = golden_assertions(&trace, options)?;
+    let check_count = entries
+        .iter()
+        .map(|entry| match &entry.action_type {
+            ActionType::Assert { checks } => checks.len(),
+            _ => 0,
+        })
+        .sum();
+
+    let mut spec = if test_path.exists() {
+        TestSpec::from_file(test_path, false)
+            .with_context(|| format!("loading test {}", test_path.display()))?
+    } else {
+        new_test_spec(&trace, test_path)?
+    };
+    spec.timeline.extend(entries);
+
+    if let Some(parent) = test_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let json_str = serde_json::to_string_pretty(&spec)?;
+    std::fs::write(test_path, json_str)
+        .with_context(|| format!("writing test {}", test_path.display()))?;
+
+    Ok(check_count)
+}
+
+fn new_test_spec(trace: &Trace, test_path: &Path) -> Result<TestSpec> {
+    let region = trace
+        .region
+        .context("trace has no run_started event to take the cleanup region from")?;
+    let name = test_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .context("test path has no file name")?;
+
+    Ok(TestSpec {
+        flint_version: Some("1.0.0".to_string()),
+        name,
+        description: trace
+            .test
+            .as_ref()
+            .map(|test| format!("Golden assertions from a trace of {test}")),
+        tags: vec!["trace".to_string()],
+        minecraft_ids: Vec::new(),
+        dependencies: Vec::new(),
+        setup: Some(SetupSpec {
+            cleanup: Some(CleanupSpec { region }),
+            player: None,
+            world: Default::default(),
+        }),
+        timeline: Vec::new(),
+        breakpoints: Vec::new(),
+    })
+}
+
+fn block_check(pos: [i32; 3], block: &str) -> AssertType {
+    AssertType::Block(BlockCheck {
+        pos,
+        is: BlockSpec::Single(make_block(block)),
+    })
+}
+
+fn assert_entry(tick: u32, checks: Vec<AssertType>) -> TimelineEntry {
+    TimelineEntry {
+        at: TickSpec::Single(tick),
+        action_type: ActionType::Assert { checks },
+    }
+}
+
+/// Block id without its properties
+fn block_id(block: &str) -> &str {
+    block.split_once('[').map_or(block, |(id, _)| id)
+}
+
+fn normalize_block_id(block: &str) -> String {
+    if block.contains(':') {
+        block.to_string()
+    } else {
+        format!("minecraft:{block}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACE: &str = r#"{"type":"run_started","test":"lamp","region":[[0,0,0],[2,1,0]]}
+{"type":"tick","tick":0,"set":[{"pos":[0,0,0],"id":"minecraft:lever[powered=true]"},{"pos":[1,0,0],"id":"minecraft:redstone_lamp[lit=false]"},{"pos":[2,0,0],"id":"minecraft:stone"}],"removed":[]}
+{"type":"tick","tick":1,"set":[],"removed":[]}
+{"type":"tick","tick":2,"set":[{"pos":[1,0,0],"id":"minecraft:redstone_lamp[lit=true]"},{"pos":[2,1,0],"id":"minecraft:sand"}],"removed":[]}
+{"type":"assert","tick":3,"pos":[1,0,0],"passed":true}
+{"type":"tick","tick":3,"set":[],"removed":[[2,1,0]]}
+{"type":"run_completed","asserts_passed":1,"asserts_failed":0}
+"#;
+
+    fn checked_positions(entry: &TimelineEntry) -> Vec<[i32; 3]> {
+        let ActionType::Assert { checks } = &entry.action_type else {
+            panic!("expected assert entry");
+        };
+        checks
+            .iter()
+            .map(|check| match check {
+                AssertType::Block(block) => block.pos,
+                _ => panic!("expected block check"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_traces_reports_first_divergence_until_states_agree() {
+        let left = Trace::parse(TRACE).unwrap();
+        let right = Trace::parse(
+            r#"{"type":"tick","tick":0,"set":[{"pos":[0,0,0],"id":"minecraft:lever[powered=true]"},{"pos":[1,0,0],"id":"minecraft:redstone_lamp[lit=false]"},{"pos":[2,0,0],"id":"minecraft:stone"}],"removed":[]}
+{"type":"tick","tick":1,"set":[],"removed":[]}
+{"type":"tick","tick":2,"set":[{"pos":[2,1,0],"id":"minecraft:sand"}],"removed":[]}
+{"type":"tick","tick":3,"set":[{"pos":[1,0,0],"id":"minecraft:redstone_lamp[lit=true]"}],"removed":[[2,1,0]]}
+{"type":"tick","tick":4,"set":[],"removed":[]}
+"#,
+        )
+        .unwrap();
+
+        let diff = diff_traces(&left, &right);
+        assert!(diff.diverged);
+        assert_eq!(diff.ticks_compared, 4);
+        assert_eq!(diff.unmatched_ticks, vec![4]);
+        assert_eq!(diff.divergent_ticks.len(), 1);
+
+        let first = diff.first_divergence.unwrap();
+        assert_eq!(first.tick, 2);
+        assert_eq!(first.divergence.pos, [1, 0, 0]);
+        assert_eq!(first.divergence.left, "minecraft:redstone_lamp[lit=true]");
+        assert_eq!(first.divergence.right, "minecraft:redstone_lamp[lit=false]");
+
+        assert!(!diff_traces(&left, &left).diverged);
+    }
+
+    #[test]
+    fn test_golden_assertions_follow_changes() {
+        let trace = Trace::parse(TRACE).unwrap();
+        assert_eq!(trace.region, Some([[0, 0, 0], [2, 1, 0]]));
+
+        let options = TraceToTestOptions {
+            ticks: Vec::new(),
+            filters: Vec::new(),
+        };
+        let entries = golden_assertions(&trace, &options).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert!(matches!(entries[0].at, TickSpec::Single(1)));
+        assert_eq!(
+            checked_positions(&entries[0]),
+            vec![[0, 0, 0], [1, 0, 0], [2, 0, 0]]
+        );
+        assert!(matches!(entries[1].at, TickSpec::Single(3)));
+        assert_eq!(checked_positions(&entries[1]), vec![[1, 0, 0], [2, 1, 0]]);
+        assert!(matches!(entries[2].at, TickSpec::Single(4)));
+        assert_eq!(checked_positions(&entries[2]), vec![[2, 1, 0]]);
+    }
+
+    #[test]
+    fn test_golden_assertions_include_the_first_delta() {
+        let trace = Trace::parse(
+            r#"{"type":"tick","tick":0,"set":[{"pos":[0,0,0],"id":"minecraft:stone"}],"removed":[]}
+{"type":"tick","tick":1,"set":[],"removed":[]}
+"#,
+        )
+        .unwrap();
+
+        let follow = TraceToTestOptions {
+            ticks: Vec::new(),
+            filters: Vec::new(),
+        };
+        let entries = golden_assertions(&trace, &follow).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(matches!(entries[0].at, TickSpec::Single(1)));
+        assert_eq!(checked_positions(&entries[0]), vec![[0, 0, 0]]);
+
+        let chosen = TraceToTestOptions {
+            ticks: vec![2],
+            filters: Vec::new(),
+        };
+        let entries = golden_assertions(&trace, &chosen).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(checked_positions(&entries[0]), vec![[0, 0, 0]]);
+    }
+
+    #[test]
+    fn test_golden_assertions_at_chosen_ticks_with_filter() {
+        let trace = Trace::parse(TRACE).unwrap();
+        let options = TraceToTestOptions {
+            ticks: vec![4, 2],
+            filters: vec!["redstone_lamp".to_string()],
+        };
+        let entries = golden_assertions(&trace, &options).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(matches!(entries[0].at, TickSpec::Single(2)));
+        assert!(matches!(entries[1].at, TickSpec::Single(4)));
+        assert_eq!(checked_positions(&entries[1]), vec![[1, 0, 0]]);
+
+        let too_early = TraceToTestOptions {
+            ticks: vec![0],
+            filters: Vec::new(),
+        };
+        assert!(golden_assertions(&trace, &too_early).is_err());
+    }
+}